@@ -36,3 +36,11 @@ pub fn read_user_input() -> io::Result<String> {
 
     Ok(cleaned)
 }
+
+
+/// Read a single line of input, without the line ending.
+pub fn read_line() -> io::Result<String> {
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
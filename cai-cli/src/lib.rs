@@ -1,13 +1,42 @@
 mod input_reading;
 mod text_enhance;
 
-use std::io::{ self, Write };
-use cai_core::ui_trait::{ UIBase, MsgRole, MsgType };
+use std::{ cell::RefCell, io::{ self, Write } };
+use cai_core::{
+    approval::{ ActionKind, ApprovalDecision, ApprovalRequest },
+    ui_trait::{ UIBase, MsgRole, MsgType },
+};
 use colored::Colorize;
-use text_enhance::enhanced_print;
+use text_enhance::{ enhanced_print, StreamPrinter };
+
+pub use text_enhance::highlight_html;
 
 pub struct UI;
 
+thread_local! {
+    /// Renderer of the assistant message currently being streamed, if any.
+    /// Thread-local because syntect's highlighter state isn't `Send`.
+    static STREAM: RefCell<Option<StreamPrinter>> = const { RefCell::new(None) };
+}
+
+/// Prints a unified diff: additions green, removals red, hunk headers cyan.
+fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else {
+            println!("{}", line);
+        }
+    }
+    println!();
+}
+
 impl UIBase for UI {
     fn init(&self) -> bool {
         true
@@ -44,6 +73,7 @@ impl UIBase for UI {
                             println!();
                         }
                     }
+                    MsgType::Diff(diff) => print_diff(&diff),
                 }
             }
 
@@ -63,7 +93,12 @@ impl UIBase for UI {
                             println!("{}{}", &header, child);
                         }
                     }
-                    
+                    MsgType::Diff(diff) => {
+                        let header = "[SYS] ".magenta().bold();
+                        println!("{}", &header);
+                        print_diff(&diff);
+                    }
+
                 }
             }
 
@@ -82,9 +117,61 @@ impl UIBase for UI {
                             println!("\t{}", child.red());
                         }
                     }
-                    
+                    MsgType::Diff(diff) => print_diff(&diff),
+
                 }
             }
         }
     }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn begin_assistant_message(&self) {
+        let header = "[A. ]".bold().blue();
+        println!("{}", &header);
+        STREAM.with(|s| *s.borrow_mut() = Some(StreamPrinter::new()));
+    }
+
+    fn assistant_delta(&self, delta: &str) {
+        STREAM.with(|s| {
+            if let Some(printer) = s.borrow_mut().as_mut() {
+                printer.push(delta);
+                io::stdout().flush().unwrap();
+            }
+        });
+    }
+
+    fn end_assistant_message(&self) {
+        if let Some(mut printer) = STREAM.with(|s| s.borrow_mut().take()) {
+            printer.finish();
+        }
+        println!("\x1b[0m"); // Reset color
+        println!();
+    }
+
+    fn request_approval(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        let action = match request.kind {
+            ActionKind::Read => "read",
+            ActionKind::Write => "write",
+            ActionKind::Execute => "run",
+        };
+        let header = "[APPROVE] ".yellow().bold();
+        println!("{}{} wants to {}:", &header, request.source, action);
+        for line in request.summary.lines() {
+            println!("    {}", line.cyan());
+        }
+        print!("{}", "[y] once  [s] for this session  [n] no  -- or type feedback to reject with it: ".yellow());
+        io::stdout().flush().unwrap();
+
+        let answer = input_reading::read_line().unwrap_or_default();
+        println!();
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => ApprovalDecision::ApproveOnce,
+            "s" | "session" => ApprovalDecision::ApproveSession,
+            "" | "n" | "no" => ApprovalDecision::Reject { feedback: None },
+            _ => ApprovalDecision::Reject { feedback: Some(answer.trim().to_string()) },
+        }
+    }
 }
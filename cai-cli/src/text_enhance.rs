@@ -9,12 +9,14 @@
 //! No public function signatures were changed.
 //! ===============================================================
 
+use cai_core::export::plain_code_html;
 use colored::*;
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
 use syntect::{
     easy::HighlightLines,
     highlighting::ThemeSet,
+    html::highlighted_html_for_string,
     parsing::SyntaxSet,
     util::{as_24_bit_terminal_escaped, LinesWithEndings},
 };
@@ -114,6 +116,102 @@ pub fn enhanced_print(resp: &str) {
     }
 }
 
+// ── Streaming ──────────────────────────────────────────────────
+/// Incremental counterpart of `enhanced_print`.
+///
+/// Chunks are buffered until a full line is available; each line is then
+/// rendered according to the block it belongs to (Markdown prose, a
+/// highlighted code fence, or a ✍ write-block). Highlighter state is kept
+/// across lines so multi-line constructs in code colour correctly.
+pub struct StreamPrinter {
+    pending: String,
+    block: StreamBlock,
+}
+
+enum StreamBlock {
+    Prose,
+    Code(Box<HighlightLines<'static>>),
+    Write,
+}
+
+impl StreamPrinter {
+    pub fn new() -> Self {
+        Self { pending: String::new(), block: StreamBlock::Prose }
+    }
+
+    /// Feed a chunk; every completed line is rendered immediately.
+    pub fn push(&mut self, chunk: &str) {
+        self.pending.push_str(chunk);
+        while let Some(newline) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=newline).collect();
+            self.render_line(&line);
+        }
+    }
+
+    /// Flush the trailing partial line and close any open block.
+    pub fn finish(&mut self) {
+        if !self.pending.is_empty() {
+            let mut line = std::mem::take(&mut self.pending);
+            line.push('\n');
+            self.render_line(&line);
+        }
+        if !matches!(self.block, StreamBlock::Prose) {
+            self.close_block();
+        }
+    }
+
+    fn render_line(&mut self, line: &str) {
+        let trimmed = line.trim_end();
+
+        if let Some(info) = trimmed.trim_start().strip_prefix("```") {
+            match self.block {
+                StreamBlock::Prose => self.open_block(info.trim()),
+                _ => self.close_block(),
+            }
+            return;
+        }
+
+        match &mut self.block {
+            StreamBlock::Prose => println!("{}", color_markdown(trimmed)),
+            StreamBlock::Write => println!("{}", trimmed),
+            StreamBlock::Code(hl) => {
+                let ranges = hl.highlight_line(line, &SYNTAX_SET).expect("highlight line");
+                print!("{}", as_24_bit_terminal_escaped(&ranges[..], true));
+            }
+        }
+    }
+
+    fn open_block(&mut self, info: &str) {
+        if let Some(path) = info.strip_prefix("write[").and_then(|p| p.strip_suffix(']')) {
+            println!("{}", format!("✍ write → {}", path.trim()).bold().yellow());
+            self.block = StreamBlock::Write;
+            return;
+        }
+
+        let lang = normalize_lang(info);
+        let syntax = SYNTAX_SET
+            .find_syntax_by_extension(&lang)
+            .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+        println!("{}", format!("┌─ {} ─┐", lang).dimmed());
+        self.block = StreamBlock::Code(Box::new(HighlightLines::new(syntax, &THEME)));
+    }
+
+    fn close_block(&mut self) {
+        if matches!(self.block, StreamBlock::Code(_)) {
+            print!("\x1b[0m");
+            println!("{}", "└────────┘".dimmed());
+        }
+        println!();
+        self.block = StreamBlock::Prose;
+    }
+}
+
+impl Default for StreamPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ── Helpers ─────────────────────────────────────────────────────
 fn color_markdown(md: &str) -> String {
     let mut out = MD_HEADER_RE
@@ -163,6 +261,16 @@ fn highlight_code(lang: &str, code: &str) {
     }
 }
 
+/// Highlight code as a standalone HTML `<pre>` block (for exports),
+/// with the same syntaxes and theme as the terminal.
+pub fn highlight_html(lang: &str, code: &str) -> String {
+    let syntax = SYNTAX_SET
+        .find_syntax_by_extension(&normalize_lang(lang))
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    highlighted_html_for_string(code, &SYNTAX_SET, syntax, &THEME)
+        .unwrap_or_else(|_| plain_code_html(lang, code))
+}
+
 /// Map loose language hints to syntect extensions.
 fn normalize_lang(lang: &str) -> String {
     match lang.to_lowercase().as_str() {
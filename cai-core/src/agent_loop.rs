@@ -0,0 +1,218 @@
+//! ===============================================================
+//! Agent Loop
+//!
+//! The response-processing half of the interaction loop: parses and
+//! executes the special blocks of an AI response, feeds the results
+//! back and follows the control tokens until user input is needed.
+//! Lives in the core (not in the binary) so tests can drive it.
+//! ===============================================================
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{
+    client::Client,
+    constants::{ CONTINUE_TOKEN, RESTART_TOKEN },
+    parsers::*,
+    prompt::{ get_resume_prompt, get_sys_prompt },
+    types::{ MessageRole, ToolCall, Usage },
+    ui_trait::{ MsgRole, MsgType, UIBase },
+};
+
+/// How the processing of a response ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopOutcome {
+    /// Nothing left to process -- ask the user for input.
+    AwaitInput,
+
+    /// The AI asked for a restart (`RESTART_TOKEN`); history is saved.
+    Restart,
+}
+
+/// Result of a non-interactive run (`run_once`).
+#[derive(Debug, Clone, Serialize)]
+pub struct OneShot {
+    /// Final assistant text, control tokens stripped.
+    pub text: String,
+
+    /// Tool calls executed during the run, in order.
+    pub tool_calls: Vec<ToolCall>,
+
+    /// Token usage of the whole run (model choosing and summaries included).
+    pub usage: Usage,
+
+    /// Set when a request failed; `text` is empty then.
+    pub error: Option<String>,
+}
+
+/// Opens the conversation: sends the system prompt on a fresh history,
+/// or the resume prompt otherwise. Returns the AI's first response.
+pub async fn start_session(ui: &dyn UIBase, assistant: &mut Client, cwd: &str) -> String {
+    if assistant.history.is_empty() {
+        let sys_prompt = get_sys_prompt(
+            memory_block(assistant, cwd).as_str(), // Relevant memories
+            cwd,
+            &assistant.config,
+        );
+        assistant.send_message(ui, MessageRole::System, &sys_prompt, None).await
+    } else {
+        let resume_prompt = get_resume_prompt(&memory_block(assistant, cwd));
+        assistant.send_message(ui, MessageRole::System, &resume_prompt, None).await
+    }
+}
+
+/// The memories most relevant to a session in `cwd`, ranked against the
+/// project's directory name.
+fn memory_block(assistant: &Client, cwd: &str) -> String {
+    let project = Path::new(cwd).file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    assistant.memory.lock().unwrap().prompt_block(&project)
+}
+
+/// Processes an AI response: runs its blocks, sends their results back and
+/// keeps going while the AI asks to continue (up to `max_continue` times).
+pub async fn process_response(
+    ui: &dyn UIBase,
+    assistant: &mut Client,
+    mut response: String
+) -> LoopOutcome {
+    /*
+    ---------------------------------------------------------------
+    | continues: Number of consecutive continues
+    | When it reaches config.max_continue, user input is required again
+    ---------------------------------------------------------------
+    */
+    let mut continues: usize = 0;
+
+    // Each iteration is one checkpoint turn: the turn is closed before
+    // asking for the next response, so tool calls made while producing it
+    // belong to the turn that processes it.
+    'processing_loop: loop {
+        /*
+        ---------------------------------------------------------------
+        | sys_message: Message that will be sent to AI after response processing
+        | When empty, no further processing is needed
+        | When not empty, triggers another AI processing round
+        ---------------------------------------------------------------
+        */
+        let mut sys_message: String = String::new();
+
+        // --- Parse and Execute Special Blocks ---
+
+        // Handles file write instructions
+        match assistant.workspace() {
+            Ok(workspace) => {
+                if let Err(e) = parse_write_block(ui, &response, &workspace, &mut assistant.approval) {
+                    // Add to response the failure message
+                    sys_message.push_str(&format!("[Write Block Failed]\n{}\n", e));
+                }
+            }
+            Err(e) => ui.print_message(MsgRole::Error, MsgType::Plain(format!("[ERROR] No workspace available: {:#}", e))),
+        }
+
+        parse_python_block(ui, &response, &mut assistant.approval, &mut sys_message); // Handles python code execution
+        match assistant.shell() {
+            Ok(shell) => parse_commands_block(ui, &response, &shell, &mut assistant.approval, &mut sys_message), // Handles shell commands
+            Err(e) => ui.print_message(MsgRole::Error, MsgType::Plain(format!("[ERROR] No shell available: {}", e))),
+        }
+        // parse_memory_block(ui, &response, assistant, &mut sys_message); // Handles memory updates
+
+        // * Token processing ----------------------------
+        if response.contains(RESTART_TOKEN) {
+            assistant.history.save();
+
+            ui.print_message(
+                MsgRole::System,
+                MsgType::Plain("[SYSTEM] Chat history saved. Restarting....".to_string()),
+            );
+
+            assistant.end_turn();
+            return LoopOutcome::Restart;
+        }
+
+        // --- Continue token logic ---
+        if response.contains(CONTINUE_TOKEN) {
+            ui.print_message(
+                MsgRole::System,
+                MsgType::Plain("[SYSTEM] Continuing".to_string()),
+            );
+
+            assistant.end_turn();
+            if sys_message.is_empty() {
+                response = assistant.send_message(ui, MessageRole::System, "[Continue]", None).await;
+            } else {
+                response = assistant.send_message(ui, MessageRole::System, &sys_message, None).await;
+            }
+            continues += 1;
+
+            // Enforce continue limit.
+            if continues >= assistant.config.max_continue {
+                assistant.send_message(
+                    ui,
+                    MessageRole::System,
+                    "[You've reached the maximum number of continues.]",
+                    None
+                ).await;
+                break 'processing_loop;
+            }
+
+            continue 'processing_loop;
+        }
+
+        // If there was a system message, send it and continue processing.
+        if sys_message.is_empty() {
+            break 'processing_loop;
+        }
+        assistant.end_turn();
+        response = assistant.send_message(ui, MessageRole::System, &sys_message, None).await;
+        continues = 0;
+    }
+
+    assistant.end_turn();
+    LoopOutcome::AwaitInput
+}
+
+/// Answers a single prompt without user interaction: the system prompt is
+/// added (not sent) on an empty history, then the prompt is sent and the
+/// response processed until no blocks, tool calls or continues remain.
+pub async fn run_once(
+    ui: &dyn UIBase,
+    assistant: &mut Client,
+    cwd: &str,
+    prompt: &str
+) -> OneShot {
+    if assistant.history.is_empty() {
+        let sys_prompt = get_sys_prompt(&memory_block(assistant, cwd), cwd, &assistant.config);
+        assistant.history.add_message(MessageRole::System, sys_prompt);
+    }
+
+    let usage_before = assistant.usage;
+    let calls_before = assistant.tool_calls.len();
+    assistant.last_error = None;
+
+    let response = assistant.send_message(ui, MessageRole::User, prompt, None).await;
+    if assistant.last_error.is_none() {
+        process_response(ui, assistant, response).await;
+    }
+
+    // The last assistant message is the answer (continues replace earlier ones).
+    let text = assistant.history.get()
+        .iter()
+        .rev()
+        .find(|m| matches!(m.role, MessageRole::Assistant))
+        .filter(|_| assistant.last_error.is_none())
+        .map(|m| m.content.replace(CONTINUE_TOKEN, "").replace(RESTART_TOKEN, "").trim().to_string())
+        .unwrap_or_default();
+
+    let mut usage = assistant.usage;
+    usage.prompt_tokens = usage.prompt_tokens.saturating_sub(usage_before.prompt_tokens);
+    usage.completion_tokens = usage.completion_tokens.saturating_sub(usage_before.completion_tokens);
+    usage.total_tokens = usage.total_tokens.saturating_sub(usage_before.total_tokens);
+
+    OneShot {
+        text,
+        tool_calls: assistant.tool_calls[calls_before..].to_vec(),
+        usage,
+        error: assistant.last_error.clone(),
+    }
+}
@@ -0,0 +1,209 @@
+//! ===============================================================
+//! Approval Gate
+//!
+//! Decides whether an action the model asked for (tool call or
+//! response block) may run, asking the user through the UI when
+//! the approval mode requires it:
+//!   * `always-ask`     -- ask for every action, reads included
+//!   * `ask-for-writes` -- ask for anything that can change state
+//!   * `auto`           -- run everything
+//!   * `deny`           -- never run anything that can change state,
+//!     except commands matching an allow rule
+//!
+//! The user can approve once, approve for the rest of the session,
+//! or reject -- optionally with feedback that goes back to the model.
+//!
+//! Shell commands are checked against the command rules (`rules.rs`)
+//! first: denied commands never run, allowed ones run without asking.
+//! ===============================================================
+
+use std::collections::HashSet;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::rules::{ split_commands, CommandRules, RuleVerdict };
+use crate::ui_trait::UIBase;
+
+/// How much the user wants to be asked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApprovalMode {
+    AlwaysAsk,
+    #[default]
+    AskForWrites,
+    Auto,
+    Deny,
+}
+
+impl ApprovalMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "always-ask" => Some(Self::AlwaysAsk),
+            "ask-for-writes" => Some(Self::AskForWrites),
+            "auto" => Some(Self::Auto),
+            "deny" => Some(Self::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AlwaysAsk => "always-ask",
+            Self::AskForWrites => "ask-for-writes",
+            Self::Auto => "auto",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+/// What an action can do -- decides whether it needs approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// Only looks at things (reading files, listing directories, ...).
+    Read,
+
+    /// Changes files.
+    Write,
+
+    /// Runs arbitrary code (shell commands, Python).
+    Execute,
+}
+
+/// An action waiting for approval, as shown to the user.
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub kind: ActionKind,
+
+    /// Who wants to act, e.g. "run_terminal" or "terminal block".
+    pub source: String,
+
+    /// What exactly will happen -- the command line, the file path, the code.
+    pub summary: String,
+
+    /// What "approve for the session" covers; later actions skip asking
+    /// when all their keys are covered.
+    pub session_keys: Vec<String>,
+
+    /// The command line, for shell commands -- checked against the rules.
+    pub command: Option<String>,
+}
+
+impl ApprovalRequest {
+    pub fn new(kind: ActionKind, source: &str, summary: impl Into<String>) -> Self {
+        Self {
+            kind,
+            source: source.to_string(),
+            summary: summary.into(),
+            session_keys: vec![source.to_string()],
+            command: None,
+        }
+    }
+
+    /// A shell command. Session approval covers later runs of the same
+    /// programs -- every program of a compound line has to be covered.
+    pub fn command(source: &str, command: &str) -> Self {
+        let mut session_keys: Vec<String> = Vec::new();
+        for program in split_commands(command).iter().flatten().filter_map(|c| c.text.split(' ').next()) {
+            let key = format!("exec:{}", program);
+            if !session_keys.contains(&key) {
+                session_keys.push(key);
+            }
+        }
+        if session_keys.is_empty() {
+            session_keys.push(String::from("exec:"));
+        }
+        Self {
+            session_keys,
+            command: Some(command.to_string()),
+            ..Self::new(ActionKind::Execute, source, command.trim())
+        }
+    }
+
+    /// A file write. Session approval covers every later write.
+    pub fn write(source: &str, path: &str) -> Self {
+        Self {
+            session_keys: vec!["write".to_string()],
+            ..Self::new(ActionKind::Write, source, path)
+        }
+    }
+}
+
+/// The user's answer to an `ApprovalRequest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    ApproveOnce,
+    ApproveSession,
+    Reject { feedback: Option<String> },
+}
+
+/// Applies the approval mode and remembers session-wide approvals.
+#[derive(Debug, Clone)]
+pub struct ApprovalGate {
+    pub mode: ApprovalMode,
+
+    /// Allow/deny rules for shell commands.
+    pub rules: CommandRules,
+    session: HashSet<String>,
+}
+
+impl Default for ApprovalGate {
+    fn default() -> Self {
+        Self::new(ApprovalMode::default())
+    }
+}
+
+impl ApprovalGate {
+    /// A gate with the built-in command rules.
+    pub fn new(mode: ApprovalMode) -> Self {
+        Self { mode, rules: CommandRules::builtin(), session: HashSet::new() }
+    }
+
+    pub fn with_rules(mut self, rules: CommandRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Whether the user has to be asked (ignoring session approvals).
+    pub fn needs_asking(&self, kind: ActionKind) -> bool {
+        match self.mode {
+            ApprovalMode::AlwaysAsk => true,
+            ApprovalMode::AskForWrites => kind != ActionKind::Read,
+            ApprovalMode::Auto | ApprovalMode::Deny => false,
+        }
+    }
+
+    /// `Ok` → go ahead. `Err` → don't run; the message explains why and is
+    /// meant to be sent back to the model.
+    pub fn check(&mut self, ui: &dyn UIBase, request: &ApprovalRequest) -> Result<(), String> {
+        if let Some(command) = &request.command {
+            match self.rules.check(command) {
+                RuleVerdict::Deny { rule, command } => {
+                    return Err(format!("[Denied] `{}` matches the deny rule `{}` and was not run.", command, rule));
+                }
+                RuleVerdict::Allow => return Ok(()),
+                RuleVerdict::Unmatched => {}
+            }
+        }
+        if self.mode == ApprovalMode::Deny && request.kind != ActionKind::Read {
+            return Err(format!("[Denied] `{}` was not run: approval mode is `deny`.", request.summary));
+        }
+        if !self.needs_asking(request.kind) || request.session_keys.iter().all(|key| self.session.contains(key)) {
+            return Ok(());
+        }
+
+        match ui.request_approval(request) {
+            ApprovalDecision::ApproveOnce => Ok(()),
+            ApprovalDecision::ApproveSession => {
+                self.session.extend(request.session_keys.iter().cloned());
+                Ok(())
+            }
+            ApprovalDecision::Reject { feedback } => {
+                let mut message = format!("[Rejected] The user did not allow `{}`.", request.summary);
+                if let Some(feedback) = feedback.filter(|f| !f.trim().is_empty()) {
+                    message.push_str(&format!("\nUser feedback: {}", feedback.trim()));
+                }
+                Err(message)
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! ===============================================================
+//! Checkpoints
+//!
+//! Snapshots of every file the agent writes, taken before the write
+//! is applied, so any change can be inspected and rolled back.
+//!
+//! Layout of a store directory (one per session):
+//!   index.json     -- the checkpoints, oldest first
+//!   blobs/<sha256> -- file contents, stored once per distinct content
+//!
+//! Checkpoints are grouped into turns: all writes made in one
+//! iteration of the agent loop share a turn number.
+//! ===============================================================
+
+use std::path::{ Path, PathBuf };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use anyhow::{ bail, Context };
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+/// One file write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: usize,
+    pub turn: usize,
+
+    /// Absolute path of the written file.
+    pub path: PathBuf,
+
+    /// Blob of the content before the write; `None` → the file didn't exist.
+    pub before: Option<String>,
+
+    /// Blob of the content written.
+    pub after: String,
+
+    /// Unix time of the write, in seconds.
+    pub time: u64,
+}
+
+#[derive(Debug)]
+pub struct CheckpointStore {
+    dir: PathBuf,
+    checkpoints: Vec<Checkpoint>,
+
+    /// Turn new checkpoints are recorded in.
+    turn: usize,
+}
+
+impl CheckpointStore {
+    /// Opens the store in `dir`, loading earlier checkpoints. Nothing is
+    /// created on disk until the first write is recorded.
+    pub fn open(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let index = dir.join("index.json");
+        let checkpoints: Vec<Checkpoint> = if index.is_file() {
+            let text = std::fs::read_to_string(&index)
+                .with_context(|| format!("cannot read {}", index.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("invalid checkpoint index {}", index.display()))?
+        } else {
+            Vec::new()
+        };
+        let turn = checkpoints.last().map_or(1, |c| c.turn + 1);
+        Ok(Self { dir, checkpoints, turn })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// All checkpoints, oldest first.
+    pub fn list(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Checkpoint> {
+        self.checkpoints.iter().find(|c| c.id == id)
+    }
+
+    /// The turn the latest checkpoint belongs to.
+    pub fn last_turn(&self) -> Option<usize> {
+        self.checkpoints.last().map(|c| c.turn)
+    }
+
+    /// Snapshots `path` before `content` is written to it.
+    pub fn record(&mut self, path: &Path, content: &str) -> anyhow::Result<&Checkpoint> {
+        let before = match std::fs::read(path) {
+            Ok(old) => Some(self.store_blob(&old)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("cannot snapshot {}", path.display())),
+        };
+        let after = self.store_blob(content.as_bytes())?;
+
+        let id = self.checkpoints.last().map_or(1, |c| c.id + 1);
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        self.checkpoints.push(Checkpoint { id, turn: self.turn, path: path.to_path_buf(), before, after, time });
+        self.save()?;
+        Ok(self.checkpoints.last().unwrap())
+    }
+
+    /// Closes the current turn; later writes go into a new one.
+    /// Does nothing when the turn has no checkpoints.
+    pub fn end_turn(&mut self) {
+        if self.last_turn() == Some(self.turn) {
+            self.turn += 1;
+        }
+    }
+
+    /// Unified diff of the write recorded by checkpoint `id`.
+    pub fn diff(&self, id: usize) -> anyhow::Result<String> {
+        let checkpoint = self.get(id).with_context(|| format!("no checkpoint #{}", id))?;
+        let before = match &checkpoint.before {
+            Some(blob) => self.read_blob(blob)?,
+            None => String::new(),
+        };
+        let after = self.read_blob(&checkpoint.after)?;
+        let path = checkpoint.path.display().to_string();
+
+        Ok(similar::TextDiff::from_lines(&before, &after)
+            .unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string())
+    }
+
+    /// Puts the file of checkpoint `id` back the way it was before that
+    /// write (deleting it when it didn't exist).
+    pub fn restore(&self, id: usize) -> anyhow::Result<PathBuf> {
+        let checkpoint = self.get(id).with_context(|| format!("no checkpoint #{}", id))?;
+        match &checkpoint.before {
+            Some(blob) => {
+                let content = std::fs::read(self.blob_path(blob))
+                    .with_context(|| format!("missing blob {}", blob))?;
+                if let Some(parent) = checkpoint.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&checkpoint.path, content)
+                    .with_context(|| format!("cannot restore {}", checkpoint.path.display()))?;
+            }
+            None => {
+                if checkpoint.path.exists() {
+                    std::fs::remove_file(&checkpoint.path)
+                        .with_context(|| format!("cannot remove {}", checkpoint.path.display()))?;
+                }
+            }
+        }
+        Ok(checkpoint.path.clone())
+    }
+
+    /// Undoes every write of `turn`, newest first, so each file ends up as
+    /// it was before the turn. Returns the restored files.
+    pub fn restore_turn(&self, turn: usize) -> anyhow::Result<Vec<PathBuf>> {
+        let ids: Vec<usize> = self.checkpoints.iter().filter(|c| c.turn == turn).map(|c| c.id).collect();
+        if ids.is_empty() {
+            bail!("no checkpoints in turn {}", turn);
+        }
+
+        let mut restored: Vec<PathBuf> = Vec::new();
+        for id in ids.into_iter().rev() {
+            let path = self.restore(id)?;
+            if !restored.contains(&path) {
+                restored.push(path);
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Restores the latest turn and forgets its checkpoints, so the next
+    /// undo goes one turn further back. Returns the turn and its files.
+    pub fn undo(&mut self) -> anyhow::Result<(usize, Vec<PathBuf>)> {
+        let Some(turn) = self.last_turn() else {
+            bail!("nothing to undo");
+        };
+        let restored = self.restore_turn(turn)?;
+        self.checkpoints.retain(|c| c.turn != turn);
+        self.turn = turn;
+        self.save()?;
+        Ok((turn, restored))
+    }
+
+    // ── Storage ──
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("blobs").join(hash)
+    }
+
+    fn store_blob(&self, content: &[u8]) -> anyhow::Result<String> {
+        let hash = format!("{:x}", Sha256::digest(content));
+        let path = self.blob_path(&hash);
+        if !path.exists() {
+            std::fs::create_dir_all(self.dir.join("blobs"))
+                .with_context(|| format!("cannot create {}", self.dir.display()))?;
+            std::fs::write(&path, content)
+                .with_context(|| format!("cannot write blob {}", path.display()))?;
+        }
+        Ok(hash)
+    }
+
+    fn read_blob(&self, hash: &str) -> anyhow::Result<String> {
+        let content = std::fs::read(self.blob_path(hash)).with_context(|| format!("missing blob {}", hash))?;
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("cannot create {}", self.dir.display()))?;
+        let index = self.dir.join("index.json");
+        std::fs::write(&index, serde_json::to_string_pretty(&self.checkpoints)?)
+            .with_context(|| format!("cannot write {}", index.display()))
+    }
+}
@@ -6,52 +6,223 @@
 //! Provides a clean interface for the CLI to communicate with the AI.
 //! ===============================================================
 
+use std::sync::Arc;
+
+use crate::approval::ApprovalGate;
+use crate::checkpoints::CheckpointStore;
 use crate::client_util::*;
-use crate::model;
+use crate::models::{ Model, ModelSet };
 use crate::prompt::get_model_choosing_prompt;
-use crate::types::{ChatMessage, MessageRole};
+use crate::config::Config;
+use crate::provider::{ replay, Completion, CompletionRequest, Provider };
+use crate::shell::{ SharedShell, Shell };
+use crate::search::index_file;
+use crate::sessions::SessionStore;
+use crate::tools::{ EditFile, GlobFiles, Grep, ListDir, ReadFile, RunTerminal, SearchHistory, ToolRegistry, WriteFile };
+use crate::tools::{ MemoryAdd, MemoryDelete, MemorySearch, MemoryUpdate, MemoryView };
+use crate::types::{ChatMessage, MessageRole, ToolCall, Usage};
 use crate::ui_trait::MsgType;
 use crate::ui_trait::{ MsgRole, UIBase };
-use crate::{ history_manager::History, memory_manager::MemoryManager };
+use crate::workspace::Workspace;
+use crate::{ history_manager::{ History, SUMMARY_PREFIX }, memory_manager::{ MemoryManager, SharedMemory } };
 
-use async_openai::{ Client as APIClient, config::OpenAIConfig };
-use serde_json::{ json, Value };
+use serde_json::json;
 use super::constants::*;
 
 const INDEPENDENT_MAX_TOKENS: u32 = 4000; // Max tokens for independent requests
-const MAX_TOKENS: u32 = 32000; // Max tokens for chat requests
-const MAX_RETRIES: usize = 10; // Max retries for API requests
 
 /// Represents the main AI client for chat interaction.
 pub struct Client {
     pub model: String,
+
+    /// Model ids served by the provider, per tier -- used by `choose_model`.
+    pub models: ModelSet,
     pub history: History,
-    pub memory: MemoryManager,
-    ai: APIClient<OpenAIConfig>,
+    pub memory: SharedMemory,
+
+    /// Resolved configuration this client was built from.
+    pub config: Config,
+
+    /// Accumulated token usage for this session, as reported by the provider.
+    pub usage: Usage,
+
+    /// Tools offered to the model -- register custom ones here.
+    pub tools: ToolRegistry,
+
+    /// Asks the user before tool calls and response blocks run.
+    pub approval: ApprovalGate,
+
+    /// Tool calls executed this session, in order.
+    pub tool_calls: Vec<ToolCall>,
+
+    /// Last failed request, if any -- not cleared automatically.
+    pub last_error: Option<String>,
+
+    /// Stream assistant replies when the UI supports it.
+    pub stream: bool,
+    provider: Arc<dyn Provider>,
+
+    /// Shell session shared by `run_terminal` and ```` ```terminal ```` blocks.
+    shell: Option<SharedShell>,
+
+    /// Directory the file tools and ```` ```write ```` blocks are confined to.
+    workspace: Option<Arc<Workspace>>,
 }
 
 impl Client {
-    /// Creates a new AI client instance using the default model and configuration.
+    /// Creates a new AI client instance from the configuration resolved for
+    /// the current directory (config files + environment).
+    pub fn new() -> anyhow::Result<Self> {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let config = Config::load(&cwd).unwrap_or_else(|e| {
+            eprintln!("[ERROR] {:#}. Using default configuration.", e);
+            Config::default()
+        });
+        Self::from_config(&config)
+    }
+
+    /// Creates a new AI client from a resolved configuration: endpoint
+    /// (api.openai.com, Anthropic, or an OpenAI-compatible server such as
+    /// Ollama, llama.cpp, vLLM, ...), models, history and memory files.
+    /// Fails when `CAI_REPLAY` names a transcript that can't be loaded.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let endpoint = config.endpoint();
+
+        // CAI_RECORD / CAI_REPLAY turn on transcript recording / offline replay
+        let mut client = Self::with_storage(
+            replay::from_env(endpoint.build_provider())?,
+            History::new(&config.history_file, config.max_history, config.summary_size()),
+            MemoryManager::new(&config.memory_file),
+        );
+        client.config = config.clone();
+        client.approval = ApprovalGate::new(config.approval).with_rules(config.command_rules());
+        client.set_models(endpoint.models);
+        if let Some(model) = &config.model {
+            client.model = model.clone();
+        }
+        Ok(client)
+    }
+
+    /// Creates a new AI client instance that talks to the given provider.
     ///
     /// # Arguments
-    /// * `model` - The model name (e.g., "gpt-4.1").
-    /// * `history` - Conversation history manager.
-    /// * `memory` - Persistent memory manager.
-    /// * `api_key` - API key for authentication.
-    pub fn new() -> Self {
-        let model: String = DEFAULT_MODEL.to_string();
+    /// * `provider` - The LLM backend used for every request.
+    pub fn with_provider(provider: Arc<dyn Provider>) -> Self {
+        Self::with_storage(
+            provider,
+            History::new(DEFAULT_HISTORY_FILE_NAME, MAX_HISTORY, SUMMARY_SIZE),
+            MemoryManager::new(DEFAULT_MEMORY_FILE_NAME),
+        )
+    }
 
-        println!("[DEBUG] Model={}", model);
+    /// Creates a new AI client with explicit history and memory managers
+    /// (e.g. backed by files outside the working directory). The memory is
+    /// shared with the `memory_*` tools.
+    pub fn with_storage(provider: Arc<dyn Provider>, history: History, memory: MemoryManager) -> Self {
+        let model: String = DEFAULT_MODEL.to_string();
 
-        // Create a OpenAI client with api key from env var OPENAI_API_KEY and default base url.
-        let ai: APIClient<OpenAIConfig> = APIClient::new();
+        let memory = memory.shared();
+        let mut tools = ToolRegistry::with_defaults();
+        tools.register(MemoryAdd::new(memory.clone()));
+        tools.register(MemoryUpdate::new(memory.clone()));
+        tools.register(MemoryDelete::new(memory.clone()));
+        tools.register(MemorySearch::new(memory.clone()));
+        tools.register(MemoryView::new(memory.clone()));
 
         Self {
             model,
-            history: History::new(DEFAULT_HISTORY_FILE_NAME, MAX_HISTORY, SUMMARY_SIZE),
-            memory: MemoryManager::new(DEFAULT_MEMORY_FILE_NAME),
-            ai,
+            models: ModelSet::default(),
+            history,
+            memory,
+            config: Config::default(),
+            usage: Usage::default(),
+            tools,
+            approval: ApprovalGate::default(),
+            tool_calls: Vec::new(),
+            last_error: None,
+            stream: true,
+            provider,
+            shell: None,
+            workspace: None,
+        }
+    }
+
+    /// Sets the models served by the provider. Unless they are the stock OpenAI
+    /// models, the default model becomes the endpoint's `full` tier.
+    pub fn set_models(&mut self, models: ModelSet) {
+        if models != ModelSet::default() {
+            self.model = models.full.clone();
         }
+        self.models = models;
+    }
+
+    /// Attaches the shell session that tool calls and ```` ```terminal ````
+    /// blocks run in, and returns the shared handle.
+    pub fn attach_shell(&mut self, shell: Shell) -> SharedShell {
+        let shell = shell.shared();
+        self.tools.register(RunTerminal::new(shell.clone()));
+        self.shell = Some(shell.clone());
+        shell
+    }
+
+    /// The attached shell session. When none is attached yet, one is spawned
+    /// in the current directory with the configured timeout and rules.
+    pub fn shell(&mut self) -> anyhow::Result<SharedShell> {
+        if let Some(shell) = &self.shell {
+            return Ok(shell.clone());
+        }
+        let cwd = std::env::current_dir()?;
+        let shell = Shell::new(&cwd.to_string_lossy())?
+            .with_timeout(self.config.command_timeout())
+            .with_rules(self.config.command_rules());
+        Ok(self.attach_shell(shell))
+    }
+
+    /// Confines the file tools and ```` ```write ```` blocks to `workspace`.
+    pub fn attach_workspace(&mut self, workspace: Workspace) -> Arc<Workspace> {
+        let workspace = Arc::new(workspace);
+        self.tools.register(WriteFile::new(workspace.clone()));
+        self.tools.register(EditFile::new(workspace.clone()));
+        self.tools.register(ReadFile::new(workspace.clone()));
+        self.tools.register(ListDir::new(workspace.clone()));
+        self.tools.register(GlobFiles::new(workspace.clone()));
+        self.tools.register(Grep::new(workspace.clone()));
+        self.workspace = Some(workspace.clone());
+        workspace
+    }
+
+    /// Makes the history part of session `session` in `store`: saves keep
+    /// the search index up to date, new memories record the session, and
+    /// the model gets `search_history`.
+    pub fn attach_sessions(&mut self, store: SessionStore, session: &str) {
+        self.history.set_index(index_file(&store), session);
+        self.memory.lock().unwrap().set_source(session);
+        self.tools.register(SearchHistory::new(store));
+    }
+
+    /// The attached workspace. When none is attached yet, one is rooted at
+    /// the current directory with the configured writable directories and
+    /// checkpoint store.
+    pub fn workspace(&mut self) -> anyhow::Result<Arc<Workspace>> {
+        if let Some(workspace) = &self.workspace {
+            return Ok(workspace.clone());
+        }
+        let workspace = Workspace::current()?
+            .with_writable(&self.config.writable_dirs)?
+            .with_checkpoints(CheckpointStore::open(self.config.checkpoint_dir())?);
+        Ok(self.attach_workspace(workspace))
+    }
+
+    /// Closes the current checkpoint turn (see `CheckpointStore::end_turn`).
+    pub fn end_turn(&self) {
+        if let Some(mut store) = self.workspace.as_ref().and_then(|w| w.checkpoints()) {
+            store.end_turn();
+        }
+    }
+
+    /// The provider this client sends requests to.
+    pub fn provider(&self) -> &dyn Provider {
+        self.provider.as_ref()
     }
 
     /// Just a normal system request to the AI, doesn't save the response or uses the history
@@ -60,24 +231,22 @@ impl Client {
         content: &str,
         model: Option<String>
     ) -> Result<String, String> {
-        let result = call_with_backoff(
-            &self.ai,
-            json!({
-                "model": model.unwrap_or_else(|| self.model.clone()),
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": content
-                    }
-                ],
-                "max_completion_tokens": INDEPENDENT_MAX_TOKENS,
-            })
-        ).await;
-
-        match result {
-            Ok(content) => {
+        let mut request = CompletionRequest::new(
+            model.unwrap_or_else(|| self.model.clone()),
+            vec![json!({
+                "role": "system",
+                "content": content
+            })]
+        );
+        request.params = json!({ "max_completion_tokens": INDEPENDENT_MAX_TOKENS });
+
+        match call_with_backoff(self.provider.as_ref(), &request).await {
+            Ok(completion) => {
                 // println!("[DEBUG] Independent request successful");
-                Ok(content)
+                if let Some(usage) = &completion.usage {
+                    self.usage.add(usage);
+                }
+                Ok(completion.content.unwrap_or_default())
             }
 
             Err(()) => Err("Failed to make independent request".to_string()),
@@ -87,10 +256,13 @@ impl Client {
     /// Creates an inserts a chat history summary into the history. Should be called if `needs_summarize()` returns true.
     /// If ok -> returns the summary, otherwise returns an error message.
     pub async fn perform_history_summary(&mut self) -> Result<String, String> {
-        let prompt = self.history.get_summarize_prompt(); // Drains messages here
+        let prompt = self.history.get_summarize_prompt();
+        if prompt.is_empty() {
+            return Ok(String::new());
+        }
         match self.make_independent_request(&prompt, None).await {
             Ok(summary) => {
-                self.history.insert_summary(format!("[Conversation summary]\n{}", summary));
+                self.history.insert_summary(format!("{}\n{}", SUMMARY_PREFIX, summary));
                 Ok(summary)
             }
             Err(e) => { Err(e) }
@@ -111,8 +283,16 @@ impl Client {
     ) -> String {
         // 1) Record user/system content
         self.history.add_message(role, content.to_string());
+        self.respond(ui, model).await
+    }
 
-        // 2) Summarize if needed
+    /// Answers the last message of the current branch -- e.g. after
+    /// `History::edit` or `History::retry_last_reply`.
+    pub async fn respond(&mut self, ui: &dyn UIBase, model: Option<String>) -> String {
+        let content: String = self.history.get().last().map(|m| m.content.clone()).unwrap_or_default();
+
+        // 2) Summarize if needed (token budget of the default model)
+        self.history.set_budget(self.config.token_budget(&self.model));
         if self.history.needs_summarize() {
             if let Err(e) = self.perform_history_summary().await {
                 ui.print_message(
@@ -124,109 +304,86 @@ impl Client {
             }
         }
 
-        // 3) Choose model via LLM (fallback to provided or default)
-        let chosen_model: String = self
-            .choose_model(content, self.history.get())
-            .await
-            .unwrap_or(model.unwrap_or(self.model.clone()));
-        println!("[DEBUG] Chosen model: {}", chosen_model);
-
-        // 4) Working copy of messages (don't pollute persistent history with tool traffic)
-        let mut rt_messages: Value = json!(self.history.get()); // Vec<messages> as Value
+        // 3) Model: the one given or pinned (--model / config), else chosen
+        //    via LLM (fallback to default)
+        let chosen_model: String = match model.or_else(|| self.config.model.clone()) {
+            Some(model) => model,
+            None => self
+                .choose_model(&content, self.history.get())
+                .await
+                .unwrap_or_else(|| self.model.clone()),
+        };
+
+        // 4) Request from the current branch (tool traffic flattened to text
+        //    when no tools are offered)
+        let offer_tools = self.config.tools && !self.tools.is_empty();
+        let mut request = CompletionRequest::new(
+            chosen_model.clone(),
+            self.history.get().iter().map(|m| if offer_tools { m.to_json() } else { m.to_text_json() }).collect()
+        );
         let mut assistant_final_text: Option<String> = None;
+        let mut final_text_streamed = false;
 
-        // 5) Base request JSON
-        let mut req = json!({
-            "model": chosen_model,
-            "messages": rt_messages,
-        });
-
-        // 6) Tools + profile
-        req["tools"] = tools_json();
-        if let Some(variant) = model_variant_of(&chosen_model) {
-            merge_json(&mut req, request_profile_of(&variant));
+        // 5) Tools + profile
+        if let Some(variant) = self.models.variant_of(&chosen_model) {
+            merge_json(&mut request.params, request_profile_of(&variant));
+        }
+        if offer_tools {
+            request.tools = Some(self.tools.to_json());
+        } else if let Some(params) = request.params.as_object_mut() {
+            // Tool knobs are rejected when no tools are sent
+            params.remove("tool_choice");
+            params.remove("parallel_tool_calls");
         }
-        
-        // 7) Tool-calling loop (limited retries)
-        for _ in 0..5 {
-            // NOTE: call_with_backoff should return the RAW JSON string for tool calls.
-            let resp = call_with_backoff(&self.ai, req.clone()).await;
-
-            match resp {
-                Ok(s) => {
-                    // Try parse as JSON (Responses/ChatCompletions raw body)
-                    if let Ok(v) = serde_json::from_str::<Value>(&s) {
-                        if let Some(choice) = v.get("choices").and_then(|c| c.get(0)) {
-                            let msg = &choice["message"];
-                            // Tools?
-                            if let Some(tc) = msg.get("tool_calls").and_then(|x| x.as_array()) {
-                                // Append the assistant msg (with tool_calls) to runtime messages
-                                rt_messages.as_array_mut().unwrap().push(msg.clone());
-
-                                // Execute each tool
-                                for t in tc {
-                                    let call_id = t.get("id").and_then(|x| x.as_str()).unwrap_or("");
-                                    let fname = t.get("function").and_then(|f| f.get("name")).and_then(|x| x.as_str()).unwrap_or("");
-                                    let fargs = t.get("function").and_then(|f| f.get("arguments")).and_then(|x| x.as_str()).unwrap_or("{}");
-                                    let args_val: Value = serde_json::from_str(fargs).unwrap_or(json!({}));
-
-                                    let result = match fname {
-                                        "run_terminal" => {
-                                            let cmd = args_val.get("command").and_then(|x| x.as_str()).unwrap_or("");
-                                            run_terminal(cmd)
-                                        },
-                                        "run_python" => {
-                                            let code = args_val.get("code").and_then(|x| x.as_str()).unwrap_or("");
-                                            run_python(code)
-                                        },
-                                        "write_file" => {
-                                            let path = args_val.get("path").and_then(|x| x.as_str()).unwrap_or("");
-                                            let content = args_val.get("content").and_then(|x| x.as_str()).unwrap_or("");
-                                            match write_file(path, content) {
-                                                Ok(_) => String::from("[write_file] OK"),
-                                                Err(e) => format!("[write_file] ERROR: {}", e),
-                                            }
-                                        },
-                                        _ => format!("[tool error] Unknown tool: {}", fname),
-                                    };
-
-                                    // Push tool result
-                                    rt_messages.as_array_mut().unwrap().push(json!({
-                                        "role": "tool",
-                                        "tool_call_id": call_id,
-                                        "content": result
-                                    }));
-                                }
-
-                                // Re-ask with augmented messages
-                                req["messages"] = rt_messages.clone();
-                                continue;
-                            }
-
-                            // No tool calls → take assistant content
-                            if let Some(text) = msg.get("content").and_then(|x| x.as_str()) {
-                                assistant_final_text = Some(text.to_string());
-                                break;
-                            }
-                        }
-                    }
 
-                    // If not JSON or missing fields → treat as final assistant text
-                    assistant_final_text = Some(s);
-                    break;
-                }
+        // 6) Tool-calling loop (limited retries)
+        for _ in 0..5 {
+            let (result, streamed) = self.request_completion(ui, &request).await;
+            let completion = match result {
+                Ok(completion) => completion,
                 Err(_) => {
+                    self.last_error = Some(format!("request to {} failed", self.provider.name()));
                     assistant_final_text = Some(String::from("[ERROR] Request failed."));
                     break;
                 }
+            };
+
+            if let Some(usage) = &completion.usage {
+                self.usage.add(usage);
+            }
+
+            // No tool calls → take assistant content
+            if completion.tool_calls.is_empty() {
+                assistant_final_text = completion.content;
+                final_text_streamed = streamed;
+                break;
+            }
+
+            // Record the assistant msg (with tool_calls) in history and the request
+            request.messages.push(completion.assistant_message());
+            self.history.push(ChatMessage::tool_calls(
+                completion.content.clone().unwrap_or_default(),
+                completion.tool_calls.clone()
+            ));
+
+            // Execute each tool
+            for call in &completion.tool_calls {
+                let result = self.run_tool(ui, call).await;
+
+                // Record the tool result, then re-ask with augmented messages
+                let message = ChatMessage::tool_result(call.id.clone(), result);
+                request.messages.push(message.to_json());
+                self.history.push(message);
             }
         }
 
         let content = assistant_final_text.unwrap_or_else(|| String::from("[No message]"));
 
-        // Only the final assistant text goes into persistent history/UI
+        // The final assistant text closes the exchange
         self.history.add_message(MessageRole::Assistant, content.clone());
-        ui.print_message(MsgRole::Assistant, MsgType::Plain(content.clone()));
+        if !final_text_streamed {
+            ui.print_message(MsgRole::Assistant, MsgType::Plain(content.clone()));
+        }
 
         if self.history.needs_summarize() {
             let _ = self.perform_history_summary().await;
@@ -238,18 +395,80 @@ impl Client {
 
 
 
+    /// Runs a tool call once the approval gate lets it through.
+    /// A rejection is returned as the tool result so the model can adapt.
+    async fn run_tool(&mut self, ui: &dyn UIBase, call: &ToolCall) -> String {
+        if let Some(tool) = self.tools.get(&call.name) {
+            if let Err(rejection) = self.approval.check(ui, &tool.approval_request(&call.args())) {
+                return rejection;
+            }
+        }
+        // `run_terminal` gets the client's shell session (configured timeout
+        // and rules) on first use, not a standalone shell
+        if call.name == "run_terminal" && self.shell.is_none() {
+            if let Err(e) = self.shell() {
+                return format!("[{}] ERROR: {:#}", call.name, e);
+            }
+        }
+        self.tool_calls.push(call.clone());
+        let result = self.tools.invoke(call).await;
+        if let Some(message) = self.tools.get(&call.name).and_then(|tool| tool.render(&result)) {
+            ui.print_message(MsgRole::System, message);
+        }
+        result
+    }
+
+    /// Runs one request of the tool loop, streaming the assistant text into
+    /// the UI when both the client and the UI allow it.
+    /// Returns the completion and whether any text was streamed.
+    async fn request_completion(
+        &self,
+        ui: &dyn UIBase,
+        request: &CompletionRequest
+    ) -> (Result<Completion, ()>, bool) {
+        if !(self.stream && ui.supports_streaming()) {
+            return (call_with_backoff(self.provider.as_ref(), request).await, false);
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let call = call_stream_with_backoff(self.provider.as_ref(), request, tx);
+
+        // Drain deltas on the same task while the provider streams.
+        // The message is opened lazily so tool-call-only replies print nothing.
+        let render = async {
+            let mut started = false;
+            while let Some(delta) = rx.recv().await {
+                if !started {
+                    ui.begin_assistant_message();
+                    started = true;
+                }
+                ui.assistant_delta(&delta);
+            }
+            if started {
+                ui.end_assistant_message();
+            }
+            started
+        };
+
+        tokio::join!(call, render)
+    }
+
     /// Chooses the appropriate model based on the content complexity.
-    /// Chooses the appropriate model based on the content complexity.
+    /// Skipped (`None` → caller's fallback) when the endpoint serves a single model.
     pub async fn choose_model(&mut self, content: &str, history: Vec<ChatMessage>) -> Option<String> {
+        if self.models.is_single() {
+            return None;
+        }
+
         let p: String = get_model_choosing_prompt(content, &history);
         // Use a cheap decider; fallback to default on failure
-        match self.make_independent_request(&p, model!(Mini)).await {
+        match self.make_independent_request(&p, Some(self.models.get(Model::Mini))).await {
             Ok(choice) => {
                 match choice.trim() {
-                    "nano" => model!(Nano),
-                    "mini" => model!(Mini),
-                    "full" => model!(Full),
-                    "max"  => model!(Max),
+                    "nano" => Some(self.models.get(Model::Nano)),
+                    "mini" => Some(self.models.get(Model::Mini)),
+                    "full" => Some(self.models.get(Model::Full)),
+                    "max"  => Some(self.models.get(Model::Max)),
                     other => {
                         eprintln!("[ERROR] Unknown model choice: {}", other);
                         None
@@ -264,6 +483,3 @@ impl Client {
     }
 
 }
-
-
-
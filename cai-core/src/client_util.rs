@@ -1,17 +1,60 @@
-use async_openai::{ config::OpenAIConfig, Client as APIClient };
 use serde_json::{json, Value};
 
-use crate::{model, models::Model};
+use crate::provider::{Completion, CompletionRequest, DeltaSender, FatalError, Provider};
+use crate::models::Model;
 
 const MAX_RETRIES: usize = 5; // Max retries for API requests
 
 /// Keep retrying with exponential back-off until the call succeeds.
-/// If the payload includes "tools", we return the RAW JSON string (so the caller can parse tool_calls).
-/// Otherwise we extract the assistant text like before, falling back to raw JSON if needed.
+/// Works against any `Provider`; the caller decides what to do with the
+/// returned text and tool calls.
 pub async fn call_with_backoff(
-    ai: &APIClient<OpenAIConfig>,
-    payload: serde_json::Value
-) -> Result<String, ()> {
+    provider: &dyn Provider,
+    request: &CompletionRequest
+) -> Result<Completion, ()> {
+    with_backoff(provider.name(), || provider.complete(request), || true).await
+}
+
+/// Same as `call_with_backoff`, but streams assistant text into `deltas`.
+/// Only attempts that failed before streaming anything are retried: the
+/// text already shown cannot be taken back, so a retry would repeat it.
+pub async fn call_stream_with_backoff(
+    provider: &dyn Provider,
+    request: &CompletionRequest,
+    deltas: DeltaSender
+) -> Result<Completion, ()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let forwarded = AtomicBool::new(false);
+    let attempt = || {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let (deltas, forwarded) = (&deltas, &forwarded);
+        async move {
+            // Relay through a per-attempt channel to see whether text went out.
+            let relay = async {
+                while let Some(delta) = rx.recv().await {
+                    forwarded.store(true, Ordering::Relaxed);
+                    let _ = deltas.send(delta);
+                }
+            };
+            tokio::join!(provider.complete_stream(request, tx), relay).0
+        }
+    };
+    with_backoff(provider.name(), attempt, || !forwarded.load(Ordering::Relaxed)).await
+}
+
+/// Exponential back-off loop shared by the request helpers.
+/// Gives up early on a `FatalError`, or once `retryable` says a failed
+/// attempt must not be repeated.
+async fn with_backoff<F, Fut>(
+    name: &str,
+    mut attempt: F,
+    retryable: impl Fn() -> bool
+) -> Result<Completion, ()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Completion>>,
+{
     use rand::{rng, Rng};
     use std::time::Duration;
     use tokio::time::sleep;
@@ -19,41 +62,23 @@ pub async fn call_with_backoff(
     const BASE_DELAY: Duration = Duration::from_secs(2);
     const MAX_DELAY: Duration = Duration::from_secs(60);
 
-    // Detect whether caller wants tool-calling raw JSON handling
-    let wants_tools = payload.get("tools").is_some();
-
     let mut delay = BASE_DELAY;
     for _ in 0..MAX_RETRIES {
-        match ai.chat().create_byot::<Value, Value>(payload.clone()).await {
-            Ok(response_val) => {
-                // When tools are declared, ALWAYS return raw JSON for the tool loop.
-                if wants_tools {
-                    return Ok(response_val.to_string());
-                }
-
-                // Legacy behavior: try to extract plain assistant text.
-                // Chat Completions shape: choices[0].message.content
-                if let Some(content) = response_val
-                    .get("choices").and_then(|c| c.get(0))
-                    .and_then(|c0| c0.get("message"))
-                    .and_then(|m| m.get("content"))
-                    .and_then(|s| s.as_str())
-                {
-                    return Ok(content.to_string());
-                }
+        match attempt().await {
+            Ok(completion) => return Ok(completion),
 
-                // Fallbacks: Responses-style or raw JSON if shape is unexpected.
-                // (Not expected here since we're calling chat(), but safe to keep.)
-                if let Some(output_text) = response_val.get("output_text").and_then(|s| s.as_str()) {
-                    return Ok(output_text.to_string());
-                }
+            Err(err) if err.is::<FatalError>() => {
+                eprintln!("[ERROR] {} request failed ({}).", name, err);
+                return Err(());
+            }
 
-                // Give the caller raw JSON so they can decide how to handle it.
-                return Ok(response_val.to_string());
+            Err(err) if !retryable() => {
+                eprintln!("[ERROR] {} request failed mid-stream ({}). Not retrying.", name, err);
+                return Err(());
             }
 
             Err(err) => {
-                eprintln!("[Retry] Request failed ({}). Sleeping {:?}…", err, delay);
+                eprintln!("[Retry] {} request failed ({}). Sleeping {:?}…", name, err, delay);
 
                 // Exponential backoff with jitter (±20%)
                 let jitter = {
@@ -71,54 +96,6 @@ pub async fn call_with_backoff(
     Err(())
 }
 
-/// Returns the tools schema to send to Chat Completions (tool calling).
-pub fn tools_json() -> Value {
-    json!([
-        {
-            "type": "function",
-            "function": {
-                "name": "run_terminal",
-                "description": "Execute a command in the user's native shell",
-                "parameters": {
-                    "type": "object",
-                    "properties": { "command": { "type": "string" } },
-                    "required": ["command"],
-                    "additionalProperties": false
-                }
-            }
-        },
-        {
-            "type": "function",
-            "function": {
-                "name": "run_python",
-                "description": "Run a Python snippet in the project workspace",
-                "parameters": {
-                    "type": "object",
-                    "properties": { "code": { "type": "string" } },
-                    "required": ["code"],
-                    "additionalProperties": false
-                }
-            }
-        },
-        {
-            "type": "function",
-            "function": {
-                "name": "write_file",
-                "description": "Write content to a file path, creating parent directories as needed",
-                "parameters": {
-                    "type": "object",
-                    "properties": {
-                        "path": { "type": "string" },
-                        "content": { "type": "string" }
-                    },
-                    "required": ["path", "content"],
-                    "additionalProperties": false
-                }
-            }
-        }
-    ])
-}
-
 /// Merge b into a (shallow/deep for objects/arrays minimal)
 pub fn merge_json(a: &mut Value, b: Value) {
     match (a, b) {
@@ -138,15 +115,6 @@ pub fn merge_json(a: &mut Value, b: Value) {
     }
 }
 
-/// Infer our Model variant from a model id string.
-pub fn model_variant_of(model_id: &str) -> Option<Model> {
-    if let Some(s) = model!(Nano) { if s == model_id { return Some(Model::Nano); } }
-    if let Some(s) = model!(Mini) { if s == model_id { return Some(Model::Mini); } }
-    if let Some(s) = model!(Full) { if s == model_id { return Some(Model::Full); } }
-    if let Some(s) = model!(Max)  { if s == model_id { return Some(Model::Max); } }
-    None
-}
-
 /// Execute a shell command and capture stdout/stderr.
 pub fn run_terminal(command: &str) -> String {
     use std::process::Command;
@@ -196,18 +164,6 @@ pub fn run_python(code: &str) -> String {
     }
 }
 
-/// Write file helper.
-pub fn write_file(path: &str, content: &str) -> Result<(), String> {
-    use std::{fs, path::Path};
-    let p = Path::new(path);
-    if let Some(parent) = p.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            return Err(format!("cannot create dirs: {}", e));
-        }
-    }
-    fs::write(p, content).map_err(|e| e.to_string())
-}
-
 
 
 pub fn request_profile_of(m: &Model) -> serde_json::Value {
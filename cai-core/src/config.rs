@@ -0,0 +1,351 @@
+//! ===============================================================
+//! Layered Configuration
+//!
+//! Resolves the runtime configuration from, in increasing priority:
+//!   1. built-in defaults (`constants.rs`)
+//!   2. user config     -- `~/.config/cai/config.toml`
+//!   3. project config  -- `.cai.toml` in the cwd or any parent
+//!   4. environment variables (`CAI_*`, `OPENAI_*`, `ANTHROPIC_*`)
+//!   5. CLI flags (applied by the binary on the resolved `Config`)
+//!
+//! Every layer is a partial TOML table merged over the previous one,
+//! so a file only needs the keys it wants to change.
+//! ===============================================================
+
+use std::path::{ Path, PathBuf };
+
+use anyhow::Context;
+use serde::{ Deserialize, Serialize };
+use toml::{ Table, Value };
+
+use crate::approval::ApprovalMode;
+use crate::storage::StorageKind;
+use crate::constants::*;
+use crate::models::ModelSet;
+use crate::provider::{ Endpoint, ProviderKind };
+use crate::rules::CommandRules;
+use crate::tokens::TokenBudget;
+
+pub const USER_CONFIG_FILE_NAME: &str = "config.toml";
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".cai.toml";
+
+/// Fully resolved configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Default model id. Unset → `DEFAULT_MODEL`, or the endpoint's `full`
+    /// tier when `provider.models` is set.
+    pub model: Option<String>,
+
+    /// Maximum number of messages to keep in conversation history.
+    pub max_history: usize,
+
+    /// Minimum number of messages to summarize at a time.
+    /// Unset → `SUMMARY_SIZE`, at most `max_history / 3`.
+    pub summary_size: Option<usize>,
+
+    /// Context window of the model in tokens. Unset → looked up from the
+    /// model id (see `tokens.rs`).
+    pub context_window: Option<usize>,
+
+    /// Fraction of the context window the history may fill before older
+    /// messages are summarized.
+    pub context_fraction: f64,
+
+    /// Maximum allowed consecutive continue tokens before requiring user input.
+    pub max_continue: usize,
+
+    /// Seconds a shell command may run before it is killed; 0 → no limit.
+    pub command_timeout: u64,
+
+    /// When to ask before running commands and writing files.
+    pub approval: ApprovalMode,
+
+    /// Extra allow/deny patterns for shell commands (`[rules]` table),
+    /// added to the built-in ones.
+    pub rules: CommandRules,
+
+    /// Directories outside the workspace that file tools may write to
+    /// (relative ones are resolved against the workspace root).
+    pub writable_dirs: Vec<String>,
+
+    /// Offer native tool calls (`run_terminal`, `write_file`, ...) to the model.
+    pub tools: bool,
+
+    /// Language and OS for the AI to use in its responses.
+    pub language: String,
+    pub os: String,
+
+    /// Where history and memory are stored (relative to the cwd). The
+    /// history file is only used when no session applies (`cai ask`, or
+    /// no data directory).
+    pub history_file: String,
+    pub memory_file: String,
+
+    /// Backend of history and memory: `json` files (default) or `sqlite`
+    /// databases, which several cai processes can share safely.
+    pub storage: StorageKind,
+
+    /// Where sessions are kept. Unset → `$XDG_DATA_HOME/cai`
+    /// (`~/.local/share/cai`).
+    pub data_dir: Option<String>,
+
+    pub provider: ProviderConfig,
+
+    /// Files that contributed to this config, lowest priority first.
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+/// `[provider]` table -- see `Endpoint`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+
+    /// One model id, or `nano,mini,full,max`.
+    pub models: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: None,
+            max_history: MAX_HISTORY,
+            summary_size: None,
+            context_window: None,
+            context_fraction: CONTEXT_FRACTION,
+            max_continue: MAX_CONTINUE,
+            command_timeout: COMMAND_TIMEOUT_SECS,
+            approval: ApprovalMode::default(),
+            rules: CommandRules::default(),
+            writable_dirs: Vec::new(),
+            tools: true,
+            language: LANGUAGE.to_string(),
+            os: OS.to_string(),
+            history_file: DEFAULT_HISTORY_FILE_NAME.to_string(),
+            memory_file: DEFAULT_MEMORY_FILE_NAME.to_string(),
+            storage: StorageKind::default(),
+            data_dir: None,
+            provider: ProviderConfig::default(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Resolves layers 1-4 for a session started in `cwd`.
+    pub fn load(cwd: &Path) -> anyhow::Result<Self> {
+        let project = find_project_config(cwd);
+        Self::load_from(
+            user_config_path().as_deref(),
+            project.as_deref(),
+            &|key| std::env::var(key).ok()
+        )
+    }
+
+    /// Resolves the layers from explicit files and an environment lookup.
+    /// Missing files are skipped; malformed ones are an error.
+    pub fn load_from(
+        user: Option<&Path>,
+        project: Option<&Path>,
+        env: &dyn Fn(&str) -> Option<String>
+    ) -> anyhow::Result<Self> {
+        let mut merged = Table::new();
+        let mut sources = Vec::new();
+
+        for path in [user, project].into_iter().flatten() {
+            if !path.is_file() {
+                continue;
+            }
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("cannot read {}", path.display()))?;
+            let table: Table = text.parse()
+                .with_context(|| format!("invalid config {}", path.display()))?;
+            merge_tables(&mut merged, table);
+            sources.push(path.to_path_buf());
+        }
+
+        let env_table = env_layer(&merged, env);
+        merge_tables(&mut merged, env_table);
+
+        let mut config: Config = Value::Table(merged)
+            .try_into()
+            .context("invalid configuration value")?;
+        config.sources = sources;
+        config.history_file = config.storage.path_for(&config.history_file);
+        config.memory_file = config.storage.path_for(&config.memory_file);
+        Ok(config)
+    }
+
+    /// `summary_size`, defaulting to `SUMMARY_SIZE` (at most a third of `max_history`).
+    pub fn summary_size(&self) -> usize {
+        self.summary_size.unwrap_or(SUMMARY_SIZE.min(self.max_history / 3))
+    }
+
+    /// Token budget of the history when talking to `model`.
+    pub fn token_budget(&self, model: &str) -> TokenBudget {
+        TokenBudget::new(model, self.context_window, self.context_fraction)
+    }
+
+    /// `command_timeout` as passed to `Shell` (`None` → no limit).
+    pub fn command_timeout(&self) -> Option<u64> {
+        (self.command_timeout > 0).then_some(self.command_timeout)
+    }
+
+    /// Where sessions are kept: `<data_dir>/sessions`. `None` when there's
+    /// no data directory (no home directory).
+    pub fn sessions_dir(&self) -> Option<PathBuf> {
+        let data_dir = match &self.data_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::data_dir()?.join("cai"),
+        };
+        Some(data_dir.join("sessions"))
+    }
+
+    /// Where file checkpoints are kept: next to the history file, one
+    /// store per session (`history.json` → `history.checkpoints/`).
+    pub fn checkpoint_dir(&self) -> PathBuf {
+        Path::new(&self.history_file).with_extension("checkpoints")
+    }
+
+    /// Built-in command rules plus the configured ones.
+    pub fn command_rules(&self) -> CommandRules {
+        let mut rules = CommandRules::builtin();
+        rules.extend(&self.rules);
+        rules
+    }
+
+    /// The models served by the configured endpoint.
+    pub fn models(&self) -> ModelSet {
+        self.provider.models
+            .as_deref()
+            .and_then(ModelSet::parse)
+            .unwrap_or_else(|| ModelSet::for_provider(self.provider.kind))
+    }
+
+    /// The endpoint requests go to.
+    pub fn endpoint(&self) -> Endpoint {
+        Endpoint {
+            kind: self.provider.kind,
+            base_url: self.provider.base_url.clone(),
+            api_key: self.provider.api_key.clone(),
+            models: self.models(),
+        }
+    }
+
+    /// Renders the resolved config as TOML (secrets masked).
+    pub fn to_toml(&self) -> String {
+        let mut shown = self.clone();
+        if shown.provider.api_key.is_some() {
+            shown.provider.api_key = Some("***".to_string());
+        }
+        toml::to_string_pretty(&shown).unwrap_or_default()
+    }
+}
+
+/// `$XDG_CONFIG_HOME/cai/config.toml`, falling back to `~/.config/cai/config.toml`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some(base.join("cai").join(USER_CONFIG_FILE_NAME))
+}
+
+/// Nearest `.cai.toml` in `cwd` or one of its parents.
+pub fn find_project_config(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+/// Deep-merges `over` into `base`; tables merge key by key, anything else replaces.
+fn merge_tables(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(a)), Value::Table(b)) => merge_tables(a, b),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Builds the environment layer. `merged` (the file layers) is needed to
+/// know which vendor's `*_BASE_URL` / `*_API_KEY` variables apply.
+fn env_layer(merged: &Table, env: &dyn Fn(&str) -> Option<String>) -> Table {
+    let get = |key: &str| env(key).filter(|v| !v.trim().is_empty());
+    let mut layer = Table::new();
+    let mut provider = Table::new();
+
+    for (var, key) in [
+        ("CAI_MODEL", "model"),
+        ("CAI_LANGUAGE", "language"),
+        ("CAI_OS", "os"),
+        ("CAI_HISTORY_FILE", "history_file"),
+        ("CAI_MEMORY_FILE", "memory_file"),
+        ("CAI_DATA_DIR", "data_dir"),
+        ("CAI_APPROVAL", "approval"),
+    ] {
+        if let Some(v) = get(var) {
+            layer.insert(key.into(), Value::String(v));
+        }
+    }
+
+    for (var, key) in [
+        ("CAI_MAX_HISTORY", "max_history"),
+        ("CAI_SUMMARY_SIZE", "summary_size"),
+        ("CAI_MAX_CONTINUE", "max_continue"),
+        ("CAI_COMMAND_TIMEOUT", "command_timeout"),
+        ("CAI_CONTEXT_WINDOW", "context_window"),
+    ] {
+        if let Some(v) = get(var) {
+            match v.trim().parse::<i64>() {
+                Ok(n) => { layer.insert(key.into(), Value::Integer(n)); }
+                Err(_) => eprintln!("[WARNING] Ignoring {}={:?}: not a number", var, v),
+            }
+        }
+    }
+
+    if let Some(v) = get("CAI_STORAGE") {
+        match StorageKind::parse(&v) {
+            Some(kind) => { layer.insert("storage".into(), Value::String(kind.as_str().into())); }
+            None => eprintln!("[WARNING] Ignoring CAI_STORAGE={:?}: use json or sqlite", v),
+        }
+    }
+
+    if let Some(v) = get("CAI_CONTEXT_FRACTION") {
+        match v.trim().parse::<f64>() {
+            Ok(f) => { layer.insert("context_fraction".into(), Value::Float(f)); }
+            Err(_) => eprintln!("[WARNING] Ignoring CAI_CONTEXT_FRACTION={:?}: not a number", v),
+        }
+    }
+
+    let file_kind = merged
+        .get("provider").and_then(|p| p.get("kind")).and_then(|k| k.as_str())
+        .and_then(ProviderKind::parse);
+    let env_kind = get("CAI_PROVIDER").and_then(|k| ProviderKind::parse(&k));
+    if let Some(kind) = env_kind {
+        provider.insert("kind".into(), Value::String(kind.as_str().into()));
+    }
+
+    let (url_var, key_var) = match env_kind.or(file_kind).unwrap_or_default() {
+        ProviderKind::OpenAI => ("OPENAI_BASE_URL", "OPENAI_API_KEY"),
+        ProviderKind::Anthropic => ("ANTHROPIC_BASE_URL", "ANTHROPIC_API_KEY"),
+    };
+    for (var, key) in [(url_var, "base_url"), (key_var, "api_key"), ("CAI_MODELS", "models")] {
+        if let Some(v) = get(var) {
+            provider.insert(key.into(), Value::String(v));
+        }
+    }
+
+    if !provider.is_empty() {
+        layer.insert("provider".into(), Value::Table(provider));
+    }
+    layer
+}
@@ -1,18 +1,28 @@
 // ===================== Configuration Constants =====================
+// Built-in defaults -- every value below except the control tokens can be
+// overridden through `config.toml` / `.cai.toml` / env vars (see `config.rs`).
 
 // Default names
 pub const DEFAULT_HISTORY_FILE_NAME: &str = "history.json";
 pub const DEFAULT_MEMORY_FILE_NAME: &str = "memory.json";
 
-/// Maximum number of messages to keep in conversation history.
-pub const MAX_HISTORY: usize = 28;
+/// Maximum number of messages to keep in conversation history -- a backstop;
+/// summaries are normally triggered by the token budget below.
+pub const MAX_HISTORY: usize = 200;
 
-/// Number of messages to summarize (should be < MAX_HISTORY).
-pub const SUMMARY_SIZE: usize = MAX_HISTORY / 3;
+/// Minimum number of messages folded into one summary.
+pub const SUMMARY_SIZE: usize = 10;
+
+/// Fraction of the model's context window the history may fill before
+/// older messages are summarized.
+pub const CONTEXT_FRACTION: f64 = 0.6;
 
 /// Maximum allowed consecutive continue tokens before requiring user input.
 pub const MAX_CONTINUE: usize = 20;
 
+/// Seconds a shell command may run before it is killed (0 in config → no limit).
+pub const COMMAND_TIMEOUT_SECS: u64 = 10;
+
 /// Special tokens for control flow in AI responses.
 pub const RESTART_TOKEN: &str = "$$RESTART$$";
 pub const CONTINUE_TOKEN: &str = "$$CONTINUE$$";
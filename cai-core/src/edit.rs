@@ -0,0 +1,270 @@
+//! ===============================================================
+//! File Edits
+//!
+//! Applies search/replace edits and unified diffs to file contents
+//! (the `edit_file` tool), so the model can change a few lines of a
+//! large file without rewriting it.
+//!
+//! A diff hunk is turned into an edit: its context and `-` lines are
+//! searched for, and replaced by its context and `+` lines. Search
+//! text is located, in order, by:
+//!   1. exact text (must be unique unless a line hint is known),
+//!   2. whole lines ignoring trailing whitespace,
+//!   3. whole lines ignoring surrounding whitespace,
+//!   4. the most similar block of lines (at least `MIN_SIMILARITY`),
+//!      looked for within `FUZZY_SEARCH_TIMEOUT`.
+//!
+//! When several places match, the one nearest the hunk's line number
+//! wins; without a line number the edit fails as ambiguous. Failures
+//! name the hunk and show the closest text, so the model can retry.
+//! ===============================================================
+
+use std::{ collections::HashMap, time::{ Duration, Instant } };
+
+use anyhow::bail;
+use similar::TextDiff;
+
+/// Fuzzy matches below this similarity ratio are rejected.
+pub const MIN_SIMILARITY: f32 = 0.8;
+
+/// Time the fuzzy search of one edit may take, whatever the file size.
+const FUZZY_SEARCH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// One change: replace `search` with `replace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub search: String,
+    pub replace: String,
+
+    /// 1-based line where `search` is expected (from a diff hunk header).
+    pub line_hint: Option<usize>,
+}
+
+impl Edit {
+    pub fn new(search: impl Into<String>, replace: impl Into<String>) -> Self {
+        Self { search: search.into(), replace: replace.into(), line_hint: None }
+    }
+}
+
+/// Turns the hunks of a unified diff into edits. File headers (`---`,
+/// `+++`, `diff`, `index`) are skipped.
+pub fn parse_unified_diff(diff: &str) -> anyhow::Result<Vec<Edit>> {
+    let mut edits: Vec<Edit> = Vec::new();
+    let mut current: Option<Edit> = None;
+
+    let mut lines = diff.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with("@@") {
+            edits.extend(current.take());
+            current = Some(Edit { search: String::new(), replace: String::new(), line_hint: hunk_start(line) });
+            continue;
+        }
+        // A file header ends the hunk (`--- ` alone may be a removed `-- ` line)
+        let file_header = line.starts_with("diff ")
+            || (line.starts_with("--- ") && lines.peek().is_some_and(|next| next.starts_with("+++ ")));
+        if file_header {
+            edits.extend(current.take());
+            continue;
+        }
+        let Some(edit) = current.as_mut() else {
+            continue; // headers before the first hunk
+        };
+
+        match line.chars().next() {
+            Some('-') => push_line(&mut edit.search, &line[1..]),
+            Some('+') => push_line(&mut edit.replace, &line[1..]),
+            Some('\\') => {} // "\ No newline at end of file"
+            Some(' ') => {
+                push_line(&mut edit.search, &line[1..]);
+                push_line(&mut edit.replace, &line[1..]);
+            }
+            // Blank context lines often lose their leading space
+            None => {
+                push_line(&mut edit.search, "");
+                push_line(&mut edit.replace, "");
+            }
+            Some(_) => bail!("unexpected line in hunk: {:?}", line),
+        }
+    }
+    edits.extend(current);
+
+    if edits.is_empty() {
+        bail!("no hunks found (expected lines starting with @@)");
+    }
+    Ok(edits)
+}
+
+/// Applies `edits` in order. Fails on the first edit that doesn't match.
+pub fn apply_edits(content: &str, edits: &[Edit]) -> anyhow::Result<String> {
+    let mut content = content.to_string();
+    let mut shift: isize = 0; // lines added by earlier edits, for the hints
+
+    for (i, edit) in edits.iter().enumerate() {
+        let hint = edit.line_hint.map(|line| (line as isize + shift).max(1) as usize);
+        let before = content.lines().count() as isize;
+        content = apply_edit(&content, edit, hint)
+            .map_err(|e| anyhow::anyhow!("edit {} of {}: {}", i + 1, edits.len(), e))?;
+        shift += content.lines().count() as isize - before;
+    }
+    Ok(content)
+}
+
+/// Unified diff between two versions of `path`.
+pub fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+// ── Matching ──
+
+fn apply_edit(content: &str, edit: &Edit, hint: Option<usize>) -> Result<String, String> {
+    if edit.search.trim().is_empty() {
+        if content.trim().is_empty() {
+            return Ok(edit.replace.clone());
+        }
+        return Err("empty search text only works on an empty file".to_string());
+    }
+
+    // 1. Exact text -- also handles edits within a line
+    let exact: Vec<usize> = content.match_indices(edit.search.as_str()).map(|(at, _)| at).collect();
+    match exact.len() {
+        1 => {
+            let at = exact[0];
+            return Ok(format!("{}{}{}", &content[..at], edit.replace, &content[at + edit.search.len()..]));
+        }
+        n if n > 1 && hint.is_none() => {
+            let lines: Vec<String> = exact.iter().map(|at| (content[..*at].lines().count() + 1).to_string()).collect();
+            return Err(format!(
+                "search text matches {} places (lines {}); include more surrounding lines",
+                n,
+                lines.join(", ")
+            ));
+        }
+        _ => {}
+    }
+
+    // 2-3. Whole lines, ignoring whitespace
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let search: Vec<&str> = edit.search.lines().collect();
+    if search.len() > lines.len() {
+        return Err("search text is longer than the file".to_string());
+    }
+    let normalizers: [fn(&str) -> &str; 2] = [str::trim_end, str::trim];
+    for normalize in normalizers {
+        let starts: Vec<usize> = (0..=lines.len() - search.len())
+            .filter(|&start| {
+                search.iter().enumerate().all(|(i, s)| normalize(lines[start + i]) == normalize(s))
+            })
+            .collect();
+        if !starts.is_empty() {
+            let start = pick(&starts, hint)?;
+            return Ok(splice(&lines, start, search.len(), &edit.replace));
+        }
+    }
+
+    // 4. Most similar block. Windows without enough characters in common
+    // to beat the best one so far are skipped before diffing.
+    let wanted = search.join("\n");
+    let wanted_chars = char_counts(&wanted);
+    let deadline = Instant::now() + FUZZY_SEARCH_TIMEOUT;
+    let mut best: Vec<(usize, f32)> = Vec::new();
+    for start in 0..=lines.len() - search.len() {
+        if Instant::now() >= deadline {
+            break;
+        }
+        let window: String = lines[start..start + search.len()]
+            .iter()
+            .map(|l| l.trim_end_matches(['\n', '\r']))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if best.first().is_some_and(|(_, top)| max_ratio(&char_counts(&window), &wanted_chars) < *top) {
+            continue;
+        }
+        let ratio = TextDiff::configure()
+            .deadline(deadline)
+            .diff_chars(window.as_str(), wanted.as_str())
+            .ratio();
+        match best.first() {
+            Some((_, top)) if ratio < *top => {}
+            Some((_, top)) if ratio == *top => best.push((start, ratio)),
+            _ => best = vec![(start, ratio)],
+        }
+    }
+
+    let Some(&(start, ratio)) = best.first() else {
+        return Err("search text not found".to_string());
+    };
+    if ratio < MIN_SIMILARITY {
+        let closest: String = lines[start..start + search.len()].concat();
+        return Err(format!(
+            "search text not found; closest is lines {}-{} ({:.0}% similar):\n{}",
+            start + 1,
+            start + search.len(),
+            ratio * 100.0,
+            closest.trim_end()
+        ));
+    }
+    let starts: Vec<usize> = best.iter().map(|(start, _)| *start).collect();
+    let start = pick(&starts, hint)?;
+    Ok(splice(&lines, start, search.len(), &edit.replace))
+}
+
+/// How many times each character occurs in `text`.
+fn char_counts(text: &str) -> HashMap<char, usize> {
+    let mut counts = HashMap::new();
+    for c in text.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Upper bound of the `diff_chars` ratio of two texts: a diff can't match
+/// more characters than they have in common.
+fn max_ratio(a: &HashMap<char, usize>, b: &HashMap<char, usize>) -> f32 {
+    let shared: usize = a.iter().map(|(c, n)| (*n).min(b.get(c).copied().unwrap_or(0))).sum();
+    let len: usize = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if len == 0 { 1.0 } else { 2.0 * shared as f32 / len as f32 }
+}
+
+/// The only candidate, or the one nearest the hint.
+fn pick(starts: &[usize], hint: Option<usize>) -> Result<usize, String> {
+    match (starts, hint) {
+        ([start], _) => Ok(*start),
+        (_, Some(line)) => Ok(*starts.iter().min_by_key(|s| (**s + 1).abs_diff(line)).unwrap()),
+        _ => {
+            let lines: Vec<String> = starts.iter().map(|s| (s + 1).to_string()).collect();
+            Err(format!(
+                "search text matches {} places (lines {}); include more surrounding lines",
+                starts.len(),
+                lines.join(", ")
+            ))
+        }
+    }
+}
+
+/// Replaces `count` lines from `start` with `replace`, keeping the line
+/// break the replaced block ended with.
+fn splice(lines: &[&str], start: usize, count: usize, replace: &str) -> String {
+    let mut out: String = lines[..start].concat();
+    out.push_str(replace);
+    let ended_with_newline = lines[start + count - 1].ends_with('\n');
+    if !replace.is_empty() && ended_with_newline && !replace.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&lines[start + count..].concat());
+    out
+}
+
+/// `@@ -12,7 +12,8 @@` → 12
+fn hunk_start(header: &str) -> Option<usize> {
+    let old = header.split_whitespace().find(|w| w.starts_with('-'))?;
+    old[1..].split(',').next()?.parse().ok()
+}
+
+fn push_line(text: &mut String, line: &str) {
+    text.push_str(line);
+    text.push('\n');
+}
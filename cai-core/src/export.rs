@@ -0,0 +1,292 @@
+//! ===============================================================
+//! Export / Import
+//!
+//! Renders a conversation (the current branch of a session) for
+//! sharing:
+//!   * Markdown -- roles as headings, tool calls and results as
+//!     fenced blocks, the system prompt folded away
+//!   * HTML     -- one standalone page; code blocks go through a
+//!     highlighter supplied by the UI (see `cai_cli::highlight_html`)
+//!   * JSONL    -- OpenAI fine-tuning format, one conversation per
+//!     line; `from_jsonl` reads it back
+//!
+//! ===============================================================
+
+use anyhow::{ bail, Context };
+use serde_json::{ json, Value };
+
+use crate::history_manager::SUMMARY_PREFIX;
+use crate::sessions::SessionMeta;
+use crate::types::{ ChatMessage, MessageRole, ToolCall };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" => Some(Self::Html),
+            "jsonl" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+
+    /// The format matching a file name's extension.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension().and_then(|ext| ext.to_str()).and_then(Self::parse)
+    }
+}
+
+/// Highlights `code` written in `lang` as an HTML fragment.
+pub type Highlighter<'a> = &'a dyn Fn(&str, &str) -> String;
+
+/// What a message is, for display.
+enum Kind<'a> {
+    SystemPrompt,
+    Summary(&'a str),
+    ToolCalls,
+    ToolResult,
+    Plain,
+}
+
+fn kind_of(index: usize, message: &ChatMessage) -> Kind<'_> {
+    match message.role {
+        MessageRole::System if index == 0 => Kind::SystemPrompt,
+        MessageRole::System if message.content.starts_with(SUMMARY_PREFIX) => {
+            Kind::Summary(message.content[SUMMARY_PREFIX.len()..].trim())
+        }
+        MessageRole::Tool => Kind::ToolResult,
+        _ if !message.tool_calls.is_empty() => Kind::ToolCalls,
+        _ => Kind::Plain,
+    }
+}
+
+fn role_name(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::User => "User",
+        MessageRole::Assistant => "Assistant",
+        MessageRole::System => "System",
+        MessageRole::Tool => "Tool",
+    }
+}
+
+fn title_of(meta: Option<&SessionMeta>) -> String {
+    match meta {
+        Some(meta) if !meta.title.is_empty() => meta.title.clone(),
+        Some(meta) => format!("Session {}", meta.id),
+        None => "Conversation".to_string(),
+    }
+}
+
+/// Pretty-printed tool arguments (raw when they aren't JSON).
+fn pretty_args(call: &ToolCall) -> String {
+    serde_json::from_str::<Value>(&call.arguments)
+        .and_then(|args| serde_json::to_string_pretty(&args))
+        .unwrap_or_else(|_| call.arguments.clone())
+}
+
+// ── Markdown ──
+
+/// A fence longer than any backtick run in `text`.
+fn fence_for(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn fenced(lang: &str, text: &str) -> String {
+    let fence = fence_for(text);
+    format!("{}{}\n{}\n{}\n", fence, lang, text.trim_end_matches('\n'), fence)
+}
+
+pub fn to_markdown(messages: &[ChatMessage], meta: Option<&SessionMeta>) -> String {
+    let mut out = format!("# {}\n\n", title_of(meta));
+    if let Some(meta) = meta {
+        out.push_str(&format!("- Session: `{}`\n", meta.id));
+        if !meta.model.is_empty() {
+            out.push_str(&format!("- Model: `{}`\n", meta.model));
+        }
+        out.push_str(&format!("- Directory: `{}`\n\n", meta.cwd.display()));
+    }
+
+    for (index, message) in messages.iter().enumerate() {
+        match kind_of(index, message) {
+            Kind::SystemPrompt => out.push_str(&format!(
+                "<details>\n<summary>System prompt</summary>\n\n{}\n</details>\n\n",
+                fenced("text", &message.content)
+            )),
+            Kind::Summary(summary) => out.push_str(&format!("## Summary of earlier messages\n\n{}\n\n", summary)),
+            Kind::ToolCalls => {
+                out.push_str("## Assistant\n\n");
+                if !message.content.trim().is_empty() {
+                    out.push_str(&format!("{}\n\n", message.content.trim()));
+                }
+                for call in &message.tool_calls {
+                    out.push_str(&format!("**Tool call** `{}` ({})\n\n{}\n", call.name, call.id, fenced("json", &pretty_args(call))));
+                }
+            }
+            Kind::ToolResult => out.push_str(&format!(
+                "**Tool result** ({})\n\n{}\n",
+                message.tool_call_id.as_deref().unwrap_or("?"),
+                fenced("text", &message.content)
+            )),
+            Kind::Plain => out.push_str(&format!("## {}\n\n{}\n\n", role_name(&message.role), message.content.trim())),
+        }
+    }
+    out
+}
+
+// ── HTML ──
+
+const HTML_STYLE: &str = "\
+body { max-width: 52rem; margin: 2rem auto; padding: 0 1rem; font: 15px/1.5 system-ui, sans-serif; color: #222; }
+h1 { font-size: 1.5rem; } .meta { color: #666; font-size: 0.9rem; }
+section { border-left: 4px solid #ccc; margin: 1.2rem 0; padding: 0.2rem 1rem; }
+section.user { border-color: #4a7bd0; } section.assistant { border-color: #3a9a5b; }
+section.system, section.summary { border-color: #aaa; background: #f7f7f7; } section.tool { border-color: #c8912e; }
+.role { font-weight: 600; font-size: 0.85rem; text-transform: uppercase; color: #555; }
+.text { white-space: pre-wrap; } pre { padding: 0.6rem; overflow-x: auto; border-radius: 4px; background: #2b303b; color: #c0c5ce; }
+code { font-family: ui-monospace, monospace; font-size: 0.85rem; }";
+
+/// HTML-escapes `text`.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Highlighter that only escapes.
+pub fn plain_code_html(_lang: &str, code: &str) -> String {
+    format!("<pre><code>{}</code></pre>", escape_html(code))
+}
+
+/// Prose with ```lang fenced blocks highlighted.
+fn text_html(text: &str, highlight: Highlighter) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let (prose, block) = rest.split_at(start);
+        let Some(newline) = block.find('\n') else { break };
+        let lang = block[3..newline].trim();
+        let Some(end) = block[newline + 1..].find("```") else { break };
+        if !prose.trim().is_empty() {
+            out.push_str(&format!("<div class=\"text\">{}</div>\n", escape_html(prose.trim())));
+        }
+        out.push_str(&highlight(lang, &block[newline + 1..newline + 1 + end]));
+        out.push('\n');
+        rest = &block[newline + 1 + end + 3..];
+    }
+    if !rest.trim().is_empty() {
+        out.push_str(&format!("<div class=\"text\">{}</div>\n", escape_html(rest.trim())));
+    }
+    out
+}
+
+fn section(class: &str, label: &str, body: &str) -> String {
+    format!("<section class=\"{}\">\n<div class=\"role\">{}</div>\n{}</section>\n", class, escape_html(label), body)
+}
+
+pub fn to_html(messages: &[ChatMessage], meta: Option<&SessionMeta>, highlight: Highlighter) -> String {
+    let title = escape_html(&title_of(meta));
+    let mut body = format!("<h1>{}</h1>\n", title);
+    if let Some(meta) = meta {
+        body.push_str(&format!(
+            "<p class=\"meta\">{}</p>\n",
+            [format!("Session {}", meta.id), meta.model.clone(), meta.cwd.display().to_string()]
+                .iter()
+                .filter(|part| !part.is_empty())
+                .map(|part| escape_html(part))
+                .collect::<Vec<_>>()
+                .join(" · ")
+        ));
+    }
+
+    for (index, message) in messages.iter().enumerate() {
+        body.push_str(&match kind_of(index, message) {
+            Kind::SystemPrompt => section(
+                "system",
+                "System",
+                &format!("<details><summary>System prompt</summary>\n{}</details>\n", highlight("text", &message.content))
+            ),
+            Kind::Summary(summary) => section("summary", "Summary of earlier messages", &text_html(summary, highlight)),
+            Kind::ToolCalls => {
+                let mut html = text_html(&message.content, highlight);
+                for call in &message.tool_calls {
+                    html.push_str(&format!(
+                        "<details open><summary>Tool call <code>{}</code></summary>\n{}</details>\n",
+                        escape_html(&call.name),
+                        highlight("json", &pretty_args(call))
+                    ));
+                }
+                section("assistant", "Assistant", &html)
+            }
+            Kind::ToolResult => section(
+                "tool",
+                "Tool result",
+                &format!("<details><summary>Output</summary>\n{}</details>\n", highlight("text", &message.content))
+            ),
+            Kind::Plain => {
+                let role = role_name(&message.role);
+                section(&role.to_lowercase(), role, &text_html(&message.content, highlight))
+            }
+        });
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        title, HTML_STYLE, body
+    )
+}
+
+// ── JSONL ──
+
+/// One fine-tuning example: `{"messages": [...]}` on a single line.
+pub fn to_jsonl(messages: &[ChatMessage]) -> String {
+    let messages: Vec<Value> = messages.iter().map(ChatMessage::to_json).collect();
+    format!("{}\n", json!({ "messages": messages }))
+}
+
+/// Reads fine-tuning JSONL: one conversation per non-empty line.
+pub fn from_jsonl(text: &str) -> anyhow::Result<Vec<Vec<ChatMessage>>> {
+    let mut conversations = Vec::new();
+    for (n, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let example: Value = serde_json::from_str(line).with_context(|| format!("line {}: invalid JSON", n + 1))?;
+        let messages = example.get("messages").and_then(|m| m.as_array())
+            .with_context(|| format!("line {}: no \"messages\" array", n + 1))?;
+        let messages = messages.iter()
+            .map(message_from_json)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("line {}", n + 1))?;
+        conversations.push(messages);
+    }
+    Ok(conversations)
+}
+
+fn message_from_json(value: &Value) -> anyhow::Result<ChatMessage> {
+    let role = match value.get("role").and_then(|r| r.as_str()) {
+        Some("system") | Some("developer") => MessageRole::System,
+        Some("user") => MessageRole::User,
+        Some("assistant") => MessageRole::Assistant,
+        Some("tool") => MessageRole::Tool,
+        Some(other) => bail!("unknown role `{}`", other),
+        None => bail!("message without a role"),
+    };
+    // Content is a string, null, or a list of parts
+    let content = match value.get("content") {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts.iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    };
+
+    let mut message = ChatMessage::new(role, content);
+    if let Some(calls) = value.get("tool_calls").and_then(|c| c.as_array()) {
+        message.tool_calls = calls.iter().map(ToolCall::from_json).collect();
+    }
+    message.tool_call_id = value.get("tool_call_id").and_then(|id| id.as_str()).map(str::to_string);
+    Ok(message)
+}
@@ -4,18 +4,80 @@
 //! Manages persistent conversation history for the AI client.
 //! Handles saving, loading, summarizing, and pruning of messages.
 //! Ensures context is preserved and efficiently managed.
+//!
+//! Messages form a tree: each one points at its parent, and a cursor
+//! marks the current leaf. The conversation sent to the model is the
+//! path from the root to the cursor; editing an earlier message or
+//! regenerating a reply starts a sibling branch, so the original one
+//! is kept and can be switched back to. History files written before
+//! the tree (a plain array of messages) load as a single branch.
+//! Where the tree is kept is up to a `HistoryStore` (see `storage`).
+//!
+//! Summaries are triggered when the current path outgrows its token
+//! budget (a fraction of the model's context window) or, as a backstop,
+//! the maximum message count. The oldest messages are folded until the
+//! path is back to half the budget, stopping at the start of a turn
+//! (or of a reply, within one long turn) so a command and its output
+//! (or a tool call and its result) are never split. They're only cut
+//! once the summary came back. Large tool outputs are compacted on the
+//! way: in the summary prompt, and in the older turns that stay.
 //! ===============================================================
 
-use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{ bail, Context };
+use serde::{ Deserialize, Serialize };
+
+use crate::{
+    prompt::SUMMARY_HISTORY_PROMPT,
+    search::SearchIndex,
+    sessions::now,
+    storage::{ history_store, HistoryStore, Tree },
+    tokens::{ TokenBudget, Tokenizer, MESSAGE_OVERHEAD },
+    types::{ ChatMessage, MessageRole },
+};
+
+/// Characters of a message shown per line by `render_tree`.
+const TREE_PREVIEW_CHARS: usize = 60;
+
+/// Start of the system messages holding history summaries.
+pub const SUMMARY_PREFIX: &str = "[Conversation summary]";
 
-use crate::{prompt::SUMMARY_HISTORY_PROMPT, types::{ChatMessage, MessageRole}};
+/// Characters of a tool output kept when compacting it (head and tail).
+pub const COMPACT_TOOL_OUTPUT_CHARS: usize = 2000;
+
+/// A message in the conversation tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub id: usize,
+
+    /// `None` → a root.
+    pub parent: Option<usize>,
+
+    /// Unix time it was added; 0 → unknown (written before times were kept).
+    #[serde(default)]
+    pub time: u64,
+
+    #[serde(flatten)]
+    pub message: ChatMessage,
+}
 
 /// Stores and manages the conversation history.
 pub struct History {
-    pub messages: Vec<ChatMessage>,
-    pub file_path: String,
+    /// Every message of every branch; a node's id is its index.
+    nodes: Vec<Node>,
+
+    /// Leaf of the current branch.
+    cursor: Option<usize>,
+
+    /// Where it's loaded from and saved to; `None` → never saved.
+    store: Option<Box<dyn HistoryStore>>,
     max_history: usize,
     summary_size: usize,
+    budget: Option<TokenBudget>,
+
+    /// Search index kept up to date on save, and the session's id in it.
+    index: Option<(PathBuf, String)>,
 }
 
 impl History {
@@ -24,103 +86,367 @@ impl History {
     /// # Arguments
     /// * `file` - Path to the history file.
     /// * `max_history` - Maximum number of messages to keep.
-    /// * `summary_size` - Number of messages to summarize at a time.
+    /// * `summary_size` - Minimum number of messages to summarize at a time.
     pub fn new(file: &str, max_history: usize, summary_size: usize) -> Self {
-        let messages: Vec<ChatMessage> = if let Ok(file) = fs::File::open(file) {
-            serde_json::from_reader(file).unwrap_or_else(|_| Vec::new())
-        } else {
-            Vec::new()
-        };
+        Self::open(history_store(file), max_history, summary_size)
+    }
+
+    /// Creates a history kept in `store` (see `storage`).
+    pub fn open(mut store: Box<dyn HistoryStore>, max_history: usize, summary_size: usize) -> Self {
+        let mut history = Self::ephemeral(max_history, summary_size);
+        match store.load() {
+            Ok(Some(Tree { nodes, cursor })) => {
+                if is_valid_tree(&nodes) && cursor.is_none_or(|c| c < nodes.len()) {
+                    history.nodes = nodes;
+                    history.cursor = cursor;
+                } else {
+                    eprintln!("[WARNING] Ignoring malformed history tree in {}", store.path().display());
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[WARNING] Ignoring history: {:#}", e),
+        }
+        history.store = Some(store);
+        history
+    }
+
+    /// Creates a history that is never written to disk (e.g. one-shot runs).
+    pub fn ephemeral(max_history: usize, summary_size: usize) -> Self {
         History {
-            messages,
-            file_path: file.to_string(),
+            nodes: Vec::new(),
+            cursor: None,
+            store: None,
             max_history,
             summary_size,
+            budget: None,
+            index: None,
         }
     }
 
-    /// Adds a message to the history.
+    /// Updates the search index at `index_file` on every save, as session `session`.
+    pub fn set_index(&mut self, index_file: impl Into<PathBuf>, session: &str) {
+        self.index = Some((index_file.into(), session.to_string()));
+    }
+
+    /// Adds a message to the current branch.
     pub fn add_message(&mut self, role: MessageRole, content: String) {
-        self.messages.push(
-            ChatMessage {
-                role,
-                content
+        self.push(ChatMessage::new(role, content));
+    }
+
+    /// Adds a message (e.g. a tool call or result) to the current branch.
+    pub fn push(&mut self, message: ChatMessage) {
+        let id = self.nodes.len();
+        self.nodes.push(Node { id, parent: self.cursor, time: now(), message });
+        self.cursor = Some(id);
+    }
+
+    // ── Branches ──
+
+    /// Leaf of the current branch.
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    pub fn node(&self, id: usize) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    /// Ids from the root to the cursor.
+    pub fn path(&self) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut next = self.cursor;
+        while let Some(id) = next {
+            path.push(id);
+            next = self.nodes[id].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Children of `id`, oldest first.
+    pub fn children(&self, id: usize) -> Vec<usize> {
+        self.nodes.iter().filter(|node| node.parent == Some(id)).map(|node| node.id).collect()
+    }
+
+    /// Continues from message `id`: the next message starts a new branch
+    /// there (unless `id` is a leaf).
+    pub fn checkout(&mut self, id: usize) -> anyhow::Result<()> {
+        self.node(id).with_context(|| format!("no message #{}", id))?;
+        self.cursor = Some(id);
+        Ok(())
+    }
+
+    /// Switches to the branch through `id`, following the newest child
+    /// down to its leaf.
+    pub fn switch(&mut self, id: usize) -> anyhow::Result<()> {
+        self.checkout(id)?;
+        let mut leaf = id;
+        while let Some(&child) = self.children(leaf).last() {
+            leaf = child;
+        }
+        self.cursor = Some(leaf);
+        Ok(())
+    }
+
+    /// Replaces user message `id` by `content` on a new branch; the
+    /// old one is kept. The new message becomes the cursor.
+    pub fn edit(&mut self, id: usize, content: String) -> anyhow::Result<()> {
+        let node = self.node(id).with_context(|| format!("no message #{}", id))?;
+        if !matches!(node.message.role, MessageRole::User) {
+            bail!("message #{} is not a user message", id);
+        }
+        self.cursor = node.parent;
+        self.add_message(MessageRole::User, content);
+        Ok(())
+    }
+
+    /// Moves the cursor to just before the latest assistant reply (and
+    /// the tool calls that led to it), so the next reply becomes a
+    /// sibling of it.
+    pub fn retry_last_reply(&mut self) -> anyhow::Result<()> {
+        let path = self.path();
+        let mut start = path.iter()
+            .rposition(|&id| matches!(self.nodes[id].message.role, MessageRole::Assistant))
+            .context("no assistant reply to regenerate")?;
+        while start > 0 && self.nodes[path[start - 1]].message.is_tool_traffic() {
+            start -= 1;
+        }
+        self.cursor = self.nodes[path[start]].parent;
+        Ok(())
+    }
+
+    /// The whole tree, one message per line. Single-child chains stay
+    /// at one level; forks indent their branches. `*` marks the current
+    /// path.
+    pub fn render_tree(&self) -> String {
+        let path = self.path();
+        let mut out = String::new();
+        let mut stack: Vec<(usize, usize)> = self.nodes.iter()
+            .filter(|node| node.parent.is_none())
+            .map(|node| (node.id, 0))
+            .rev()
+            .collect();
+
+        while let Some((id, depth)) = stack.pop() {
+            let message = &self.nodes[id].message;
+            let calls = message.tool_calls.iter().map(|c| format!("{}()", c.name)).collect::<Vec<_>>().join(", ");
+            let text = if message.content.trim().is_empty() { calls.as_str() } else { message.content.as_str() };
+            let line = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+            let mut preview: String = line.chars().take(TREE_PREVIEW_CHARS).collect();
+            if line.chars().count() > TREE_PREVIEW_CHARS {
+                preview.push('…');
             }
-        )
+            out.push_str(&format!(
+                "{}{} #{} {:?}: {}\n",
+                "  ".repeat(depth),
+                if path.contains(&id) { "*" } else { " " },
+                id,
+                message.role,
+                preview
+            ));
+
+            let children = self.children(id);
+            let depth = if children.len() > 1 { depth + 1 } else { depth };
+            stack.extend(children.into_iter().rev().map(|child| (child, depth)));
+        }
+        out
+    }
+
+    // ── Token budget & summaries ──
+
+    /// Sets the token budget (depends on the model in use).
+    pub fn set_budget(&mut self, budget: TokenBudget) {
+        self.budget = Some(budget);
+    }
+
+    /// Tokens the current branch takes, counted with the budget's
+    /// tokenizer (estimated when no budget is set).
+    pub fn tokens(&self) -> usize {
+        self.get().iter().map(|msg| self.message_tokens(msg)).sum()
+    }
+
+    fn message_tokens(&self, message: &ChatMessage) -> usize {
+        let tokenizer = self.budget.map_or(Tokenizer::Estimate, |b| b.tokenizer);
+        let calls: usize = message.tool_calls.iter()
+            .map(|call| tokenizer.count(&call.name) + tokenizer.count(&call.arguments) + MESSAGE_OVERHEAD)
+            .sum();
+        tokenizer.count(&message.content) + calls + MESSAGE_OVERHEAD
     }
 
     /// Checks if the history needs summarization.
     pub fn needs_summarize(&self) -> bool {
-        self.messages.len() > self.max_history
+        self.path().len() > self.max_history
+            || self.budget.is_some_and(|budget| self.tokens() > budget.max_tokens)
     }
 
-    /// Generates a prompt for summarizing the conversation.
-    pub fn get_summarize_prompt(&mut self) -> String {
-        if self.messages.len() <= self.summary_size { return "".to_string(); }
+    /// How many messages after the first to summarize: enough to get back
+    /// to half the token budget (at least `summary_size`), extended to the
+    /// next turn boundary -- or cut back to the previous one when there's
+    /// none. A single long turn is cut at a reply instead, never between a
+    /// tool call and its results. The last message is always kept.
+    fn summarize_count(&self, messages: &[ChatMessage]) -> usize {
+        let last = messages.len().saturating_sub(1);
+        if last <= 1 {
+            return 0;
+        }
 
-        let messages: Vec<ChatMessage> = self.messages.drain(1..self.summary_size + 1).collect();
-        let messages_str = messages.iter().map(|msg| format!("{:?}", msg.content)).collect::<Vec<String>>().join("\n");
+        let mut end = (1 + self.summary_size).min(last);
+        if let Some(budget) = self.budget {
+            let target = budget.max_tokens / 2;
+            let mut remaining = self.tokens()
+                - messages[1..end].iter().map(|msg| self.message_tokens(msg)).sum::<usize>();
+            while remaining > target && end < last {
+                remaining -= self.message_tokens(&messages[end]);
+                end += 1;
+            }
+        }
+        let turn = |i: &usize| starts_turn(messages, *i);
+        let reply = |i: &usize| starts_turn(messages, *i) || matches!(messages[*i].role, MessageRole::Assistant);
+        let end = (end..=last).find(turn)
+            .or_else(|| (2..end).rev().find(turn))
+            // No boundary at all: one long turn
+            .or_else(|| (end..=last).find(reply))
+            .or_else(|| (2..end).rev().find(reply))
+            .unwrap_or(1);
+        end - 1
+    }
 
+    /// Generates a prompt for summarizing the conversation, or "" when
+    /// there's nothing that can be summarized. Nothing is cut until
+    /// `insert_summary` gets the summary.
+    pub fn get_summarize_prompt(&self) -> String {
+        let messages = self.get();
+        let count = self.summarize_count(&messages);
+        if count == 0 { return "".to_string(); }
+
+        let messages_str = messages[1..count + 1].iter().map(summary_line).collect::<Vec<String>>().join("\n");
         format!("{}\n---\n{}", SUMMARY_HISTORY_PROMPT, messages_str)
     }
 
-    /// Inserts a summary into the history.
+    /// Inserts a summary into the current branch, after its first message,
+    /// in place of the messages `get_summarize_prompt` listed (other
+    /// branches keep them). Tool outputs of the kept turns before the
+    /// latest one are compacted.
     pub fn insert_summary(&mut self, summary: String) {
+        let path = self.path();
+        if path.len() < 2 {
+            self.add_message(MessageRole::System, summary);
+            return;
+        }
 
-        // Ensure we don't fuck up
-        let insertion_index = if self.messages.len() > 1 {
-            1 // Insert after the first message
-        } else {
-            0 // If there's only one message, insert at the start
-        };
+        let messages = self.get();
+        let count = self.summarize_count(&messages);
+        let latest_turn = (count + 1..messages.len()).rev().find(|&i| starts_turn(&messages, i)).unwrap_or(count + 1);
+        for &id in &path[count + 1..latest_turn] {
+            let message = &mut self.nodes[id].message;
+            if matches!(message.role, MessageRole::Tool) {
+                message.content = compact(&message.content, COMPACT_TOOL_OUTPUT_CHARS);
+            }
+        }
 
-        self.messages.insert(insertion_index, ChatMessage {
-            role: MessageRole::System,
-            content: summary,
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            id,
+            parent: Some(path[0]),
+            time: now(),
+            message: ChatMessage::new(MessageRole::System, summary),
         });
+        self.nodes[path[count + 1]].parent = Some(id);
+    }
+
+    // ── Access & storage ──
+
+    /// Every message of every branch, by id.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
     }
 
-    /// Returns the current history as a vector of messages.
+    /// Returns the current branch as a vector of messages.
     pub fn get(&self) -> Vec<ChatMessage> {
-        self.messages.clone()
+        self.path().into_iter().map(|id| self.nodes[id].message.clone()).collect()
     }
 
-    /// Saves the history to disk.
-    pub fn save(&self) {
-        if let Ok(file) = fs::File::create(&self.file_path) {
-            serde_json::to_writer_pretty(file, &self.messages).unwrap();
+    /// Saves the history to its store. A SQLite store may merge in
+    /// messages another process saved meanwhile.
+    pub fn save(&mut self) {
+        let Some(store) = self.store.as_mut() else { return };
+        let mut tree = Tree { nodes: std::mem::take(&mut self.nodes), cursor: self.cursor };
+        if let Err(e) = store.save(&mut tree) {
+            eprintln!("[WARNING] History not saved: {:#}", e);
         }
-    }
+        self.nodes = tree.nodes;
+        self.cursor = tree.cursor;
 
-    /// Loads the history from disk. -- not used since we load it in the constructor
-    // pub fn load(&mut self) {
-    //     let messages: Vec<ChatMessage> = if let Ok(file) = fs::File::open(&self.file_path) {
-    //         serde_json::from_reader(file).unwrap_or_else(|_| Vec::new())
-    //     } else {
-    //         Vec::new()
-    //     };
-    //     self.messages = messages;
-    // }
+        if let Some((index_file, session)) = &self.index {
+            let mut index = SearchIndex::open(index_file);
+            index.update(session, &self.nodes);
+            if let Err(e) = index.save() {
+                eprintln!("[WARNING] Search index not updated: {:#}", e);
+            }
+        }
+    }
 
-    /// Clears the history.
+    /// Clears the history, every branch included.
     pub fn clear(&mut self) {
-        if let Some(init) = self.messages.first().cloned() {
-            self.messages = vec![init];
-        } else {
+        let init = self.path().first().map(|&id| self.nodes[id].message.clone()).unwrap_or_else(|| {
             // If there's no first message, initialize with a safe system prompt
-            self.messages = vec![ChatMessage {
-                role: MessageRole::System,
-                content: SUMMARY_HISTORY_PROMPT.to_string(),
-            }];
-        }
+            ChatMessage::new(MessageRole::System, SUMMARY_HISTORY_PROMPT)
+        });
+        self.nodes.clear();
+        self.cursor = None;
+        self.push(init);
 
-        if let Ok(file) = fs::File::create(&self.file_path) {
-            serde_json::to_writer_pretty(file,  &self.messages).unwrap();
-        }
+        self.save();
     }
 
     /// Checks if the history is empty.
     pub fn is_empty(&self) -> bool {
-        self.messages.is_empty()
+        self.nodes.is_empty()
+    }
+}
+
+/// Ids are indices, parents exist and every node reaches a root.
+fn is_valid_tree(nodes: &[Node]) -> bool {
+    nodes.iter().enumerate().all(|(i, node)| {
+        let mut next = node.parent;
+        for _ in 0..nodes.len() {
+            match next {
+                None => return node.id == i,
+                Some(p) if p < nodes.len() => next = nodes[p].parent,
+                Some(_) => return false,
+            }
+        }
+        false
+    })
+}
+
+/// Whether a new turn starts at `index`: a user message, or a system
+/// message that isn't the output of the preceding assistant reply or
+/// tool call.
+fn starts_turn(messages: &[ChatMessage], index: usize) -> bool {
+    match messages[index].role {
+        MessageRole::User => true,
+        MessageRole::System => !matches!(messages[index - 1].role, MessageRole::Assistant | MessageRole::Tool),
+        MessageRole::Assistant | MessageRole::Tool => false,
     }
-}
\ No newline at end of file
+}
+
+/// A message as listed in the summary prompt; tool outputs compacted.
+fn summary_line(message: &ChatMessage) -> String {
+    let text = match message.role {
+        MessageRole::Tool => format!("[tool result] {}", compact(&message.content, COMPACT_TOOL_OUTPUT_CHARS)),
+        _ => message.to_text_json()["content"].as_str().unwrap_or_default().to_string(),
+    };
+    format!("{:?}", text)
+}
+
+/// `text` cut to its first and last `max / 2` characters.
+pub fn compact(text: &str, max: usize) -> String {
+    let chars = text.chars().count();
+    if chars <= max {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(max / 2).collect();
+    let tail: String = text.chars().skip(chars - max / 2).collect();
+    format!("{}\n[... {} characters cut ...]\n{}", head, chars - max, tail)
+}
@@ -3,6 +3,9 @@ pub mod ui_trait;
 // Client module -- handles AI interactions
 pub mod client;
 
+// Response processing loop -- executes blocks and follows control tokens
+pub mod agent_loop;
+
 // Contains the prompt templates and instructions
 pub mod prompt;
 
@@ -19,17 +22,56 @@ pub mod types;
 // Handles the history of messages
 pub mod history_manager;
 
+// Token counting and per-model context budgets
+pub mod tokens;
+
+// History and memory backends -- JSON files or SQLite
+pub mod storage;
+
+// Named sessions -- one history and checkpoint store each
+pub mod sessions;
+
+// Markdown / HTML / JSONL export of conversations, JSONL import
+pub mod export;
+
+// Ranked keyword search over every saved session
+pub mod search;
+
 // Memory module -- handles memory management
 pub mod memory_manager;
 
-// Essentially a wrapper around the OpenAI API to respect the rate limits
+// LLM backends -- the `Provider` trait and its implementations
+pub mod provider;
+
+// Retry/back-off wrapper around providers and tool helpers
 pub mod client_util;
 
+// Native tools -- the `Tool` trait and the registry the client dispatches through
+pub mod tools;
+
+// Approval gate -- asks the user before actions run
+pub mod approval;
+
+// Allow/deny rules for shell commands
+pub mod rules;
+
+// Workspace root file tools are confined to
+pub mod workspace;
+
+// Snapshots of files taken before the agent writes them
+pub mod checkpoints;
+
+// Search/replace and unified-diff edits for the `edit_file` tool
+pub mod edit;
+
 // Contains the passive context for the AI -- used to make the AI "think" better
 pub mod passive_context;
 
-// Contains the configuration constants
+// Contains the configuration constants -- built-in defaults
 pub mod constants;
 
+// Layered configuration: defaults, config files, environment
+pub mod config;
+
 pub mod models;
 
@@ -4,15 +4,102 @@
 //! Manages long-term memory for the AI client.
 //! Allows reading, writing, and clearing of persistent memory.
 //! Used to provide context and continuity across sessions.
+//!
+//! Each memory is an entry with a stable id, tags, created/updated
+//! times, the session it came from and an importance score. Entries
+//! are kept in a `MemoryStore` (JSON file or SQLite, see `storage`);
+//! every change is applied to the store and re-read, so changes made
+//! by another process in between are kept. Memory files from before
+//! entries (one memory per line) load as entries with fresh ids.
 //! ===============================================================
 
 
-use regex::Regex;
+use std::sync::{ Arc, Mutex };
+
+use regex::RegexBuilder;
+use serde::{ Deserialize, Serialize };
+
+use crate::sessions::now;
+use crate::storage::{ memory_store, MemoryStore };
+
+/// Importance of entries added without one (scores go from 0 to 1).
+pub const DEFAULT_IMPORTANCE: f64 = 0.5;
+
+/// Entries put into the system prompt at session start.
+pub const PROMPT_MEMORIES: usize = 20;
+
+/// Memory shared by the client and the memory tools.
+pub type SharedMemory = Arc<Mutex<MemoryManager>>;
+
+/// One thing to remember.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    /// Assigned by the store; never reused.
+    pub id: u64,
+    pub content: String,
+
+    /// Lowercase, without duplicates.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Unix times, in seconds.
+    pub created: u64,
+    pub updated: u64,
+
+    /// Session the entry was added in; `None` → outside a session.
+    #[serde(default)]
+    pub source: Option<String>,
+
+    /// From 0 (trivia) to 1 (always relevant).
+    #[serde(default = "default_importance")]
+    pub importance: f64,
+}
+
+fn default_importance() -> f64 {
+    DEFAULT_IMPORTANCE
+}
+
+impl MemoryEntry {
+    /// A new entry; the store assigns the id.
+    pub fn new(content: &str) -> Self {
+        let time = now();
+        Self {
+            id: 0,
+            content: content.trim().to_string(),
+            tags: Vec::new(),
+            created: time,
+            updated: time,
+            source: None,
+            importance: DEFAULT_IMPORTANCE,
+        }
+    }
+
+    /// `#3 [rust, style] prefers tabs`
+    pub fn line(&self) -> String {
+        if self.tags.is_empty() {
+            format!("#{} {}", self.id, self.content)
+        } else {
+            format!("#{} [{}] {}", self.id, self.tags.join(", "), self.content)
+        }
+    }
+}
+
+/// Changes to an entry; `None` fields are kept.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryChange {
+    pub content: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub importance: Option<f64>,
+}
 
 /// Handles persistent memory storage and retrieval.
 pub struct MemoryManager {
     pub file_path: String,
-    memory: Vec<String>,
+    store: Box<dyn MemoryStore>,
+    entries: Vec<MemoryEntry>,
+
+    /// Session recorded as the source of new entries.
+    source: Option<String>,
 }
 
 impl MemoryManager {
@@ -20,73 +107,191 @@ impl MemoryManager {
     pub fn new(file: &str) -> Self {
         let mut manager = MemoryManager {
             file_path: file.to_string(),
-            memory: Vec::new(),
+            store: memory_store(file),
+            entries: Vec::new(),
+            source: None,
         };
-        manager.load();
+        if let Err(e) = manager.load() {
+            eprintln!("[WARNING] Ignoring memory: {:#}", e);
+        }
         manager
     }
 
-    /// Adds a new memory fragment and saves to disk.
-    pub fn add(&mut self, content: &str) {
-        self.memory.push(content.trim().to_string());
-        self.save();
-        println!("[SYSTEM] Added to memory: {}", content);
+    /// Wraps the manager for sharing with the memory tools.
+    pub fn shared(self) -> SharedMemory {
+        Arc::new(Mutex::new(self))
     }
 
-    /// Updates the first memory fragment matching `pattern` with `replacement` and saves.
-    pub fn update(&mut self, pattern: &str, replacement: &str) {
-        let re = Regex::new(pattern).unwrap();
-        if let Some(pos) = self.memory.iter().position(|line| re.is_match(line)) {
-            self.memory[pos] = replacement.trim().to_string();
-            self.save();
-            println!("[SYSTEM] Updated memory: '{}' -> '{}'", pattern, replacement);
-        } else {
-            println!("[SYSTEM] No memory matched pattern: {}", pattern);
+    /// Records `session` as the source of the entries added from now on.
+    pub fn set_source(&mut self, session: &str) {
+        self.source = Some(session.to_string());
+    }
+
+    /// Every entry, oldest first.
+    pub fn entries(&self) -> &[MemoryEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: u64) -> Option<&MemoryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds an entry and returns it with its id.
+    pub fn add(&mut self, content: &str, tags: &[String], importance: Option<f64>) -> anyhow::Result<MemoryEntry> {
+        let mut entry = MemoryEntry::new(content);
+        if entry.content.is_empty() {
+            anyhow::bail!("empty memory");
         }
+        entry.tags = normalize_tags(tags);
+        entry.importance = check_importance(importance.unwrap_or(DEFAULT_IMPORTANCE))?;
+        entry.source = self.source.clone();
+
+        let result = self.store.insert(entry);
+        self.reload(result)
     }
 
-    /// Deletes the first memory fragment matching `pattern` and saves.
-    pub fn delete(&mut self, pattern: &str) {
-        let re = Regex::new(pattern).unwrap();
-        if let Some(pos) = self.memory.iter().position(|line| re.is_match(line)) {
-            let removed = self.memory.remove(pos);
-            self.save();
-            println!("[SYSTEM] Deleted memory: {}", removed);
-        } else {
-            println!("[SYSTEM] No memory matched pattern: {}", pattern);
+    /// Applies `change` to entry `id` and returns the updated entry.
+    pub fn update(&mut self, id: u64, change: MemoryChange) -> anyhow::Result<MemoryEntry> {
+        self.load()?;
+        let mut entry = self.get(id).cloned().ok_or_else(|| anyhow::anyhow!("no memory #{}", id))?;
+        if let Some(content) = change.content {
+            if content.trim().is_empty() {
+                anyhow::bail!("empty memory");
+            }
+            entry.content = content.trim().to_string();
+        }
+        if let Some(tags) = change.tags {
+            entry.tags = normalize_tags(&tags);
+        }
+        if let Some(importance) = change.importance {
+            entry.importance = check_importance(importance)?;
         }
+        entry.updated = now().max(entry.created);
+
+        let result = self.store.update(&entry).and_then(|found| match found {
+            true => Ok(entry),
+            false => Err(anyhow::anyhow!("no memory #{}", id)),
+        });
+        self.reload(result)
     }
 
-    /// Reads memory, optionally filtering by a pattern.
-    pub fn read(&self, pattern: Option<&str>) -> String {
-        if let Some(pat) = pattern {
-            let re = Regex::new(pat).unwrap();
-            self.memory
-                .iter()
-                .filter(|line| re.is_match(line))
-                .cloned()
-                .collect::<Vec<String>>()
-                .join("\n")
-        } else {
-            self.memory.join("\n")
+    /// Deletes entry `id` and returns it.
+    pub fn delete(&mut self, id: u64) -> anyhow::Result<MemoryEntry> {
+        self.load()?;
+        let entry = self.get(id).cloned().ok_or_else(|| anyhow::anyhow!("no memory #{}", id))?;
+        let result = self.store.remove(id).and_then(|found| match found {
+            true => Ok(entry),
+            false => Err(anyhow::anyhow!("no memory #{}", id)),
+        });
+        self.reload(result)
+    }
+
+    /// Entries whose content or tags match `pattern` (a case-insensitive regex).
+    pub fn find(&self, pattern: &str) -> anyhow::Result<Vec<&MemoryEntry>> {
+        let re = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| anyhow::anyhow!("invalid pattern `{}`: {}", pattern, e))?;
+        Ok(self.entries
+            .iter()
+            .filter(|entry| re.is_match(&entry.content) || entry.tags.iter().any(|tag| re.is_match(tag)))
+            .collect())
+    }
+
+    /// Entries containing every word of `query` (in content or tags, ignoring
+    /// case), optionally only those tagged `tag`.
+    pub fn search(&self, query: &str, tag: Option<&str>) -> Vec<&MemoryEntry> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let tag = tag.map(|tag| tag.trim().trim_start_matches('#').to_lowercase());
+        self.entries
+            .iter()
+            .filter(|entry| tag.as_ref().is_none_or(|tag| entry.tags.contains(tag)))
+            .filter(|entry| {
+                let text = format!("{} {}", entry.content, entry.tags.join(" ")).to_lowercase();
+                words.iter().all(|word| text.contains(word.as_str()))
+            })
+            .collect()
+    }
+
+    /// Every entry, one `line` each.
+    pub fn view(&self) -> String {
+        self.entries.iter().map(MemoryEntry::line).collect::<Vec<_>>().join("\n")
+    }
+
+    /// The `limit` entries most worth knowing when working on `context`
+    /// (e.g. the working directory), best first. Scores add up the
+    /// importance, how recently the entry changed, and the words of
+    /// `context` found in its content or tags.
+    pub fn relevant(&self, context: &str, limit: usize) -> Vec<&MemoryEntry> {
+        let words: Vec<String> = context
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= 3)
+            .map(str::to_lowercase)
+            .collect();
+        let time = now();
+        let score = |entry: &MemoryEntry| {
+            let days = time.saturating_sub(entry.updated) as f64 / 86_400.0;
+            let text = format!("{} {}", entry.content, entry.tags.join(" ")).to_lowercase();
+            let matches = words.iter().filter(|word| text.contains(word.as_str())).count().min(3);
+            entry.importance * 2.0 + 1.0 / (1.0 + days / 30.0) + matches as f64
+        };
+
+        let mut ranked: Vec<(f64, &MemoryEntry)> = self.entries.iter().map(|entry| (score(entry), entry)).collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.id.cmp(&a.1.id)));
+        ranked.into_iter().take(limit).map(|(_, entry)| entry).collect()
+    }
+
+    /// The memories for the system prompt of a session in `context`.
+    pub fn prompt_block(&self, context: &str) -> String {
+        if self.entries.is_empty() {
+            return String::from("No memories yet.");
         }
+        let relevant = self.relevant(context, PROMPT_MEMORIES);
+        let mut block = relevant.iter().map(|entry| entry.line()).collect::<Vec<_>>().join("\n");
+        if self.entries.len() > relevant.len() {
+            block.push_str(&format!("\n({} more -- use memory_search)", self.entries.len() - relevant.len()));
+        }
+        block
     }
 
-    /// Loads memory from disk into memory vector.
-    pub fn load(&mut self) {
-        self.memory = std::fs::read_to_string(&self.file_path)
-            .map(|content| content.lines().map(|l| l.to_string()).collect())
-            .unwrap_or_else(|_| Vec::new());
+    /// Loads memory from the store.
+    pub fn load(&mut self) -> anyhow::Result<()> {
+        self.entries = self.store.load()?;
+        Ok(())
     }
 
-    /// Saves the current memory vector to disk.
-    pub fn save(&self) {
-        let _ = std::fs::write(&self.file_path, self.memory.join("\n"));
+    /// Clears the memory file and in-memory entries.
+    pub fn clear(&mut self) -> anyhow::Result<()> {
+        let result = self.store.clear();
+        self.reload(result)
     }
 
-    /// Clears the memory file and in-memory vector.
-    pub fn clear(&mut self) {
-        self.memory.clear();
-        self.save();
+    /// Reloads what's stored after a change, keeping the change's result.
+    fn reload<T>(&mut self, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        let loaded = self.load();
+        let value = result?;
+        loaded?;
+        Ok(value)
     }
-}
\ No newline at end of file
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim().trim_start_matches('#').to_lowercase()) {
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+fn check_importance(importance: f64) -> anyhow::Result<f64> {
+    if !(0.0..=1.0).contains(&importance) {
+        anyhow::bail!("importance must be between 0 and 1, got {}", importance);
+    }
+    Ok(importance)
+}
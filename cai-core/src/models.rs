@@ -1,3 +1,5 @@
+use crate::provider::ProviderKind;
+
 pub enum Model {
     Nano,   // ultra-cheap, basic stuff
     Mini,   // fast + good for mid-tier reasoning
@@ -20,3 +22,90 @@ macro_rules! model {
         $crate::models::get_model($crate::models::Model::$variant)
     };
 }
+
+
+/// The model ids an endpoint serves, one per tier.
+/// Defaults to the OpenAI models returned by `get_model`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelSet {
+    pub nano: String,
+    pub mini: String,
+    pub full: String,
+    pub max: String,
+}
+
+impl ModelSet {
+    /// Uses the same model for every tier -- typical for a local server.
+    pub fn single(model: &str) -> Self {
+        Self {
+            nano: model.to_string(),
+            mini: model.to_string(),
+            full: model.to_string(),
+            max: model.to_string(),
+        }
+    }
+
+    /// Parses a model list: either a single model id (used for all tiers)
+    /// or four comma-separated ids in `nano,mini,full,max` order.
+    pub fn parse(list: &str) -> Option<Self> {
+        let ids: Vec<&str> = list.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+        match ids.as_slice() {
+            [one] => Some(Self::single(one)),
+            [nano, mini, full, max] => Some(Self {
+                nano: nano.to_string(),
+                mini: mini.to_string(),
+                full: full.to_string(),
+                max: max.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Stock models for a vendor's official API.
+    pub fn for_provider(kind: ProviderKind) -> Self {
+        match kind {
+            ProviderKind::OpenAI => Self::default(),
+            ProviderKind::Anthropic => Self {
+                nano: "claude-haiku-4-5".to_string(),
+                mini: "claude-haiku-4-5".to_string(),
+                full: "claude-sonnet-4-5".to_string(),
+                max: "claude-opus-4-1".to_string(),
+            },
+        }
+    }
+
+    pub fn get(&self, model: Model) -> String {
+        match model {
+            Model::Nano => self.nano.clone(),
+            Model::Mini => self.mini.clone(),
+            Model::Full => self.full.clone(),
+            Model::Max  => self.max.clone(),
+        }
+    }
+
+    /// Infer the tier of a model id. `Max` shares its id with `Full` by default,
+    /// so the first matching tier wins.
+    pub fn variant_of(&self, model_id: &str) -> Option<Model> {
+        if self.nano == model_id { return Some(Model::Nano); }
+        if self.mini == model_id { return Some(Model::Mini); }
+        if self.full == model_id { return Some(Model::Full); }
+        if self.max == model_id  { return Some(Model::Max); }
+        None
+    }
+
+    /// True when every tier maps to the same model, so choosing a model is pointless.
+    pub fn is_single(&self) -> bool {
+        self.nano == self.mini && self.mini == self.full && self.full == self.max
+    }
+}
+
+impl Default for ModelSet {
+    fn default() -> Self {
+        Self {
+            nano: get_model(Model::Nano).unwrap_or_default(),
+            mini: get_model(Model::Mini).unwrap_or_default(),
+            full: get_model(Model::Full).unwrap_or_default(),
+            max: get_model(Model::Max).unwrap_or_default(),
+        }
+    }
+}
@@ -1,13 +1,17 @@
-/// This is commands parser
-/// If finds and executes commands in the response
-/// Commands are written in a code block like this:
-/// ```terminal
-/// echo "Hello World"
-/// ```
+//! This is commands parser
+//! If finds and executes commands in the response
+//! Commands are written in a code block like this:
+//! ```terminal
+//! echo "Hello World"
+//! ```
 
 use lazy_static::lazy_static;
 use regex::Regex;
-use crate::{shell::Shell, ui_trait::{MsgType, MsgRole, UIBase}};
+use crate::{
+    approval::{ ApprovalGate, ApprovalRequest },
+    shell::SharedShell,
+    ui_trait::{MsgType, MsgRole, UIBase},
+};
 
 lazy_static!(
     static ref COMMANDS_BLOCK_RE: Regex = Regex::new(
@@ -16,7 +20,13 @@ lazy_static!(
 );
 
 
-pub fn parse_commands_block(ui: &dyn UIBase, response: &str, shell: &mut Shell, sys_message: &mut String) {
+pub fn parse_commands_block(
+    ui: &dyn UIBase,
+    response: &str,
+    shell: &SharedShell,
+    gate: &mut ApprovalGate,
+    sys_message: &mut String
+) {
     if COMMANDS_BLOCK_RE.is_match(response) {
         let blocks = COMMANDS_BLOCK_RE.find_iter(response);
 
@@ -45,8 +55,11 @@ pub fn parse_commands_block(ui: &dyn UIBase, response: &str, shell: &mut Shell,
             let mut ui_content_children: Vec<String> = Vec::with_capacity(commands.len());
 
             for command in commands {
-                let command_output = shell.execute(&command, Some(10))
-                    .unwrap_or_else(|_| "Command execution failed.".to_string());
+                let command_output = match gate.check(ui, &ApprovalRequest::command("terminal block", &command)) {
+                    Ok(()) => shell.lock().unwrap().run(&command)
+                        .unwrap_or_else(|e| format!("Command execution failed: {}", e)),
+                    Err(rejection) => rejection,
+                };
 
                 // Push to sys_message
                 let content = format!("{} -> {}\n", command, command_output);
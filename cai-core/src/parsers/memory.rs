@@ -1,6 +1,6 @@
 use regex::Regex;
 
-use crate::{client::Client, prompt::MEMORY_PROMPT, ui_trait::{MsgRole, MsgType, UIBase}};
+use crate::{client::Client, memory_manager::MemoryChange, prompt::MEMORY_PROMPT, ui_trait::{MsgRole, MsgType, UIBase}};
 
 lazy_static::lazy_static! {
     static ref MEMORY_BLOCK_RE: Regex = Regex::new(
@@ -9,34 +9,37 @@ lazy_static::lazy_static! {
 }
 
 pub fn parse_memory_block(ui: &dyn UIBase, response: &str, ai: &mut Client, sys_message: &mut String) {
-    if MEMORY_BLOCK_RE.is_match(&response) {
+    if MEMORY_BLOCK_RE.is_match(response) {
         // Per each memory block, extract the action and content
         // Then, perform the action on the memory
         // If the action requires a response, update the response
 
-        for capture in MEMORY_BLOCK_RE.captures_iter(&response) {
+        for capture in MEMORY_BLOCK_RE.captures_iter(response) {
             let action = capture.get(1).unwrap().as_str();
             let content = capture.get(2).unwrap().as_str();
 
             // Optional response to send back
             match action {
-                "add" => {
-                    ai.memory.add(content);
-                }
-                "update" => {
-                    let mut lines = content.lines();
-                    let pat = lines.next().unwrap();
-                    let rep = lines.next().unwrap();
-
-                    ai.memory.update(pat, rep);
-                }
-                "delete" => {
-                    ai.memory.delete(content);
+                "add" | "update" | "delete" => {
+                    match change_memory(ai, action, content) {
+                        Ok(message) => ui.print_message(MsgRole::System, MsgType::Plain(message)),
+                        Err(e) => {
+                            let content = format!("[Memory Error] {:#}\n", e);
+                            sys_message.push_str(&content);
+                            ui.print_message(MsgRole::System, MsgType::Plain(content));
+                        }
+                    }
                 }
                 "view" => {
-                    let pat = if content.trim().is_empty() { None } else { Some(content) };
-
-                    let content = format!("[Memory View]\n{}\n", ai.memory.read(pat));
+                    let view = match content.trim() {
+                        "" => Ok(ai.memory.lock().unwrap().view()),
+                        pattern => ai.memory.lock().unwrap().find(pattern)
+                            .map(|entries| entries.iter().map(|entry| entry.line()).collect::<Vec<_>>().join("\n")),
+                    };
+                    let content = match view {
+                        Ok(view) => format!("[Memory View]\n{}\n", view),
+                        Err(e) => format!("[Memory Error] {:#}\n", e),
+                    };
                     sys_message.push_str(content.as_str());
                     ui.print_message(
                         MsgRole::System,
@@ -59,3 +62,22 @@ pub fn parse_memory_block(ui: &dyn UIBase, response: &str, ai: &mut Client, sys_
         }
     }
 }
+
+/// Runs an add, update (id, then the new content) or delete (id) block.
+fn change_memory(ai: &mut Client, action: &str, content: &str) -> anyhow::Result<String> {
+    match action {
+        "add" => ai.memory.lock().unwrap().add(content, &[], None).map(|entry| format!("Added to memory: {}", entry.line())),
+        "update" => {
+            let (id, text) = content.trim_start().split_once('\n').unwrap_or((content, ""));
+            let change = MemoryChange { content: Some(text.to_string()), ..Default::default() };
+            ai.memory.lock().unwrap().update(memory_id(id)?, change).map(|entry| format!("Updated memory: {}", entry.line()))
+        }
+        _ => ai.memory.lock().unwrap().delete(memory_id(content)?).map(|entry| format!("Deleted memory: {}", entry.line())),
+    }
+}
+
+/// The id in `text`, with or without its `#`.
+fn memory_id(text: &str) -> anyhow::Result<u64> {
+    let text = text.trim();
+    text.trim_start_matches('#').parse().map_err(|_| anyhow::anyhow!("invalid memory id `{}`", text))
+}
@@ -1,8 +1,9 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::{ io::{ self, Write }, process::{ Command, Stdio } };
+use std::{ io::Write, process::{ Command, Stdio } };
 use tempfile::NamedTempFile;
 
+use crate::approval::{ ActionKind, ApprovalGate, ApprovalRequest };
 use crate::ui_trait::{ MsgRole, MsgType, UIBase };
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -11,7 +12,7 @@ lazy_static! {
     static ref PYTHON_BLOCK_RE: Regex = Regex::new(r"(?s)```python\s*\r?\n(.*?)```").unwrap();
 }
 
-pub fn parse_python_block(ui: &dyn UIBase, response: &str, sys_message: &mut String) {
+pub fn parse_python_block(ui: &dyn UIBase, response: &str, gate: &mut ApprovalGate, sys_message: &mut String) {
     // 1. collect code snippets
     let snippets: Vec<&str> = PYTHON_BLOCK_RE.captures_iter(response)
         .filter_map(|cap| cap.get(1).map(|m| m.as_str()))
@@ -21,11 +22,18 @@ pub fn parse_python_block(ui: &dyn UIBase, response: &str, sys_message: &mut Str
         return; // nothing to do
     }
 
+    // All snippets run as one script, so they're approved together
+    let request = ApprovalRequest::new(ActionKind::Execute, "python block", snippets.join("\n"));
+    if let Err(rejection) = gate.check(ui, &request) {
+        sys_message.push_str(&format!("{}\n", rejection));
+        return;
+    }
+
     // 2. build full script (add our helper built-ins, then snippets)
     let mut script = String::new();
     script.push_str(include_str!("./_python_builtins.py"));
     script.push_str("\n\n");
-    script.extend(snippets.join("\n").chars());
+    script.push_str(&snippets.join("\n"));
 
     // 3. write to a unique temp file
     let mut tmp = NamedTempFile::new().expect("tmp file");
@@ -93,7 +101,7 @@ pub fn parse_python_block(ui: &dyn UIBase, response: &str, sys_message: &mut Str
 /// or `None` if none of them responded.
 ///
 /// # Example
-/// ```no_run
+/// ```ignore
 /// let python = get_python_name().expect("No Python interpreter found!");
 /// let out = std::process::Command::new(python)
 ///     .arg("-c")
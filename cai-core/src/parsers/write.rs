@@ -1,7 +1,9 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use crate::approval::{ ApprovalGate, ApprovalRequest };
 use crate::ui_trait::{MsgRole, MsgType, UIBase};
+use crate::workspace::Workspace;
 
 // Write block parser
 // Writing block look like this:
@@ -19,15 +21,35 @@ lazy_static!(
     ).unwrap();
 );
 
-pub fn parse_write_block(ui: &dyn UIBase, response: &str) -> Result<(), String> {
-    for capture in WRITE_BLOCK_RE.captures_iter(&response) {
+/// Writes every block the gate lets through, confined to the workspace.
+/// Rejected blocks and paths outside the workspace are skipped and reported
+/// in the error, together with the first write failure.
+pub fn parse_write_block(
+    ui: &dyn UIBase,
+    response: &str,
+    workspace: &Workspace,
+    gate: &mut ApprovalGate
+) -> Result<(), String> {
+    let mut rejected: Vec<String> = Vec::new();
+
+    for capture in WRITE_BLOCK_RE.captures_iter(response) {
         let file_path = capture.get(1).unwrap().as_str();
         let content = capture.get(2).unwrap().as_str();
 
-        std::fs::write(file_path, content)
-            .map_err(|e| format!("Failed to write to file: {}", e))?;
+        if let Err(e) = workspace.resolve(file_path) {
+            rejected.push(format!("[Denied] {:#}", e));
+            continue;
+        }
+        if let Err(rejection) = gate.check(ui, &ApprovalRequest::write("write block", file_path)) {
+            rejected.push(rejection);
+            continue;
+        }
+
+        workspace.write(file_path, content)
+            .map_err(|e| format!("Failed to write to file: {:#}", e))?;
 
         ui.print_message(MsgRole::System, MsgType::Plain(format!("Writing to file: {}", file_path)));
     }
-    Ok(())
+
+    if rejected.is_empty() { Ok(()) } else { Err(rejected.join("\n")) }
 }
\ No newline at end of file
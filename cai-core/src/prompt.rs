@@ -5,7 +5,7 @@
 //! Ensures consistent and context-rich instructions for the AI model.
 //! ===============================================================
 
-use crate::{constants::{CONTINUE_TOKEN, LANGUAGE, OS, RESTART_TOKEN}, types::ChatMessage};
+use crate::{config::Config, constants::{CONTINUE_TOKEN, RESTART_TOKEN}, types::ChatMessage};
 
 
 const PERSONALITY_PROMPT: &str = r#"Your name it's "Vey", you're a women.
@@ -35,7 +35,11 @@ Primary rule: when work is needed, CALL TOOLS, don’t just print code.
 ## Tools (use via function calls)
 - run_terminal(command): execute shell commands in the user's native shell.
 - run_python(code): execute Python snippets; use for data wrangling, quick tests, or generating files.
-- write_file(path, content): write or replace files; create dirs as needed.
+- write_file(path, content): write new files or fully replace small ones; create dirs as needed. Paths are relative to the working directory and must stay inside it.
+- read_file(path, start_line?, end_line?), list_dir(path?, depth?), glob(pattern, path?), grep(pattern, path?, glob?): inspect the workspace; prefer them over cat/find/grep in the terminal.
+- edit_file(path, edits | diff): change part of an existing file with search/replace pairs (copy the search text exactly, with enough lines to be unique) or a unified diff. Prefer it over write_file for existing files; it returns the resulting diff.
+- search_history(query, role?, session?, since?, until?): find what was said or run in earlier sessions, when the user refers to past conversations.
+- memory_add(content, tags?, importance?), memory_update(id, content?, tags?, importance?), memory_delete(id), memory_search(query, tag?), memory_view(): your long-term memory across sessions. Save lasting facts (user preferences, project decisions) when you learn them or the user asks you to remember; fix or forget ones that turn out wrong.
 
 ## Formatting
 - Only print code blocks when the user explicitly asks to see code. Otherwise, use tools.
@@ -48,6 +52,9 @@ OS: {OS}
 CWD: {CURRENT_PATH}
 Personality: {PERSONALITY}
 
+## Memory
+{MEMORY}
+
 Be candid and efficient. Solve things."#;
 
 
@@ -56,9 +63,11 @@ Be candid and efficient. Solve things."#;
 /// # Arguments
 /// * `memory` - Current persistent memory.
 /// * `cwd` - Current working directory.
+/// * `config` - Resolved configuration (language, OS).
 pub fn get_sys_prompt(
     memory: &str,
     cwd: &str,
+    config: &Config,
 ) -> String {
     SYS_PROMPT
         .replace("{RESTART_TOKEN}", RESTART_TOKEN)
@@ -66,37 +75,43 @@ pub fn get_sys_prompt(
         .replace("{MEMORY_PROMPT}", MEMORY_PROMPT)
         .replace("{CONTINUE_TOKEN}", CONTINUE_TOKEN)
         .replace("{CURRENT_PATH}", cwd)
-        .replace("{LANGUAGE}", LANGUAGE)
-        .replace("{OS}", OS)
+        .replace("{LANGUAGE}", &config.language)
+        .replace("{OS}", &config.os)
         .replace("{PERSONALITY}", PERSONALITY_PROMPT)
 }
 pub const RESUME_PROMPT: &str = r#"Conversation has been resumed. Doesn't mean pick up where you left off, but you can.
 This is tecnically a new conversation, but you can use the memory to recall information from the previous one."#;
 
+/// The resume prompt with the current memories, which may have changed
+/// since the conversation's system prompt.
+pub fn get_resume_prompt(memory: &str) -> String {
+    format!("{}\n\n## Memory\n{}", RESUME_PROMPT, memory)
+}
+
 
 pub const MEMORY_PROMPT: &str = r#"- To manage your memory you can use the memory block. and pass an action as argument. below you can see the available actions:
     - To write to memory, create a code block named "memory" and add the information there. Example:
     ```memory[add]
     user name is John
     ```
-    - To update memory, create a code block named "memory" and add the information there. Example:
+    Every memory gets an id, shown as #ID when you view the memory.
+
+    - To update memory, create a code block named "memory" with the id on the first line and the new content on the second. Example:
     ```memory[update]
-    user name is John
+    3
     user name is Mike
     ```
-    This works by replacing the first occurrence of the pattern with the replacement. First line is the pattern, second line is the replacement.
 
-    - To delete from memory, create a code block named "memory" and add the information there. Example:
+    - To delete from memory, create a code block named "memory" with the id of the memory. Example:
     ```memory[delete]
-    user name is John
+    3
     ```
-    This will delete the first occurrence of the pattern from the memory.
 
     - To view memory, create a code block named "memory" and add the information there. Example:
     ```memory[view]
     optional pattern
     ```
-    This will show the memory, if a pattern is provided, it will show only the lines that match the pattern.
+    This will show the memory, if a pattern is provided, it will show only the memories that match the pattern.
 
 Use your memory to recall information in future responses, make good use of it. You can use multiple memory blocks in the same response.
 Memory it's yours, don't need to be related to the user.
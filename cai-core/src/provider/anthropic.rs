@@ -0,0 +1,332 @@
+//! Anthropic Messages API backend.
+//!
+//! Translates the Chat Completions shaped conversation used by `Client`
+//! into Anthropic's format and back:
+//! * leading `system` messages   → top-level `system` field
+//! * later `system` messages     → `user` text blocks
+//! * assistant `tool_calls`      → `tool_use` blocks
+//! * `tool` messages             → `tool_result` blocks in a `user` message
+//!
+//! History storage is untouched -- the mapping only lives here.
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{ json, Map, Value };
+
+use super::{ Completion, CompletionRequest, DeltaSender, Provider, ToolCallAccumulator };
+use crate::types::{ ToolCall, Usage };
+
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const API_VERSION: &str = "2023-06-01";
+
+/// `max_tokens` is mandatory for Anthropic; used when the request doesn't set one.
+const DEFAULT_MAX_TOKENS: u64 = 8192;
+
+pub struct AnthropicProvider {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl AnthropicProvider {
+    /// Creates a provider. `base_url` defaults to api.anthropic.com.
+    pub fn new(base_url: Option<&str>, api_key: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.unwrap_or(DEFAULT_BASE_URL).trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    /// Builds the Messages API payload for a request.
+    pub fn payload(request: &CompletionRequest) -> Value {
+        let mut system: Vec<String> = Vec::new();
+        let mut messages: Vec<Value> = Vec::new();
+
+        for msg in &request.messages {
+            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            let text = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+
+            match role {
+                // System prompt(s) before the first real turn go in the top-level field.
+                "system" if messages.is_empty() => system.push(text.to_string()),
+
+                // Anthropic has no mid-conversation system role.
+                "system" => push_blocks(&mut messages, "user", vec![text_block(text)]),
+
+                "assistant" => {
+                    let mut blocks = Vec::new();
+                    if !text.is_empty() {
+                        blocks.push(text_block(text));
+                    }
+                    if let Some(calls) = msg.get("tool_calls").and_then(|x| x.as_array()) {
+                        for call in calls.iter().map(ToolCall::from_json) {
+                            blocks.push(json!({
+                                "type": "tool_use",
+                                "id": call.id,
+                                "name": call.name,
+                                "input": call.args(),
+                            }));
+                        }
+                    }
+                    push_blocks(&mut messages, "assistant", blocks);
+                }
+
+                "tool" => {
+                    let id = msg.get("tool_call_id").and_then(|x| x.as_str()).unwrap_or("");
+                    push_blocks(&mut messages, "user", vec![json!({
+                        "type": "tool_result",
+                        "tool_use_id": id,
+                        "content": text,
+                    })]);
+                }
+
+                _ => push_blocks(&mut messages, "user", vec![text_block(text)]),
+            }
+        }
+
+        // The conversation must start with a user turn.
+        if messages.first().is_none_or(|m| m["role"] != "user") {
+            messages.insert(0, json!({ "role": "user", "content": [text_block("[Start]")] }));
+        }
+
+        let params = request.params.as_object().cloned().unwrap_or_default();
+        let max_tokens = params
+            .get("max_completion_tokens")
+            .or_else(|| params.get("max_tokens"))
+            .and_then(|x| x.as_u64())
+            .unwrap_or(DEFAULT_MAX_TOKENS);
+
+        let mut payload = json!({
+            "model": request.model,
+            "max_tokens": max_tokens,
+            "messages": messages,
+        });
+        if !system.is_empty() {
+            payload["system"] = json!(system.join("\n\n"));
+        }
+
+        if let Some(tools) = request.tools.as_ref().and_then(|t| t.as_array()) {
+            payload["tools"] = Value::Array(tools.iter().map(tool_definition).collect());
+
+            // Only the tool-choice knobs have an Anthropic equivalent.
+            if let Some(Value::String(choice)) = params.get("tool_choice") {
+                let mut tool_choice = Map::new();
+                tool_choice.insert("type".into(), json!(if choice == "required" { "any" } else { choice.as_str() }));
+                if params.get("parallel_tool_calls") == Some(&json!(false)) {
+                    tool_choice.insert("disable_parallel_tool_use".into(), json!(true));
+                }
+                payload["tool_choice"] = Value::Object(tool_choice);
+            }
+        }
+
+        payload
+    }
+
+    /// Parses a Messages API response body.
+    pub fn parse_response(response: &Value) -> Completion {
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+
+        for block in response.get("content").and_then(|c| c.as_array()).into_iter().flatten() {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") => text.push_str(block.get("text").and_then(|t| t.as_str()).unwrap_or("")),
+                Some("tool_use") => tool_calls.push(ToolCall {
+                    id: block.get("id").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+                    name: block.get("name").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+                    arguments: block.get("input").cloned().unwrap_or(json!({})).to_string(),
+                }),
+                _ => {}
+            }
+        }
+
+        let usage = response.get("usage").map(|u| {
+            let prompt_tokens = u.get("input_tokens").and_then(|x| x.as_u64()).unwrap_or(0);
+            let completion_tokens = u.get("output_tokens").and_then(|x| x.as_u64()).unwrap_or(0);
+            Usage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }
+        });
+
+        Completion {
+            content: if text.is_empty() && !tool_calls.is_empty() { None } else { Some(text) },
+            tool_calls,
+            usage,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> anyhow::Result<Completion> {
+        let resp = self.send(Self::payload(request)).await?;
+        let body: Value = resp.json().await?;
+        Ok(Self::parse_response(&body))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        deltas: DeltaSender
+    ) -> anyhow::Result<Completion> {
+        let mut payload = Self::payload(request);
+        payload["stream"] = json!(true);
+
+        let resp = self.send(payload).await?;
+        let mut body = resp.bytes_stream();
+
+        let mut text = String::new();
+        let mut tools = ToolCallAccumulator::default();
+        let mut usage = Usage::default();
+
+        // Server-sent events, split at blank lines before decoding so a
+        // character cut between two chunks isn't mangled.
+        let mut pending: Vec<u8> = Vec::new();
+        let mut finished = false;
+        while !finished {
+            match body.next().await {
+                Some(bytes) => pending.extend_from_slice(&bytes?),
+                None => {
+                    // Whatever's left is the last event
+                    pending.extend_from_slice(b"\n\n");
+                    finished = true;
+                }
+            }
+
+            for event in take_events(&mut pending) {
+                let index = event.get("index").and_then(|x| x.as_u64()).unwrap_or(0) as usize;
+                match event.get("type").and_then(|t| t.as_str()) {
+                    Some("message_start") => {
+                        let u = &event["message"]["usage"];
+                        usage.prompt_tokens = u.get("input_tokens").and_then(|x| x.as_u64()).unwrap_or(0);
+                    }
+                    Some("content_block_start") => {
+                        let block = &event["content_block"];
+                        if block["type"] == "tool_use" {
+                            tools.push(index, block["id"].as_str(), block["name"].as_str(), None);
+                        }
+                    }
+                    Some("content_block_delta") => {
+                        let delta = &event["delta"];
+                        match delta.get("type").and_then(|t| t.as_str()) {
+                            Some("text_delta") => {
+                                let piece = delta["text"].as_str().unwrap_or("");
+                                text.push_str(piece);
+                                let _ = deltas.send(piece.to_string());
+                            }
+                            Some("input_json_delta") => {
+                                tools.push(index, None, None, delta["partial_json"].as_str());
+                            }
+                            _ => {}
+                        }
+                    }
+                    Some("message_delta") => {
+                        if let Some(out) = event["usage"].get("output_tokens").and_then(|x| x.as_u64()) {
+                            usage.completion_tokens = out;
+                        }
+                    }
+                    Some("error") => {
+                        let message = event["error"]["message"].as_str().unwrap_or("unknown error");
+                        anyhow::bail!("stream error: {}", message);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
+        let tool_calls = tools.finish();
+        Ok(Completion {
+            content: if text.is_empty() && !tool_calls.is_empty() { None } else { Some(text) },
+            tool_calls,
+            usage: Some(usage),
+        })
+    }
+}
+
+impl AnthropicProvider {
+    /// POSTs a payload to the Messages endpoint, turning HTTP errors into `Err`.
+    async fn send(&self, payload: Value) -> anyhow::Result<reqwest::Response> {
+        let mut http_req = self.http
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("anthropic-version", API_VERSION)
+            .json(&payload);
+        if let Some(key) = &self.api_key {
+            http_req = http_req.header("x-api-key", key);
+        }
+
+        let resp = http_req.send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body: Value = resp.json().await.unwrap_or(Value::Null);
+            let message = body
+                .get("error").and_then(|e| e.get("message")).and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            anyhow::bail!("HTTP {}: {}", status, message);
+        }
+        Ok(resp)
+    }
+}
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+fn text_block(text: &str) -> Value {
+    json!({ "type": "text", "text": text })
+}
+
+/// Appends content blocks, merging into the previous message when it has the
+/// same role (Anthropic expects alternating turns, and every `tool_result`
+/// must sit in the single user message following the `tool_use`).
+fn push_blocks(messages: &mut Vec<Value>, role: &str, blocks: Vec<Value>) {
+    if blocks.is_empty() {
+        return;
+    }
+    if let Some(last) = messages.last_mut() {
+        if last["role"] == role {
+            if let Some(content) = last["content"].as_array_mut() {
+                content.extend(blocks);
+                return;
+            }
+        }
+    }
+    messages.push(json!({ "role": role, "content": blocks }));
+}
+
+/// Chat Completions tool definition → Anthropic tool definition.
+fn tool_definition(tool: &Value) -> Value {
+    let function = tool.get("function").unwrap_or(tool);
+    json!({
+        "name": function.get("name").cloned().unwrap_or(json!("")),
+        "description": function.get("description").cloned().unwrap_or(json!("")),
+        "input_schema": function.get("parameters").cloned().unwrap_or(json!({ "type": "object" })),
+    })
+}
+
+/// Removes the complete events (those ended by a blank line) from the
+/// front of `pending` and returns their JSON `data:`. Only whole events
+/// are decoded, so UTF-8 split across network chunks stays intact.
+fn take_events(pending: &mut Vec<u8>) -> Vec<Value> {
+    let mut events = Vec::new();
+    loop {
+        let blank = pending.windows(2).position(|w| w == b"\n\n")
+            .map(|at| (at, 2))
+            .into_iter()
+            .chain(pending.windows(3).position(|w| w == b"\n\r\n").map(|at| (at, 3)))
+            .min();
+        let Some((at, len)) = blank else { break };
+        let raw: Vec<u8> = pending.drain(..at + len).collect();
+
+        let raw = String::from_utf8_lossy(&raw);
+        let data: Vec<&str> = raw
+            .lines()
+            .filter_map(|line| line.trim_end().strip_prefix("data:"))
+            .map(str::trim)
+            .collect();
+        if let Ok(event) = serde_json::from_str::<Value>(&data.join("\n")) {
+            events.push(event);
+        }
+    }
+    events
+}
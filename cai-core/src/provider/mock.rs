@@ -0,0 +1,75 @@
+//! Deterministic, scripted provider for tests.
+//!
+//! Replies are handed out in order, one per request (model choosing and
+//! summarization requests included). Every request is recorded so tests
+//! can assert on what the client sent.
+
+use std::{ collections::VecDeque, sync::Mutex };
+
+use async_trait::async_trait;
+
+use super::{ Completion, CompletionRequest, Provider };
+
+pub struct MockProvider {
+    script: Mutex<VecDeque<Completion>>,
+    requests: Mutex<Vec<CompletionRequest>>,
+
+    /// Returned once the script runs out, so a test never hangs on retries.
+    fallback: Completion,
+}
+
+impl MockProvider {
+    pub fn new(script: Vec<Completion>) -> Self {
+        Self {
+            script: Mutex::new(script.into()),
+            requests: Mutex::new(Vec::new()),
+            fallback: Completion::text("[mock] script exhausted"),
+        }
+    }
+
+    /// Appends a plain text reply to the script.
+    pub fn reply(self, content: &str) -> Self {
+        self.push(Completion::text(content));
+        self
+    }
+
+    /// Appends a reply requesting a single tool call.
+    pub fn tool_call(self, name: &str, arguments: serde_json::Value) -> Self {
+        let id = format!("call_{}", self.script.lock().unwrap().len());
+        self.push(Completion::tool_call(&id, name, arguments));
+        self
+    }
+
+    /// Appends an arbitrary completion to the script.
+    pub fn push(&self, completion: Completion) {
+        self.script.lock().unwrap().push_back(completion);
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of scripted replies not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> anyhow::Result<Completion> {
+        self.requests.lock().unwrap().push(request.clone());
+        Ok(self.script.lock().unwrap().pop_front().unwrap_or_else(|| self.fallback.clone()))
+    }
+}
@@ -0,0 +1,281 @@
+//! ===============================================================
+//! LLM Provider Abstraction
+//!
+//! Defines the `Provider` trait every LLM backend must implement.
+//! The `Client` only talks to this trait, so the tool loop, history
+//! summarization and model choosing run against any vendor.
+//!
+//! Messages are exchanged in the Chat Completions shape
+//! (`{"role": .., "content": ..}`); backends that speak another wire
+//! format translate to and from it.
+//! ===============================================================
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{ Deserialize, Serialize };
+use serde_json::{ json, Value };
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::Config;
+use crate::models::ModelSet;
+use crate::types::{ ToolCall, Usage };
+
+pub mod openai;
+pub use openai::OpenAIProvider;
+
+pub mod anthropic;
+pub use anthropic::AnthropicProvider;
+
+pub mod mock;
+pub use mock::MockProvider;
+
+pub mod replay;
+pub use replay::{ RecordingProvider, ReplayProvider };
+
+/// Wire format spoken by an endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// Chat Completions -- OpenAI and every OpenAI-compatible server.
+    #[default]
+    OpenAI,
+
+    /// Anthropic Messages API.
+    Anthropic,
+}
+
+impl ProviderKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "openai" => Some(Self::OpenAI),
+            "anthropic" | "claude" => Some(Self::Anthropic),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OpenAI => "openai",
+            Self::Anthropic => "anthropic",
+        }
+    }
+}
+
+/// Where requests go: base URL, credentials and the models served there.
+///
+/// `base_url == None` means the official OpenAI API, which is the only case
+/// that requires an API key. Any OpenAI-compatible server (Ollama, llama.cpp,
+/// vLLM, ...) can be used by pointing `base_url` at it.
+#[derive(Debug, Clone, Default)]
+pub struct Endpoint {
+    /// Wire format of the endpoint.
+    pub kind: ProviderKind,
+
+    /// e.g. "http://localhost:11434/v1" -- `None` for the vendor's official API
+    pub base_url: Option<String>,
+
+    /// Optional for local servers.
+    pub api_key: Option<String>,
+
+    /// Model ids served by this endpoint, per tier.
+    pub models: ModelSet,
+}
+
+impl Endpoint {
+    /// Reads the endpoint from the environment only (no config files):
+    /// * `CAI_PROVIDER`    -- `openai` (default) or `anthropic`.
+    /// * `OPENAI_BASE_URL` / `ANTHROPIC_BASE_URL` -- base URL of the server.
+    /// * `OPENAI_API_KEY`  / `ANTHROPIC_API_KEY`  -- API key (optional when a base URL is set).
+    /// * `CAI_MODELS`      -- one model id, or `nano,mini,full,max`.
+    pub fn from_env() -> Self {
+        Config::load_from(None, None, &|key| std::env::var(key).ok())
+            .unwrap_or_default()
+            .endpoint()
+    }
+
+    /// Only the vendors' official APIs need a key; local servers usually don't.
+    pub fn requires_api_key(&self) -> bool {
+        self.base_url.is_none()
+    }
+
+    /// Name of the environment variable holding the key for this endpoint.
+    pub fn api_key_var(&self) -> &'static str {
+        match self.kind {
+            ProviderKind::OpenAI => "OPENAI_API_KEY",
+            ProviderKind::Anthropic => "ANTHROPIC_API_KEY",
+        }
+    }
+
+    /// Builds the provider matching this endpoint's wire format.
+    pub fn build_provider(&self) -> Arc<dyn Provider> {
+        match self.kind {
+            ProviderKind::OpenAI => Arc::new(OpenAIProvider::from_endpoint(self)),
+            ProviderKind::Anthropic => Arc::new(
+                AnthropicProvider::new(self.base_url.as_deref(), self.api_key.clone())
+            ),
+        }
+    }
+}
+
+/// A single request sent to a provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    /// Model id, e.g. "gpt-5-mini".
+    pub model: String,
+
+    /// Conversation in Chat Completions shape.
+    pub messages: Vec<Value>,
+
+    /// Tool definitions (Chat Completions `tools` array), if any.
+    pub tools: Option<Value>,
+
+    /// Extra request parameters merged into the payload
+    /// (e.g. `max_completion_tokens`, `tool_choice`).
+    pub params: Value,
+}
+
+impl CompletionRequest {
+    pub fn new(model: impl Into<String>, messages: Vec<Value>) -> Self {
+        Self {
+            model: model.into(),
+            messages,
+            tools: None,
+            params: json!({}),
+        }
+    }
+}
+
+/// What a provider returns for a single request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Completion {
+    /// Assistant text, if the model produced any.
+    pub content: Option<String>,
+
+    /// Tool calls requested by the model (empty when none).
+    pub tool_calls: Vec<ToolCall>,
+
+    /// Token usage reported by the backend, if any.
+    pub usage: Option<Usage>,
+}
+
+impl Completion {
+    /// A plain text reply.
+    pub fn text(content: impl Into<String>) -> Self {
+        Self { content: Some(content.into()), ..Default::default() }
+    }
+
+    /// A reply requesting a single tool call.
+    pub fn tool_call(id: &str, name: &str, arguments: Value) -> Self {
+        Self {
+            content: None,
+            tool_calls: vec![ToolCall { id: id.to_string(), name: name.to_string(), arguments: arguments.to_string() }],
+            usage: None,
+        }
+    }
+
+    /// The assistant message in Chat Completions shape -- used to feed
+    /// tool calls back into the next request of the tool loop.
+    pub fn assistant_message(&self) -> Value {
+        let mut msg = json!({
+            "role": "assistant",
+            "content": self.content,
+        });
+        if !self.tool_calls.is_empty() {
+            msg["tool_calls"] = Value::Array(
+                self.tool_calls.iter().map(ToolCall::to_json).collect()
+            );
+        }
+        msg
+    }
+}
+
+/// Receives assistant text chunks while a completion is streamed.
+pub type DeltaSender = UnboundedSender<String>;
+
+/// Error a provider returns when trying again cannot help (e.g. a replayed
+/// transcript ran out): the back-off loop gives up on it at once.
+#[derive(Debug)]
+pub struct FatalError(pub String);
+
+impl std::fmt::Display for FatalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for FatalError {}
+
+/// Assembles tool calls from streamed fragments.
+/// Fragments are keyed by their index in the message; ids and names usually
+/// arrive once, arguments arrive as JSON string pieces.
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: Vec<ToolCall>,
+}
+
+impl ToolCallAccumulator {
+    pub fn push(&mut self, index: usize, id: Option<&str>, name: Option<&str>, arguments: Option<&str>) {
+        while self.calls.len() <= index {
+            self.calls.push(ToolCall { id: String::new(), name: String::new(), arguments: String::new() });
+        }
+        let call = &mut self.calls[index];
+
+        if let Some(id) = id.filter(|id| !id.is_empty()) {
+            call.id = id.to_string();
+        }
+        // Some servers repeat the full name on every fragment
+        if let Some(name) = name.filter(|name| *name != call.name) {
+            call.name.push_str(name);
+        }
+        if let Some(arguments) = arguments {
+            call.arguments.push_str(arguments);
+        }
+    }
+
+    /// The assembled calls, skipping indexes that never received a tool call
+    /// (e.g. text blocks in Anthropic streams).
+    pub fn finish(self) -> Vec<ToolCall> {
+        self.calls
+            .into_iter()
+            .enumerate()
+            .filter(|(_, call)| !call.name.is_empty())
+            .map(|(i, mut call)| {
+                if call.id.is_empty() {
+                    call.id = format!("call_{}", i);
+                }
+                if call.arguments.trim().is_empty() {
+                    call.arguments = "{}".to_string();
+                }
+                call
+            })
+            .collect()
+    }
+}
+
+/// Trait that every LLM backend must satisfy.
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// Short backend name, used in logs.
+    fn name(&self) -> &str;
+
+    /// Run a single (non-streaming) completion.
+    async fn complete(&self, request: &CompletionRequest) -> anyhow::Result<Completion>;
+
+    /// Run a streaming completion: assistant text is sent to `deltas` as it
+    /// arrives, the fully assembled completion (tool calls included) is returned.
+    ///
+    /// Backends without streaming support fall back to `complete` and send the
+    /// whole text as a single chunk.
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        deltas: DeltaSender
+    ) -> anyhow::Result<Completion> {
+        let completion = self.complete(request).await?;
+        if let Some(text) = completion.content.as_ref().filter(|t| !t.is_empty()) {
+            let _ = deltas.send(text.clone());
+        }
+        Ok(completion)
+    }
+}
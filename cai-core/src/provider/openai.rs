@@ -0,0 +1,166 @@
+//! OpenAI Chat Completions backend (via `async-openai`).
+
+use async_openai::{ config::OpenAIConfig, Client as APIClient };
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{ json, Value };
+
+use super::{ Completion, CompletionRequest, DeltaSender, Endpoint, Provider, ToolCallAccumulator };
+use crate::client_util::merge_json;
+use crate::types::{ ToolCall, Usage };
+
+pub struct OpenAIProvider {
+    ai: APIClient<OpenAIConfig>,
+}
+
+impl OpenAIProvider {
+    /// Creates a provider with api key from env var OPENAI_API_KEY and default base url.
+    pub fn new() -> Self {
+        Self { ai: APIClient::new() }
+    }
+
+    /// Creates a provider for any OpenAI-compatible endpoint.
+    pub fn from_endpoint(endpoint: &Endpoint) -> Self {
+        let mut config = OpenAIConfig::new()
+            .with_api_key(endpoint.api_key.clone().unwrap_or_default());
+        if let Some(base_url) = &endpoint.base_url {
+            config = config.with_api_base(base_url.trim_end_matches('/'));
+        }
+        Self { ai: APIClient::with_config(config) }
+    }
+
+    /// Builds the raw Chat Completions payload for a request.
+    pub fn payload(request: &CompletionRequest) -> Value {
+        let mut payload = json!({
+            "model": request.model,
+            "messages": request.messages,
+        });
+        if let Some(tools) = &request.tools {
+            payload["tools"] = tools.clone();
+        }
+        merge_json(&mut payload, request.params.clone());
+        payload
+    }
+
+    /// Parses a raw Chat Completions response body.
+    pub fn parse_response(response: &Value) -> Completion {
+        let msg = response
+            .get("choices").and_then(|c| c.get(0))
+            .and_then(|c0| c0.get("message"));
+
+        let content = msg
+            .and_then(|m| m.get("content"))
+            .and_then(|s| s.as_str())
+            .map(|s| s.to_string())
+            // Responses-style fallback, in case the backend answers in that shape
+            .or_else(|| response.get("output_text").and_then(|s| s.as_str()).map(|s| s.to_string()));
+
+        let tool_calls = msg
+            .and_then(|m| m.get("tool_calls"))
+            .and_then(|x| x.as_array())
+            .map(|calls| {
+                calls.iter().enumerate().map(|(i, c)| {
+                    let mut call = ToolCall::from_json(c);
+                    // Tool results are matched by id, so make sure there is one
+                    if call.id.is_empty() {
+                        call.id = format!("call_{}", i);
+                    }
+                    call
+                }).collect()
+            })
+            .unwrap_or_default();
+
+        let usage = response.get("usage").and_then(parse_usage);
+
+        Completion { content, tool_calls, usage }
+    }
+}
+
+fn parse_usage(u: &Value) -> Option<Usage> {
+    if u.is_null() {
+        return None;
+    }
+    Some(Usage {
+        prompt_tokens: u.get("prompt_tokens").and_then(|x| x.as_u64()).unwrap_or(0),
+        completion_tokens: u.get("completion_tokens").and_then(|x| x.as_u64()).unwrap_or(0),
+        total_tokens: u.get("total_tokens").and_then(|x| x.as_u64()).unwrap_or(0),
+    })
+}
+
+impl Default for OpenAIProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAIProvider {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> anyhow::Result<Completion> {
+        let response: Value = self.ai
+            .chat()
+            .create_byot::<Value, Value>(Self::payload(request)).await?;
+        Ok(Self::parse_response(&response))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        deltas: DeltaSender
+    ) -> anyhow::Result<Completion> {
+        let mut payload = Self::payload(request);
+        payload["stream"] = json!(true);
+        payload["stream_options"] = json!({ "include_usage": true });
+
+        let mut stream = self.ai
+            .chat()
+            .create_stream_byot::<Value, Value>(payload).await?;
+
+        let mut text = String::new();
+        let mut tools = ToolCallAccumulator::default();
+        let mut usage = None;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+
+            if let Some(u) = chunk.get("usage").and_then(parse_usage) {
+                usage = Some(u);
+            }
+
+            let Some(delta) = chunk
+                .get("choices").and_then(|c| c.get(0))
+                .and_then(|c0| c0.get("delta"))
+            else {
+                continue;
+            };
+
+            if let Some(piece) = delta.get("content").and_then(|x| x.as_str()) {
+                if !piece.is_empty() {
+                    text.push_str(piece);
+                    let _ = deltas.send(piece.to_string());
+                }
+            }
+
+            for (i, call) in delta.get("tool_calls").and_then(|x| x.as_array()).into_iter().flatten().enumerate() {
+                let index = call.get("index").and_then(|x| x.as_u64()).map(|x| x as usize).unwrap_or(i);
+                let function = call.get("function");
+                tools.push(
+                    index,
+                    call.get("id").and_then(|x| x.as_str()),
+                    function.and_then(|f| f.get("name")).and_then(|x| x.as_str()),
+                    function.and_then(|f| f.get("arguments")).and_then(|x| x.as_str()),
+                );
+            }
+        }
+
+        let tool_calls = tools.finish();
+        Ok(Completion {
+            content: if text.is_empty() && !tool_calls.is_empty() { None } else { Some(text) },
+            tool_calls,
+            usage,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+//! Record / replay of provider traffic.
+//!
+//! `RecordingProvider` wraps a real provider and appends every
+//! request/response pair to a JSON fixture file. `ReplayProvider` serves
+//! those responses back in order, fully offline.
+//!
+//! Enabled from the environment with `CAI_RECORD=<file>` or
+//! `CAI_REPLAY=<file>` (see `from_env`). A replay never falls back to the
+//! live backend: a transcript that can't be loaded, runs out or (in strict
+//! mode) no longer matches the requests is an error.
+
+use std::{ fs, path::{ Path, PathBuf }, sync::{ Arc, Mutex } };
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{ Deserialize, Serialize };
+
+use super::{ Completion, CompletionRequest, DeltaSender, FatalError, Provider };
+
+/// One request/response pair of a transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub request: CompletionRequest,
+    pub response: Completion,
+}
+
+/// Contents of a fixture file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Transcript {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// ────────────────────────────────────────────────────────────────
+// Recording
+// ────────────────────────────────────────────────────────────────
+pub struct RecordingProvider {
+    inner: Arc<dyn Provider>,
+    path: PathBuf,
+    transcript: Mutex<Transcript>,
+}
+
+impl RecordingProvider {
+    /// Records into `path`, overwriting any previous transcript.
+    pub fn new(inner: Arc<dyn Provider>, path: impl Into<PathBuf>) -> Self {
+        Self { inner, path: path.into(), transcript: Mutex::new(Transcript::default()) }
+    }
+
+    /// Appends an exchange and flushes the whole transcript, so a crash
+    /// loses at most the in-flight request.
+    fn record(&self, request: &CompletionRequest, response: &Completion) {
+        let mut transcript = self.transcript.lock().unwrap();
+        transcript.exchanges.push(Exchange { request: request.clone(), response: response.clone() });
+        if let Err(e) = transcript.save(&self.path) {
+            eprintln!("[ERROR] Failed to write transcript {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> anyhow::Result<Completion> {
+        let response = self.inner.complete(request).await?;
+        self.record(request, &response);
+        Ok(response)
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        deltas: DeltaSender
+    ) -> anyhow::Result<Completion> {
+        let response = self.inner.complete_stream(request, deltas).await?;
+        self.record(request, &response);
+        Ok(response)
+    }
+}
+
+// ────────────────────────────────────────────────────────────────
+// Replaying
+// ────────────────────────────────────────────────────────────────
+pub struct ReplayProvider {
+    exchanges: Vec<Exchange>,
+    cursor: Mutex<usize>,
+
+    /// When set, a request that differs from the recorded one fails
+    /// instead of getting the recorded response.
+    pub strict: bool,
+}
+
+impl ReplayProvider {
+    pub fn new(transcript: Transcript) -> Self {
+        Self { exchanges: transcript.exchanges, cursor: Mutex::new(0), strict: false }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::new(Transcript::load(path)?))
+    }
+
+    /// Number of recorded exchanges not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.exchanges.len() - *self.cursor.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> anyhow::Result<Completion> {
+        let mut cursor = self.cursor.lock().unwrap();
+        let Some(exchange) = self.exchanges.get(*cursor) else {
+            return Err(FatalError(format!("transcript exhausted after {} requests", *cursor)).into());
+        };
+        *cursor += 1;
+
+        if self.strict && exchange.request != *request {
+            return Err(FatalError(format!("request #{} does not match the transcript", *cursor)).into());
+        }
+        Ok(exchange.response.clone())
+    }
+}
+
+/// Wraps `provider` for recording or replaces it for replaying, depending on
+/// the `CAI_RECORD` / `CAI_REPLAY` environment variables. Otherwise returns it as is.
+pub fn from_env(provider: Arc<dyn Provider>) -> anyhow::Result<Arc<dyn Provider>> {
+    if let Some(path) = std::env::var_os("CAI_REPLAY") {
+        let path = Path::new(&path);
+        let replay = ReplayProvider::load(path)
+            .with_context(|| format!("Cannot load transcript {}", path.display()))?;
+        return Ok(Arc::new(replay));
+    }
+    if let Some(path) = std::env::var_os("CAI_RECORD") {
+        return Ok(Arc::new(RecordingProvider::new(provider, path)));
+    }
+    Ok(provider)
+}
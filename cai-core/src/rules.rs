@@ -0,0 +1,526 @@
+//! ===============================================================
+//! Command Rules
+//!
+//! Declarative allow/deny rules for shell commands, checked before
+//! anything runs (by the approval gate, `Shell::execute` and the
+//! `run_terminal` tool).
+//!
+//! Patterns:
+//!   * glob (default) -- `cargo test`, `git *`, `mkfs*`. A pattern
+//!     matches the command itself or the command with more arguments,
+//!     so `ls` matches `ls -la`.
+//!   * pipeline       -- `curl | sh` matches commands of a pipeline in
+//!     that order, with anything in between (`curl x | tee f | sh`).
+//!   * regex          -- `re:^rm\s+-[rf]+\s+/`. Deny regexes are also
+//!     matched against the whole command line.
+//!
+//! Command lines are split on `;`, `&&`, `||`, `&`, `|`, newlines,
+//! subshells, `$(...)` / backticks, `sh -c '...'` and `eval '...'`, and
+//! wrappers (`env`, `nohup`, `xargs`, `command`, `time`) are unwrapped,
+//! so a denied command can't hide inside an allowed one. Process
+//! substitution counts as a pipe: `bash <(curl ...)` is `curl | bash`.
+//! A line is allowed only when every command in it matches an allow
+//! rule and none of them writes a file (`>`, `--output`).
+//! ===============================================================
+
+use regex::Regex;
+use serde::{ Deserialize, Serialize };
+
+/// Allow/deny pattern lists -- the `[rules]` config table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandRules {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+/// Outcome of checking a command line against the rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleVerdict {
+    /// Every command matches an allow rule -- no need to ask.
+    Allow,
+
+    /// Some command matches a deny rule -- never run it.
+    Deny { rule: String, command: String },
+
+    /// Neither -- up to the approval mode.
+    Unmatched,
+}
+
+/// A single command of a pipeline, normalized for matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// Words with quotes removed, leading `VAR=value` assignments and
+    /// shell keywords dropped, and the program reduced to its file name.
+    pub text: String,
+
+    /// Redirects output into a file (`>`, `>>`) or is told to write one
+    /// (`--output`), so it can write.
+    pub writes: bool,
+}
+
+/// Commands connected by pipes.
+pub type Pipeline = Vec<SimpleCommand>;
+
+impl CommandRules {
+    /// Rules every session starts with; config rules are added on top.
+    pub fn builtin() -> Self {
+        let list = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            allow: list(&[
+                "ls", "pwd", "git status", "git diff", "git log", "cargo test", "cargo check",
+            ]),
+            deny: list(&[
+                r"re:^rm\s+(-\S+\s+)*(/|/\*|~|~/)(\s|$)", "sudo", "mkfs*",
+                "curl | sh", "curl | bash", "wget | sh", "wget | bash",
+            ]),
+        }
+    }
+
+    /// Appends the patterns of `other`.
+    pub fn extend(&mut self, other: &CommandRules) {
+        self.allow.extend(other.allow.iter().cloned());
+        self.deny.extend(other.deny.iter().cloned());
+    }
+
+    /// Checks a full command line.
+    pub fn check(&self, line: &str) -> RuleVerdict {
+        let pipelines = split_commands(line);
+
+        for rule in &self.deny {
+            let pattern = Pattern::new(rule);
+            if pattern.matches_line(line) {
+                return RuleVerdict::Deny { rule: rule.clone(), command: line.trim().to_string() };
+            }
+            for pipeline in &pipelines {
+                if let Some(command) = pattern.matches_pipeline(pipeline) {
+                    return RuleVerdict::Deny { rule: rule.clone(), command };
+                }
+            }
+        }
+
+        let allow: Vec<Pattern> = self.allow.iter().map(|rule| Pattern::new(rule)).collect();
+        let commands: Vec<&SimpleCommand> = pipelines.iter().flatten().collect();
+        let allowed = !commands.is_empty() && commands.iter().all(|command| {
+            !command.writes && allow.iter().any(|pattern| pattern.matches_command(&command.text))
+        });
+
+        if allowed { RuleVerdict::Allow } else { RuleVerdict::Unmatched }
+    }
+}
+
+// ── Patterns ────────────────────────────────────────────────────
+
+enum Pattern {
+    /// One glob per pipeline stage (a single one for plain patterns).
+    Glob(Vec<Regex>),
+    Regex(Regex),
+    /// Pattern that failed to compile -- matches nothing.
+    Invalid,
+}
+
+impl Pattern {
+    fn new(rule: &str) -> Self {
+        if let Some(re) = rule.strip_prefix("re:") {
+            return match Regex::new(re) {
+                Ok(re) => Pattern::Regex(re),
+                Err(e) => {
+                    eprintln!("[WARNING] Ignoring invalid command rule {:?}: {}", rule, e);
+                    Pattern::Invalid
+                }
+            };
+        }
+        let stages: Option<Vec<Regex>> = rule.split('|').map(|stage| glob_regex(stage.trim())).collect();
+        stages.map(Pattern::Glob).unwrap_or(Pattern::Invalid)
+    }
+
+    fn matches_command(&self, command: &str) -> bool {
+        match self {
+            Pattern::Glob(stages) => stages.len() == 1 && stages[0].is_match(command),
+            Pattern::Regex(re) => re.is_match(command),
+            Pattern::Invalid => false,
+        }
+    }
+
+    /// Deny regexes may describe a whole line (e.g. `curl.*\|\s*sh`).
+    fn matches_line(&self, line: &str) -> bool {
+        matches!(self, Pattern::Regex(re) if re.is_match(line.trim()))
+    }
+
+    /// The matching command (or stages, joined by ` | `), if any.
+    fn matches_pipeline(&self, pipeline: &Pipeline) -> Option<String> {
+        match self {
+            Pattern::Glob(stages) if stages.len() > 1 => {
+                // Stages in order, not necessarily next to each other
+                let mut matched: Vec<&str> = Vec::new();
+                for command in pipeline {
+                    if matched.len() < stages.len() && stages[matched.len()].is_match(&command.text) {
+                        matched.push(&command.text);
+                    }
+                }
+                (matched.len() == stages.len()).then(|| matched.join(" | "))
+            }
+            _ => pipeline
+                .iter()
+                .find(|cmd| self.matches_command(&cmd.text))
+                .map(|cmd| cmd.text.clone()),
+        }
+    }
+}
+
+/// `cargo test*` → `^cargo test.*(\s.*)?$`
+fn glob_regex(glob: &str) -> Option<Regex> {
+    let glob = glob.split_whitespace().collect::<Vec<_>>().join(" ");
+    if glob.is_empty() {
+        return None;
+    }
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push_str(r"(\s.*)?$");
+    Regex::new(&re).ok()
+}
+
+// ── Command line splitting ──────────────────────────────────────
+
+/// Words that may precede the actual program.
+const PREFIX_WORDS: &[&str] = &[
+    "if", "then", "else", "elif", "do", "while", "until", "!", "exec",
+];
+
+/// Programs that run the command following their options, with the
+/// options that take a separate argument.
+const WRAPPERS: &[(&str, &[&str])] = &[
+    ("env", &["-u", "--unset", "-C", "--chdir", "-S", "--split-string"]),
+    ("nohup", &[]),
+    ("command", &[]),
+    ("time", &["-f", "--format", "-o", "--output"]),
+    ("xargs", &[
+        "-a", "--arg-file", "-d", "--delimiter", "-E", "-I", "-L", "--max-lines", "-n", "--max-args",
+        "-P", "--max-procs", "-s", "--max-chars", "--process-slot-var",
+    ]),
+];
+
+/// Shells whose `-c` argument is itself a command line.
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh"];
+
+/// Splits a command line into pipelines of normalized commands.
+pub fn split_commands(line: &str) -> Vec<Pipeline> {
+    let mut splitter = Splitter::default();
+    splitter.run(line);
+    splitter.finish()
+}
+
+#[derive(Default)]
+struct Splitter {
+    pipelines: Vec<Pipeline>,
+    pipeline: Pipeline,
+    current: String,
+    writes: bool,
+
+    /// Commands of `<(...)` / `>(...)` in the current command: they pipe
+    /// into it / it pipes into them.
+    inputs: Vec<SimpleCommand>,
+    outputs: Vec<SimpleCommand>,
+}
+
+impl Splitter {
+    fn run(&mut self, line: &str) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '\\' => {
+                    self.current.push(c);
+                    if let Some(&next) = chars.get(i + 1) {
+                        self.current.push(next);
+                        i += 1;
+                    }
+                }
+                '\'' => {
+                    let end = find_from(&chars, i + 1, '\'').unwrap_or(chars.len());
+                    self.current.extend(&chars[i..(end + 1).min(chars.len())]);
+                    i = end;
+                }
+                '"' => {
+                    // Command substitutions still run inside double quotes
+                    let end = closing_double_quote(&chars, i + 1);
+                    let quoted: String = chars[i..(end + 1).min(chars.len())].iter().collect();
+                    self.substitutions(&quoted);
+                    self.current.push_str(&quoted);
+                    i = end;
+                }
+                '`' => {
+                    let end = find_from(&chars, i + 1, '`').unwrap_or(chars.len());
+                    let inner: String = chars[(i + 1).min(chars.len())..end].iter().collect();
+                    self.nested(&inner);
+                    self.current.push('_');
+                    i = end;
+                }
+                '$' if chars.get(i + 1) == Some(&'(') => {
+                    let end = closing_paren(&chars, i + 2);
+                    let inner: String = chars[i + 2..end].iter().collect();
+                    self.nested(&inner);
+                    self.current.push('_');
+                    i = end;
+                }
+                // Process substitution
+                '<' | '>' if chars.get(i + 1) == Some(&'(') => {
+                    let end = closing_paren(&chars, i + 2);
+                    let inner = split_commands(&chars[i + 2..end].iter().collect::<String>());
+                    let commands = inner.iter().flatten().cloned();
+                    if c == '<' { self.inputs.extend(commands) } else { self.outputs.extend(commands) }
+                    self.pipelines.extend(inner);
+                    self.current.push('_');
+                    i = end;
+                }
+                '(' | ')' | ';' | '\n' => self.end_pipeline(),
+                '{' | '}' if self.current.trim().is_empty() => self.end_pipeline(),
+                '&' if chars.get(i + 1) == Some(&'&') => {
+                    self.end_pipeline();
+                    i += 1;
+                }
+                // `2>&1`, `>&2` are redirections, not background jobs
+                '&' if matches!(self.current.chars().last(), Some('>') | Some('<')) => self.current.push(c),
+                '&' => self.end_pipeline(),
+                '|' if chars.get(i + 1) == Some(&'|') => {
+                    self.end_pipeline();
+                    i += 1;
+                }
+                '|' => {
+                    self.end_command();
+                    if chars.get(i + 1) == Some(&'&') {
+                        i += 1;
+                    }
+                }
+                '>' => {
+                    self.current.push(c);
+                    let rest: String = chars[i + 1..].iter().collect();
+                    let target = rest.trim_start_matches('>').trim_start();
+                    if !target.starts_with('&') && !target.starts_with("/dev/null") {
+                        self.writes = true;
+                    }
+                }
+                c => self.current.push(c),
+            }
+            i += 1;
+        }
+    }
+
+    /// Splits `$(...)` found inside a double-quoted string.
+    fn substitutions(&mut self, quoted: &str) {
+        let chars: Vec<char> = quoted.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] == '$' && chars.get(i + 1) == Some(&'(') {
+                let end = closing_paren(&chars, i + 2);
+                self.nested(&chars[i + 2..end].iter().collect::<String>());
+                i = end;
+            } else if chars[i] == '`' {
+                let end = find_from(&chars, i + 1, '`').unwrap_or(chars.len());
+                self.nested(&chars[(i + 1).min(chars.len())..end].iter().collect::<String>());
+                i = end;
+            }
+            i += 1;
+        }
+    }
+
+    fn nested(&mut self, inner: &str) {
+        self.pipelines.extend(split_commands(inner));
+    }
+
+    fn end_command(&mut self) {
+        let raw = std::mem::take(&mut self.current);
+        let mut writes = std::mem::take(&mut self.writes);
+        let inputs = std::mem::take(&mut self.inputs);
+        let outputs = std::mem::take(&mut self.outputs);
+        let words = normalize(&words(&raw));
+        if words.is_empty() {
+            return;
+        }
+        writes |= words.iter().any(|w| w == "--output" || w.starts_with("--output="));
+
+        // `eval '...'` runs its arguments as a command line
+        if words[0] == "eval" {
+            self.nested(&words[1..].join(" "));
+        }
+
+        // `sh -c '...'` runs its argument as a command line
+        if SHELLS.contains(&words[0].as_str()) {
+            if let Some(pos) = words.iter().position(|w| w == "-c") {
+                if let Some(script) = words.get(pos + 1) {
+                    self.nested(script);
+                }
+            }
+        }
+
+        let command = SimpleCommand { text: words.join(" "), writes };
+        if !inputs.is_empty() || !outputs.is_empty() {
+            let mut pipeline = inputs;
+            pipeline.push(command.clone());
+            pipeline.extend(outputs);
+            self.pipelines.push(pipeline);
+        }
+        self.pipeline.push(command);
+    }
+
+    fn end_pipeline(&mut self) {
+        self.end_command();
+        if !self.pipeline.is_empty() {
+            self.pipelines.push(std::mem::take(&mut self.pipeline));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Pipeline> {
+        self.end_pipeline();
+        self.pipelines
+    }
+}
+
+/// Drops assignments, keywords and wrappers (with their options) in front
+/// of the program and reduces the program to its file name
+/// (`env -i /usr/bin/sudo` → `sudo`).
+fn normalize(words: &[String]) -> Vec<String> {
+    let mut words = words.to_vec();
+    loop {
+        let start = words
+            .iter()
+            .position(|w| !PREFIX_WORDS.contains(&w.as_str()) && !is_assignment(w))
+            .unwrap_or(words.len());
+        words.drain(..start);
+        let Some(program) = words.first_mut() else { return words };
+        if let Some(name) = program.rsplit('/').next().filter(|n| !n.is_empty()) {
+            *program = name.to_string();
+        }
+
+        let Some((_, with_argument)) = WRAPPERS.iter().find(|(name, _)| *name == words[0]) else {
+            return words;
+        };
+        let mut i = 1;
+        while let Some(option) = words.get(i).filter(|w| w.starts_with('-')).cloned() {
+            match option.as_str() {
+                "--" => {
+                    i += 1;
+                    break;
+                }
+                // `env -S 'sudo id'` splits its argument into words
+                "-S" | "--split-string" if i + 1 < words.len() => {
+                    let split = self::words(&words[i + 1]);
+                    words.splice(i..i + 2, split);
+                }
+                // `command -v sudo` only looks the program up
+                "-v" | "-V" if words[0] == "command" => return words,
+                option if with_argument.contains(&option) => i += 2,
+                _ => i += 1,
+            }
+        }
+        // A bare wrapper is the command itself
+        if i >= words.len() {
+            return words;
+        }
+        words.drain(..i);
+    }
+}
+
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        None => false,
+    }
+}
+
+/// Shell-style word splitting with quotes removed.
+fn words(raw: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+fn find_from(chars: &[char], start: usize, target: char) -> Option<usize> {
+    (start..chars.len()).find(|&i| chars[i] == target)
+}
+
+/// Index of the `"` closing a string opened before `start` (or the end).
+fn closing_double_quote(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '"' => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Index of the `)` closing a `(` opened before `start` (or the end).
+fn closing_paren(chars: &[char], start: usize) -> usize {
+    let mut depth = 1;
+    let mut quote: Option<char> = None;
+    let mut i = start;
+    while i < chars.len() {
+        let c = chars[i];
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '\\') => i += 1,
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
@@ -0,0 +1,402 @@
+//! ===============================================================
+//! Session Search
+//!
+//! Keyword search over the messages of every saved session, ranked
+//! with BM25. The index lives next to the sessions
+//! (`<sessions>/search-index.json`) and keeps, per message, its role,
+//! time, length and term counts -- not the text, which is read back
+//! from the session only for the hits shown.
+//!
+//! `History::save` updates the index of its session; only messages
+//! that are new or changed (e.g. compacted) are re-tokenized. Before
+//! each search, sessions that were added or deleted behind the
+//! index's back are picked up.
+//! ===============================================================
+
+use std::collections::hash_map::Entry;
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
+
+use anyhow::{ bail, Context };
+use serde::{ Deserialize, Serialize };
+
+use crate::history_manager::{ History, Node };
+use crate::sessions::{ SessionMeta, SessionStore };
+use crate::types::MessageRole;
+
+pub const INDEX_FILE_NAME: &str = "search-index.json";
+
+/// BM25 parameters.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Characters of context shown around the first match.
+const SNIPPET_CONTEXT: usize = 80;
+
+/// One indexed message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Doc {
+    role: MessageRole,
+
+    /// Unix time of the message; 0 → unknown (older histories).
+    time: u64,
+
+    /// Of the content, to spot changed messages.
+    hash: u64,
+
+    /// Number of terms.
+    len: u32,
+    terms: HashMap<String, u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    #[serde(skip)]
+    path: PathBuf,
+
+    /// Session id → its messages by node id (`None` → not indexed).
+    sessions: BTreeMap<String, Vec<Option<Doc>>>,
+}
+
+/// What to look for.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    pub text: String,
+    pub role: Option<MessageRole>,
+    pub session: Option<String>,
+
+    /// Unix times, inclusive.
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+
+    /// Maximum number of hits; 0 → 10.
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub session: String,
+    pub title: String,
+    pub node: usize,
+    pub role: MessageRole,
+    pub time: u64,
+    pub score: f64,
+
+    /// The text around the first match ...
+    pub snippet: String,
+
+    /// ... and where the query terms are in it.
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl SearchHit {
+    /// The snippet with every match wrapped in `open` / `close`.
+    pub fn highlighted(&self, open: &str, close: &str) -> String {
+        let mut out = String::new();
+        let mut last = 0;
+        for range in &self.highlights {
+            out.push_str(&self.snippet[last..range.start]);
+            out.push_str(open);
+            out.push_str(&self.snippet[range.clone()]);
+            out.push_str(close);
+            last = range.end;
+        }
+        out.push_str(&self.snippet[last..]);
+        out
+    }
+}
+
+impl SearchIndex {
+    /// Loads the index at `path`; a missing or unreadable one starts empty
+    /// (it's rebuilt from the sessions).
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut index: SearchIndex = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        index.path = path;
+        index
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("cannot create {}", parent.display()))?;
+        }
+        std::fs::write(&self.path, serde_json::to_string(self)?)
+            .with_context(|| format!("cannot write {}", self.path.display()))
+    }
+
+    /// Indexes the messages of `session` that are new or changed.
+    /// System prompts (root system messages) are left out.
+    pub fn update(&mut self, session: &str, nodes: &[Node]) {
+        let docs = self.sessions.entry(session.to_string()).or_default();
+        docs.resize(nodes.len(), None);
+        for (doc, node) in docs.iter_mut().zip(nodes) {
+            let message = &node.message;
+            if node.parent.is_none() && matches!(message.role, MessageRole::System) {
+                *doc = None;
+                continue;
+            }
+            let text = searchable_text(node);
+            let hash = fnv1a(&text);
+            if doc.as_ref().is_some_and(|doc| doc.hash == hash) {
+                continue;
+            }
+            let mut terms: HashMap<String, u32> = HashMap::new();
+            let mut len = 0;
+            for (_, term) in tokenize(&text) {
+                *terms.entry(term).or_default() += 1;
+                len += 1;
+            }
+            *doc = Some(Doc { role: message.role.clone(), time: node.time, hash, len, terms });
+        }
+    }
+
+    pub fn remove(&mut self, session: &str) {
+        self.sessions.remove(session);
+    }
+
+    pub fn contains(&self, session: &str) -> bool {
+        self.sessions.contains_key(session)
+    }
+
+    /// Ranked `(session, node, score)` of the messages matching `query`;
+    /// `created` gives the time of messages without one.
+    fn rank(&self, query: &SearchQuery, created: &HashMap<String, u64>) -> Vec<(String, usize, f64)> {
+        let terms: Vec<String> = tokenize(&query.text).into_iter().map(|(_, term)| term).collect::<HashSet<_>>()
+            .into_iter().collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let docs = || self.sessions.iter().flat_map(|(session, docs)| {
+            docs.iter().enumerate().filter_map(move |(node, doc)| doc.as_ref().map(|doc| (session, node, doc)))
+        });
+        let total = docs().count() as f64;
+        let average_len = docs().map(|(_, _, doc)| doc.len as f64).sum::<f64>() / total.max(1.0);
+        let idf: HashMap<&str, f64> = terms.iter().map(|term| {
+            let df = docs().filter(|(_, _, doc)| doc.terms.contains_key(term)).count() as f64;
+            (term.as_str(), (1.0 + (total - df + 0.5) / (df + 0.5)).ln())
+        }).collect();
+
+        let mut hits: Vec<(String, usize, f64)> = docs()
+            .filter(|(session, _, doc)| {
+                let time = if doc.time == 0 { created.get(*session).copied().unwrap_or(0) } else { doc.time };
+                query.session.as_ref().is_none_or(|s| s == *session)
+                    && query.role.as_ref().is_none_or(|role| std::mem::discriminant(role) == std::mem::discriminant(&doc.role))
+                    && query.since.is_none_or(|since| time >= since)
+                    && query.until.is_none_or(|until| time <= until)
+            })
+            .filter_map(|(session, node, doc)| {
+                let score: f64 = terms.iter().filter_map(|term| {
+                    let tf = *doc.terms.get(term)? as f64;
+                    Some(idf[term.as_str()] * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * doc.len as f64 / average_len)))
+                }).sum();
+                (score > 0.0).then(|| (session.clone(), node, score))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)).then_with(|| a.1.cmp(&b.1)));
+        hits.truncate(if query.limit == 0 { 10 } else { query.limit });
+        hits
+    }
+}
+
+/// The index of the sessions in `store`.
+pub fn index_file(store: &SessionStore) -> PathBuf {
+    store.dir().join(INDEX_FILE_NAME)
+}
+
+/// Searches every session in `store`, first syncing the index with
+/// sessions added or deleted since it was last saved.
+pub fn search(store: &SessionStore, query: &SearchQuery) -> anyhow::Result<Vec<SearchHit>> {
+    if let Some(session) = &query.session {
+        store.get(session)?;
+    }
+    let sessions: Vec<SessionMeta> = store.list()?;
+    let mut index = SearchIndex::open(index_file(store));
+
+    let mut changed = false;
+    let stale: Vec<String> = index.sessions.keys()
+        .filter(|id| !sessions.iter().any(|meta| &&meta.id == id))
+        .cloned()
+        .collect();
+    for id in stale {
+        index.remove(&id);
+        changed = true;
+    }
+    for meta in &sessions {
+        if index.contains(&meta.id) {
+            continue;
+        }
+        let history = load_history(store, &meta.id)?;
+        index.update(&meta.id, history.nodes());
+        changed = true;
+    }
+    if changed {
+        index.save()?;
+    }
+
+    let created: HashMap<String, u64> = sessions.iter().map(|meta| (meta.id.clone(), meta.created)).collect();
+    let terms: Vec<String> = tokenize(&query.text).into_iter().map(|(_, term)| term).collect();
+    let mut histories: HashMap<String, History> = HashMap::new();
+    let mut hits = Vec::new();
+    for (session, node, score) in index.rank(query, &created) {
+        let history = match histories.entry(session.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(load_history(store, &session)?),
+        };
+        let Some(node) = history.node(node) else { continue };
+        let (snippet, highlights) = snippet(&searchable_text(node), &terms);
+        let time = if node.time == 0 { created.get(&session).copied().unwrap_or(0) } else { node.time };
+        hits.push(SearchHit {
+            title: sessions.iter().find(|meta| meta.id == session).map(|meta| meta.title.clone()).unwrap_or_default(),
+            node: node.id,
+            role: node.message.role.clone(),
+            session,
+            time,
+            score,
+            snippet,
+            highlights,
+        });
+    }
+    Ok(hits)
+}
+
+fn load_history(store: &SessionStore, id: &str) -> anyhow::Result<History> {
+    Ok(History::new(&store.history_file(id)?.to_string_lossy(), usize::MAX, 0))
+}
+
+/// Message text plus the names and arguments of its tool calls.
+fn searchable_text(node: &Node) -> String {
+    let mut text = node.message.content.clone();
+    for call in &node.message.tool_calls {
+        text.push_str(&format!("\n{}({})", call.name, call.arguments));
+    }
+    text
+}
+
+/// Lowercased words (letters, digits, `_`) with their byte ranges.
+fn tokenize(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        let word = c.is_alphanumeric() || c == '_';
+        match (word, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push((s..i, text[s..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// Up to `SNIPPET_CONTEXT` characters around the first match of `terms`,
+/// with the ranges of every match inside.
+fn snippet(text: &str, terms: &[String]) -> (String, Vec<Range<usize>>) {
+    let matches: Vec<Range<usize>> = tokenize(text).into_iter()
+        .filter(|(_, token)| terms.contains(token))
+        .map(|(range, _)| range)
+        .collect();
+    let (first, first_end) = matches.first().map_or((0, 0), |range| (range.start, range.end));
+
+    let floor = |mut i: usize| { while !text.is_char_boundary(i) { i -= 1; } i };
+    let start = floor(first.saturating_sub(SNIPPET_CONTEXT));
+    let end = floor((first + SNIPPET_CONTEXT * 2).min(text.len()));
+    // Whole words at the edges
+    let start = if start == 0 {
+        0
+    } else {
+        text[start..end].char_indices()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(start, |(i, c)| start + i + c.len_utf8())
+            .min(first)
+    };
+    let end = if end == text.len() { end } else { text[start..end].rfind(char::is_whitespace).map_or(end, |i| (start + i).max(first_end)) };
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < text.len() { "…" } else { "" };
+    let body = text[start..end].replace('\n', " ");
+    let highlights = matches.into_iter()
+        .filter(|range| range.start >= start && range.end <= end)
+        .map(|range| range.start - start + prefix.len()..range.end - start + prefix.len())
+        .collect();
+    (format!("{}{}{}", prefix, body, suffix), highlights)
+}
+
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// `user`, `assistant`, `system` or `tool`.
+pub fn parse_role(name: &str) -> anyhow::Result<MessageRole> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "user" => Ok(MessageRole::User),
+        "assistant" => Ok(MessageRole::Assistant),
+        "system" => Ok(MessageRole::System),
+        "tool" => Ok(MessageRole::Tool),
+        other => bail!("unknown role `{}` -- use user, assistant, system or tool", other),
+    }
+}
+
+// ── Dates ──
+
+/// `2026-10-18` (midnight UTC), or a time relative to `now`: `3d`, `12h`, `2w`.
+pub fn parse_date(text: &str, now: u64) -> anyhow::Result<u64> {
+    let text = text.trim();
+    if let Some((number, unit)) = text.char_indices().last().map(|(i, c)| (&text[..i], c)) {
+        if let (Ok(n), Some(secs)) = (number.parse::<u64>(), match unit {
+            'h' => Some(3600),
+            'd' => Some(86_400),
+            'w' => Some(7 * 86_400),
+            _ => None,
+        }) {
+            return Ok(now.saturating_sub(n * secs));
+        }
+    }
+
+    let parts: Vec<&str> = text.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        bail!("invalid date `{}` -- use YYYY-MM-DD or 3d, 12h, 2w", text);
+    };
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<i64>(), month.parse::<u32>(), day.parse::<u32>()) else {
+        bail!("invalid date `{}` -- use YYYY-MM-DD or 3d, 12h, 2w", text);
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        bail!("invalid date `{}`", text);
+    }
+    Ok(days_from_civil(year, month, day) as u64 * 86_400)
+}
+
+/// `YYYY-MM-DD` (UTC) of a Unix time.
+pub fn format_date(time: u64) -> String {
+    let (year, month, day) = civil_from_days((time / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Days since 1970-01-01 (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if month <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, month, day)
+}
@@ -0,0 +1,188 @@
+//! ===============================================================
+//! Sessions
+//!
+//! Named conversations stored under the data directory, so every
+//! project keeps its own history wherever cai is launched from.
+//!
+//! Layout of the sessions directory:
+//!   <id>/meta.json            -- title, times, model and cwd
+//!   <id>/history.json         -- the conversation (`History`), or
+//!   <id>/history.db              with `storage = "sqlite"`
+//!   <id>/history.checkpoints/ -- file snapshots (`CheckpointStore`)
+//!
+//! Ids are the name given by the user, or the next free number.
+//! ===============================================================
+
+use std::path::{ Path, PathBuf };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use anyhow::{ bail, Context };
+use serde::{ Deserialize, Serialize };
+
+/// Characters of the first message used as a default title.
+const TITLE_CHARS: usize = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub id: String,
+
+    /// Empty until set by `rename` or taken from the first message.
+    pub title: String,
+
+    /// Unix times, in seconds.
+    pub created: u64,
+    pub updated: u64,
+
+    /// Model of the latest exchange.
+    pub model: String,
+
+    /// Directory the session was started in.
+    pub cwd: PathBuf,
+}
+
+impl SessionMeta {
+    /// Sets the title from `message` unless there is one already.
+    pub fn title_from(&mut self, message: &str) {
+        if !self.title.is_empty() {
+            return;
+        }
+        let line = message.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+        self.title = line.chars().take(TITLE_CHARS).collect();
+        if line.chars().count() > TITLE_CHARS {
+            self.title.push('…');
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// The sessions in `dir`. Nothing is created until a session is.
+    pub fn open(dir: impl AsRef<Path>) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the conversation of session `id` is stored: `history.db`
+    /// once it's kept in SQLite, else `history.json`.
+    pub fn history_file(&self, id: &str) -> anyhow::Result<PathBuf> {
+        check_name(id)?;
+        let db = self.dir.join(id).join("history.db");
+        Ok(if db.is_file() { db } else { self.dir.join(id).join("history.json") })
+    }
+
+    /// All sessions, most recently updated first.
+    pub fn list(&self) -> anyhow::Result<Vec<SessionMeta>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", self.dir.display())),
+        };
+        let mut sessions: Vec<SessionMeta> = entries
+            .flatten()
+            .filter_map(|entry| self.load(&entry.file_name().to_string_lossy()).ok())
+            .collect();
+        sessions.sort_by(|a, b| b.updated.cmp(&a.updated).then_with(|| b.id.cmp(&a.id)));
+        Ok(sessions)
+    }
+
+    /// Session `id`.
+    pub fn get(&self, id: &str) -> anyhow::Result<SessionMeta> {
+        check_name(id)?;
+        if !self.meta_file(id).is_file() {
+            bail!("no session `{}` in {}", id, self.dir.display());
+        }
+        self.load(id)
+    }
+
+    /// The most recently updated session started in `cwd`.
+    pub fn latest_for(&self, cwd: &Path) -> anyhow::Result<Option<SessionMeta>> {
+        Ok(self.list()?.into_iter().find(|meta| meta.cwd == cwd))
+    }
+
+    /// Creates a session named `name` (or numbered) for `cwd`.
+    pub fn create(&self, name: Option<&str>, cwd: &Path, model: &str) -> anyhow::Result<SessionMeta> {
+        let id = match name {
+            Some(name) => {
+                check_name(name)?;
+                if self.dir.join(name).exists() {
+                    bail!("session `{}` already exists", name);
+                }
+                name.to_string()
+            }
+            None => (1..).map(|n: usize| n.to_string()).find(|id| !self.dir.join(id).exists()).unwrap(),
+        };
+        let now = now();
+        let meta = SessionMeta {
+            id,
+            title: String::new(),
+            created: now,
+            updated: now,
+            model: model.to_string(),
+            cwd: cwd.to_path_buf(),
+        };
+        self.save(&meta)?;
+        Ok(meta)
+    }
+
+    /// Writes `meta`, bumping its update time.
+    pub fn touch(&self, meta: &mut SessionMeta) -> anyhow::Result<()> {
+        meta.updated = now().max(meta.updated);
+        self.save(meta)
+    }
+
+    pub fn rename(&self, id: &str, title: &str) -> anyhow::Result<SessionMeta> {
+        let mut meta = self.get(id)?;
+        meta.title = title.trim().to_string();
+        self.save(&meta)?;
+        Ok(meta)
+    }
+
+    /// Deletes session `id` with its history and checkpoints.
+    pub fn delete(&self, id: &str) -> anyhow::Result<()> {
+        // `get` checks the id, so this stays inside the sessions directory
+        self.get(id)?;
+        let dir = self.dir.join(id);
+        std::fs::remove_dir_all(&dir).with_context(|| format!("cannot delete {}", dir.display()))
+    }
+
+    fn meta_file(&self, id: &str) -> PathBuf {
+        self.dir.join(id).join("meta.json")
+    }
+
+    fn load(&self, id: &str) -> anyhow::Result<SessionMeta> {
+        let path = self.meta_file(id);
+        let text = std::fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("invalid session {}", path.display()))
+    }
+
+    fn save(&self, meta: &SessionMeta) -> anyhow::Result<()> {
+        let dir = self.dir.join(&meta.id);
+        std::fs::create_dir_all(&dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        let path = self.meta_file(&meta.id);
+        std::fs::write(&path, serde_json::to_string_pretty(meta)?)
+            .with_context(|| format!("cannot write {}", path.display()))
+    }
+}
+
+/// Session names become directory names: letters, digits, `.`, `-`, `_`.
+fn check_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if !valid {
+        bail!("invalid session name `{}` -- use letters, digits, `.`, `-` and `_`", name);
+    }
+    Ok(())
+}
+
+/// The current Unix time, in seconds.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
@@ -2,14 +2,28 @@
 //! Cross-Platform Shell Abstraction
 //! ===============================================================
 
+use std::sync::{ Arc, Mutex };
+
+use crate::constants::COMMAND_TIMEOUT_SECS;
+use crate::rules::{ CommandRules, RuleVerdict };
 use crate::shells::{self, util::ShellBackend};
 
+/// A shell session shared between the `Client` (tool calls) and the
+/// response parsers (```` ```terminal ```` blocks), so both see the same
+/// cwd, environment and timeout.
+pub type SharedShell = Arc<Mutex<Shell>>;
 
 // ────────────────────────────────────────────────────────────────
 // Shell struct -- public API used or run AI's commands
 // ────────────────────────────────────────────────────────────────
 pub struct Shell {
     inner: Box<dyn ShellBackend>,
+
+    /// Timeout applied by `run` (`None` → wait forever).
+    pub timeout_secs: Option<u64>,
+
+    /// Commands matching a deny rule are refused by `execute`.
+    pub rules: CommandRules,
 }
 
 impl Shell {
@@ -26,11 +40,37 @@ impl Shell {
             }
         };
 
-        Ok(Self { inner })
+        Ok(Self { inner, timeout_secs: Some(COMMAND_TIMEOUT_SECS), rules: CommandRules::builtin() })
+    }
+
+    /// Sets the timeout used by `run` (`None` → wait forever).
+    pub fn with_timeout(mut self, timeout_secs: Option<u64>) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    /// Replaces the command rules (the built-in ones by default).
+    pub fn with_rules(mut self, rules: CommandRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Wraps the shell into a handle that can be shared.
+    pub fn shared(self) -> SharedShell {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Run a command with the shell's own timeout.
+    pub fn run(&mut self, command: &str) -> anyhow::Result<String> {
+        let timeout_secs = self.timeout_secs;
+        self.execute(command, timeout_secs)
     }
 
-    /// Delegate to the concrete backend.
+    /// Check the deny rules, then delegate to the concrete backend.
     pub fn execute(&mut self, command: &str, timeout_secs: Option<u64>) -> anyhow::Result<String> {
+        if let RuleVerdict::Deny { rule, command } = self.rules.check(command) {
+            anyhow::bail!("`{}` denied by rule `{}`", command, rule);
+        }
         self.inner.execute(command, timeout_secs)
     }
 }
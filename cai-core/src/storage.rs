@@ -0,0 +1,606 @@
+//! ===============================================================
+//! Storage Backends
+//!
+//! Where `History` and `MemoryManager` keep their data. Two backends
+//! implement `HistoryStore` and `MemoryStore`:
+//!   * JSON        -- the original files (`history.json` holds the
+//!     message tree, `memory.json` the memory entries). Every save
+//!     rewrites the file, through a temporary file and a rename so a
+//!     crash never leaves it half written.
+//!   * SQLite      -- one database per file (`history.db`,
+//!     `memory.db`). Saves only write the messages that are new or
+//!     changed, in one transaction. The schema is versioned
+//!     (`PRAGMA user_version`) and migrated on open.
+//!
+//! Several processes can share a SQLite file: writes wait for each
+//! other (WAL journal, busy timeout, `BEGIN IMMEDIATE`), and messages
+//! another process appended since the last load are kept and merged
+//! in as a sibling branch instead of being overwritten. Memory
+//! changes are single-row statements on an entry's id, so they never
+//! clobber each other either.
+//!
+//! The backend follows the file extension (`.db`, `.sqlite`,
+//! `.sqlite3` → SQLite); `storage = "sqlite"` in the config switches
+//! the default files over. A new SQLite file starts from the JSON
+//! file next to it, if there is one.
+//! ===============================================================
+
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+use anyhow::Context;
+use rusqlite::{ params, Connection, OptionalExtension, TransactionBehavior };
+use serde::{ Deserialize, Serialize };
+
+use crate::history_manager::Node;
+use crate::memory_manager::MemoryEntry;
+use crate::types::ChatMessage;
+
+/// How long a write waits for another process holding the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Schema of history databases; entry `n` upgrades version `n` to `n + 1`.
+const HISTORY_MIGRATIONS: &[&str] = &["
+    CREATE TABLE nodes (
+        id      INTEGER PRIMARY KEY,
+        parent  INTEGER,
+        time    INTEGER NOT NULL DEFAULT 0,
+        message TEXT NOT NULL
+    );
+    CREATE TABLE state (
+        key   TEXT PRIMARY KEY,
+        value TEXT
+    );
+"];
+
+/// Schema of memory databases.
+const MEMORY_MIGRATIONS: &[&str] = &["
+    CREATE TABLE memories (
+        id      INTEGER PRIMARY KEY AUTOINCREMENT,
+        content TEXT NOT NULL
+    );
+", "
+    ALTER TABLE memories ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE memories ADD COLUMN created INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE memories ADD COLUMN updated INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE memories ADD COLUMN source TEXT;
+    ALTER TABLE memories ADD COLUMN importance REAL NOT NULL DEFAULT 0.5;
+", "
+    CREATE TABLE state (
+        key   TEXT PRIMARY KEY,
+        value TEXT
+    );
+"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// `history.json` and `memory.json`.
+    #[default]
+    Json,
+
+    /// `history.db` and `memory.db`.
+    Sqlite,
+}
+
+impl StorageKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" | "text" => Some(Self::Json),
+            "sqlite" | "sqlite3" | "db" => Some(Self::Sqlite),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Sqlite => "sqlite",
+        }
+    }
+
+    /// The backend storing `path`, by its extension.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("db" | "sqlite" | "sqlite3") => Self::Sqlite,
+            _ => Self::Json,
+        }
+    }
+
+    /// `path` renamed for this backend: `history.json` → `history.db`.
+    /// Paths already naming a SQLite file are kept.
+    pub fn path_for(self, path: &str) -> String {
+        let path = Path::new(path);
+        match self {
+            Self::Sqlite if Self::of(path) != Self::Sqlite => path.with_extension("db").to_string_lossy().into_owned(),
+            _ => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// The message tree of a history, as stored.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+    pub cursor: Option<usize>,
+}
+
+/// Where a `History` is loaded from and saved to.
+pub trait HistoryStore: Send {
+    /// The stored tree; `None` → nothing stored yet.
+    fn load(&mut self) -> anyhow::Result<Option<Tree>>;
+
+    /// Stores `tree`. Backends shared between processes may merge in
+    /// what others stored meanwhile, renumbering `tree`.
+    fn save(&mut self, tree: &mut Tree) -> anyhow::Result<()>;
+
+    /// The file, for messages.
+    fn path(&self) -> &Path;
+}
+
+/// Where a `MemoryManager` keeps its entries. Changes apply to the
+/// stored entries as they are now, not to a copy loaded earlier.
+pub trait MemoryStore: Send {
+    /// Every entry, by id.
+    fn load(&mut self) -> anyhow::Result<Vec<MemoryEntry>>;
+
+    /// Stores a new entry under the next free id and returns it.
+    fn insert(&mut self, entry: MemoryEntry) -> anyhow::Result<MemoryEntry>;
+
+    /// Replaces the entry with `entry.id`; false → there is none.
+    fn update(&mut self, entry: &MemoryEntry) -> anyhow::Result<bool>;
+
+    /// Removes entry `id`; false → there is none.
+    fn remove(&mut self, id: u64) -> anyhow::Result<bool>;
+    fn clear(&mut self) -> anyhow::Result<()>;
+    fn path(&self) -> &Path;
+}
+
+/// The history store for `path`, by its extension.
+pub fn history_store(path: &str) -> Box<dyn HistoryStore> {
+    match StorageKind::of(Path::new(path)) {
+        StorageKind::Json => Box::new(JsonHistory::new(path)),
+        StorageKind::Sqlite => Box::new(SqliteHistory::new(path)),
+    }
+}
+
+/// The memory store for `path`, by its extension.
+pub fn memory_store(path: &str) -> Box<dyn MemoryStore> {
+    match StorageKind::of(Path::new(path)) {
+        StorageKind::Json => Box::new(JsonMemory::new(path)),
+        StorageKind::Sqlite => Box::new(SqliteMemory::new(path)),
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path`, then renames it over `path`.
+fn write_atomic(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    let mut file = tempfile::NamedTempFile::new_in(dir).with_context(|| format!("cannot write in {}", dir.display()))?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(path).with_context(|| format!("cannot write {}", path.display()))?;
+    Ok(())
+}
+
+// ── JSON ──
+
+/// On-disk form of `history.json`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Tree { nodes: Vec<Node>, cursor: Option<usize> },
+
+    /// Before branching: the messages of the only branch.
+    Linear(Vec<ChatMessage>),
+}
+
+/// `Stored::Tree` without the copy.
+#[derive(Serialize)]
+struct StoredRef<'a> {
+    nodes: &'a [Node],
+    cursor: Option<usize>,
+}
+
+pub struct JsonHistory {
+    path: PathBuf,
+}
+
+impl JsonHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl HistoryStore for JsonHistory {
+    fn load(&mut self) -> anyhow::Result<Option<Tree>> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", self.path.display())),
+        };
+        let stored: Stored = serde_json::from_str(&text).with_context(|| format!("invalid history {}", self.path.display()))?;
+        Ok(Some(match stored {
+            Stored::Tree { nodes, cursor } => Tree { nodes, cursor },
+            Stored::Linear(messages) => {
+                let nodes: Vec<Node> = messages.into_iter().enumerate()
+                    .map(|(id, message)| Node { id, parent: id.checked_sub(1), time: 0, message })
+                    .collect();
+                Tree { cursor: nodes.len().checked_sub(1), nodes }
+            }
+        }))
+    }
+
+    fn save(&mut self, tree: &mut Tree) -> anyhow::Result<()> {
+        let json = serde_json::to_vec_pretty(&StoredRef { nodes: &tree.nodes, cursor: tree.cursor })?;
+        write_atomic(&self.path, &json)
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// On-disk form of `memory.json`.
+#[derive(Default, Serialize, Deserialize)]
+struct MemoryFile {
+    /// Id of the next entry; ids of deleted entries aren't reused.
+    next_id: u64,
+    entries: Vec<MemoryEntry>,
+}
+
+/// Memory as a JSON file. Files from before entries -- plain text, one
+/// memory per line -- are read as entries and rewritten as JSON on the
+/// first change.
+pub struct JsonMemory {
+    path: PathBuf,
+}
+
+impl JsonMemory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read(&self) -> anyhow::Result<MemoryFile> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(MemoryFile { next_id: 1, entries: Vec::new() }),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", self.path.display())),
+        };
+        if text.trim_start().starts_with('{') {
+            return serde_json::from_str(&text).with_context(|| format!("invalid memory {}", self.path.display()));
+        }
+
+        // One memory per line, dated by the file
+        let time = std::fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or_else(crate::sessions::now, |d| d.as_secs());
+        let entries: Vec<MemoryEntry> = text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .zip(1..)
+            .map(|(line, id)| MemoryEntry { id, created: time, updated: time, ..MemoryEntry::new(line) })
+            .collect();
+        Ok(MemoryFile { next_id: entries.len() as u64 + 1, entries })
+    }
+
+    fn write(&self, file: &MemoryFile) -> anyhow::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec_pretty(file)?)
+    }
+}
+
+impl MemoryStore for JsonMemory {
+    fn load(&mut self) -> anyhow::Result<Vec<MemoryEntry>> {
+        Ok(self.read()?.entries)
+    }
+
+    fn insert(&mut self, mut entry: MemoryEntry) -> anyhow::Result<MemoryEntry> {
+        let mut file = self.read()?;
+        let next = file.entries.iter().map(|e| e.id + 1).max().unwrap_or(1);
+        entry.id = file.next_id.max(next);
+        file.next_id = entry.id + 1;
+        file.entries.push(entry.clone());
+        self.write(&file)?;
+        Ok(entry)
+    }
+
+    fn update(&mut self, entry: &MemoryEntry) -> anyhow::Result<bool> {
+        let mut file = self.read()?;
+        let Some(stored) = file.entries.iter_mut().find(|e| e.id == entry.id) else { return Ok(false) };
+        *stored = entry.clone();
+        self.write(&file)?;
+        Ok(true)
+    }
+
+    fn remove(&mut self, id: u64) -> anyhow::Result<bool> {
+        let mut file = self.read()?;
+        let Some(pos) = file.entries.iter().position(|e| e.id == id) else { return Ok(false) };
+        file.entries.remove(pos);
+        self.write(&file)?;
+        Ok(true)
+    }
+
+    fn clear(&mut self) -> anyhow::Result<()> {
+        let file = self.read()?;
+        self.write(&MemoryFile { next_id: file.next_id, entries: Vec::new() })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// ── SQLite ──
+
+/// Opens (creating) the database at `path` and brings its schema up to date.
+fn open_database(path: &Path, migrations: &[&str]) -> anyhow::Result<Connection> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    }
+    let mut db = Connection::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+    db.busy_timeout(BUSY_TIMEOUT)?;
+    db.pragma_update(None, "journal_mode", "WAL")?;
+    db.pragma_update(None, "synchronous", "NORMAL")?;
+
+    let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let version: i64 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let version = version as usize;
+    if version > migrations.len() {
+        anyhow::bail!("{} was written by a newer cai (schema {})", path.display(), version);
+    }
+    for (n, migration) in migrations.iter().enumerate().skip(version) {
+        tx.execute_batch(migration).with_context(|| format!("cannot migrate {} to schema {}", path.display(), n + 1))?;
+    }
+    tx.pragma_update(None, "user_version", migrations.len() as i64)?;
+    tx.commit()?;
+    Ok(db)
+}
+
+/// The JSON file a new SQLite file starts from.
+fn legacy_file(path: &Path) -> Option<PathBuf> {
+    let legacy = path.with_extension("json");
+    (legacy != path && legacy.is_file()).then_some(legacy)
+}
+
+pub struct SqliteHistory {
+    path: PathBuf,
+    db: Option<Connection>,
+
+    /// Nodes as last loaded or saved (serialized), to write only changes.
+    synced: Vec<String>,
+}
+
+impl SqliteHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), db: None, synced: Vec::new() }
+    }
+
+    fn db(&mut self) -> anyhow::Result<&mut Connection> {
+        if self.db.is_none() {
+            self.db = Some(open_database(&self.path, HISTORY_MIGRATIONS)?);
+        }
+        Ok(self.db.as_mut().unwrap())
+    }
+}
+
+fn node_from_row(row: &rusqlite::Row) -> rusqlite::Result<(Node, String)> {
+    let id: i64 = row.get(0)?;
+    let parent: Option<i64> = row.get(1)?;
+    let time: i64 = row.get(2)?;
+    let message: String = row.get(3)?;
+    let message: ChatMessage = serde_json::from_str(&message)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e)))?;
+    let node = Node { id: id as usize, parent: parent.map(|p| p as usize), time: time as u64, message };
+    let json = node_json(&node);
+    Ok((node, json))
+}
+
+fn node_json(node: &Node) -> String {
+    serde_json::to_string(node).unwrap_or_default()
+}
+
+impl HistoryStore for SqliteHistory {
+    fn load(&mut self) -> anyhow::Result<Option<Tree>> {
+        let path = self.path.clone();
+        let db = self.db()?;
+        let rows: Vec<(Node, String)> = db.prepare("SELECT id, parent, time, message FROM nodes ORDER BY id")?
+            .query_map([], node_from_row)?
+            .collect::<rusqlite::Result<_>>()
+            .with_context(|| format!("invalid history {}", path.display()))?;
+        let cursor: Option<String> = db
+            .query_row("SELECT value FROM state WHERE key = 'cursor'", [], |row| row.get(0))
+            .optional()?
+            .flatten();
+
+        if rows.is_empty() {
+            self.synced.clear();
+            return match legacy_file(&path) {
+                Some(legacy) => JsonHistory::new(legacy).load(),
+                None => Ok(None),
+            };
+        }
+        let (nodes, synced): (Vec<Node>, Vec<String>) = rows.into_iter().unzip();
+        self.synced = synced;
+        Ok(Some(Tree { nodes, cursor: cursor.and_then(|c| c.parse().ok()) }))
+    }
+
+    fn save(&mut self, tree: &mut Tree) -> anyhow::Result<()> {
+        let mut synced = std::mem::take(&mut self.synced);
+        let db = self.db()?;
+        let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let stored: Vec<(Node, String)> = tx.prepare("SELECT id, parent, time, message FROM nodes ORDER BY id")?
+            .query_map([], node_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        let known = synced.len();
+        let unchanged = stored.len() >= known && stored[..known].iter().map(|(_, json)| json).eq(synced.iter());
+
+        if !unchanged {
+            // Another process rewrote or shrank the history (e.g. cleared
+            // it): ours replaces it whole, so no stale rows are left.
+            synced.clear();
+        } else if stored.len() > known && tree.nodes.len() >= known {
+            // Nodes another process appended since we last synced: keep
+            // them, and move ours after them (they become a sibling branch).
+            let theirs: Vec<(Node, String)> = stored.into_iter().skip(known).collect();
+            let shift = |id: usize| if id >= known { id + theirs.len() } else { id };
+            let ours: Vec<Node> = tree.nodes.drain(known..).map(|node| Node {
+                id: shift(node.id),
+                parent: node.parent.map(shift),
+                ..node
+            }).collect();
+            tree.cursor = tree.cursor.map(shift);
+            for (node, json) in theirs {
+                tree.nodes.push(node);
+                synced.push(json);
+            }
+            tree.nodes.extend(ours);
+        }
+
+        tx.execute("DELETE FROM nodes WHERE id >= ?1", [tree.nodes.len() as i64])?;
+        let mut written = Vec::with_capacity(tree.nodes.len());
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO nodes (id, parent, time, message) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(id) DO UPDATE SET parent = excluded.parent, time = excluded.time, message = excluded.message"
+            )?;
+            for node in &tree.nodes {
+                let json = node_json(node);
+                if synced.get(node.id) != Some(&json) {
+                    upsert.execute(params![
+                        node.id as i64,
+                        node.parent.map(|p| p as i64),
+                        node.time as i64,
+                        serde_json::to_string(&node.message)?
+                    ])?;
+                }
+                written.push(json);
+            }
+        }
+        tx.execute(
+            "INSERT INTO state (key, value) VALUES ('cursor', ?1) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [tree.cursor.map(|c| c.to_string())]
+        )?;
+        tx.commit()?;
+        self.synced = written;
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+pub struct SqliteMemory {
+    path: PathBuf,
+    db: Option<Connection>,
+}
+
+impl SqliteMemory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), db: None }
+    }
+
+    /// The database, filled from the JSON (or older text) file next to it
+    /// when new. The import is marked done, so memories deleted later
+    /// don't come back from the old file.
+    fn db(&mut self) -> anyhow::Result<&mut Connection> {
+        if self.db.is_none() {
+            let new = !self.path.exists();
+            let mut db = open_database(&self.path, MEMORY_MIGRATIONS)?;
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let imported: bool = tx.query_row("SELECT EXISTS (SELECT 1 FROM state WHERE key = 'imported')", [], |row| row.get(0))?;
+            if !imported {
+                // Databases from before the marker were filled when created
+                if let Some(legacy) = legacy_file(&self.path).filter(|_| new) {
+                    for entry in JsonMemory::new(legacy).load()? {
+                        insert_memory(&tx, &entry, true)?;
+                    }
+                }
+                tx.execute("INSERT INTO state (key, value) VALUES ('imported', '1')", [])?;
+            }
+            tx.commit()?;
+            self.db = Some(db);
+        }
+        Ok(self.db.as_mut().unwrap())
+    }
+}
+
+/// Inserts `entry`, under its own id when `keep_id`.
+fn insert_memory(db: &Connection, entry: &MemoryEntry, keep_id: bool) -> anyhow::Result<u64> {
+    db.execute(
+        "INSERT INTO memories (id, content, tags, created, updated, source, importance)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            keep_id.then_some(entry.id as i64),
+            entry.content,
+            serde_json::to_string(&entry.tags)?,
+            entry.created as i64,
+            entry.updated as i64,
+            entry.source,
+            entry.importance
+        ]
+    )?;
+    Ok(db.last_insert_rowid() as u64)
+}
+
+fn memory_from_row(row: &rusqlite::Row) -> rusqlite::Result<MemoryEntry> {
+    let tags: String = row.get(2)?;
+    Ok(MemoryEntry {
+        id: row.get::<_, i64>(0)? as u64,
+        content: row.get(1)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        created: row.get::<_, i64>(3)? as u64,
+        updated: row.get::<_, i64>(4)? as u64,
+        source: row.get(5)?,
+        importance: row.get(6)?,
+    })
+}
+
+impl MemoryStore for SqliteMemory {
+    fn load(&mut self) -> anyhow::Result<Vec<MemoryEntry>> {
+        let db = self.db()?;
+        let entries = db.prepare("SELECT id, content, tags, created, updated, source, importance FROM memories ORDER BY id")?
+            .query_map([], memory_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(entries)
+    }
+
+    fn insert(&mut self, mut entry: MemoryEntry) -> anyhow::Result<MemoryEntry> {
+        entry.id = insert_memory(self.db()?, &entry, false)?;
+        Ok(entry)
+    }
+
+    fn update(&mut self, entry: &MemoryEntry) -> anyhow::Result<bool> {
+        let changed = self.db()?.execute(
+            "UPDATE memories SET content = ?2, tags = ?3, updated = ?4, importance = ?5 WHERE id = ?1",
+            params![
+                entry.id as i64,
+                entry.content,
+                serde_json::to_string(&entry.tags)?,
+                entry.updated as i64,
+                entry.importance
+            ]
+        )?;
+        Ok(changed > 0)
+    }
+
+    fn remove(&mut self, id: u64) -> anyhow::Result<bool> {
+        let changed = self.db()?.execute("DELETE FROM memories WHERE id = ?1", [id as i64])?;
+        Ok(changed > 0)
+    }
+
+    fn clear(&mut self) -> anyhow::Result<()> {
+        self.db()?.execute("DELETE FROM memories", [])?;
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,93 @@
+//! ===============================================================
+//! Token Counting
+//!
+//! Local, offline token counts used to keep the history within the
+//! model's context window. OpenAI models are counted with their own
+//! BPE (tiktoken); every other model (Anthropic, local servers) gets
+//! an estimate of one token per four characters.
+//! ===============================================================
+
+use tiktoken_rs::{ tokenizer::{ get_tokenizer, Tokenizer as Encoding }, CoreBPE };
+
+/// Context window assumed for models not listed in `context_window`.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 32_768;
+
+/// Tokens a chat message costs on top of its content (role, separators).
+pub const MESSAGE_OVERHEAD: usize = 4;
+
+/// Counts tokens for one model.
+#[derive(Clone, Copy)]
+pub enum Tokenizer {
+    /// The model's own byte-pair encoding.
+    Bpe(&'static CoreBPE),
+
+    /// ~4 characters per token.
+    Estimate,
+}
+
+impl Tokenizer {
+    pub fn for_model(model: &str) -> Self {
+        match get_tokenizer(base_name(model)) {
+            Some(Encoding::O200kBase) => Self::Bpe(tiktoken_rs::o200k_base_singleton()),
+            Some(Encoding::Cl100kBase) => Self::Bpe(tiktoken_rs::cl100k_base_singleton()),
+            _ => Self::Estimate,
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Bpe(bpe) => bpe.encode_ordinary(text).len(),
+            Self::Estimate => text.chars().count().div_ceil(4),
+        }
+    }
+
+    /// Whether counts are exact rather than estimated.
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Self::Bpe(_))
+    }
+}
+
+/// Context window of `model` in tokens, from the vendors' model docs.
+pub fn context_window(model: &str) -> usize {
+    let model = base_name(model).to_lowercase();
+    let known: [(&str, usize); 11] = [
+        ("claude", 200_000),
+        ("gpt-4.1", 1_047_576),
+        ("gpt-5", 400_000),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("gpt-4o", 128_000),
+        ("chatgpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4", 8_192),
+        ("gpt-3.5", 16_385),
+    ];
+    known
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map_or(DEFAULT_CONTEXT_WINDOW, |(_, window)| *window)
+}
+
+/// How many tokens the history may use before it's summarized.
+#[derive(Clone, Copy)]
+pub struct TokenBudget {
+    pub tokenizer: Tokenizer,
+    pub max_tokens: usize,
+}
+
+impl TokenBudget {
+    /// `fraction` of the context window (`window`, or the model's own).
+    pub fn new(model: &str, window: Option<usize>, fraction: f64) -> Self {
+        let window = window.unwrap_or_else(|| context_window(model));
+        Self {
+            tokenizer: Tokenizer::for_model(model),
+            max_tokens: (window as f64 * fraction.clamp(0.05, 1.0)) as usize,
+        }
+    }
+}
+
+/// `openai/gpt-4o` → `gpt-4o`
+fn base_name(model: &str) -> &str {
+    model.rsplit('/').next().unwrap_or(model)
+}
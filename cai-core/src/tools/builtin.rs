@@ -0,0 +1,246 @@
+//! The tools every client starts with.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{ json, Value };
+
+use super::Tool;
+use crate::approval::{ ActionKind, ApprovalRequest };
+use crate::client_util::{ run_python, run_terminal };
+use crate::edit::{ apply_edits, parse_unified_diff, unified_diff, Edit };
+use crate::rules::{ CommandRules, RuleVerdict };
+use crate::shell::SharedShell;
+use crate::ui_trait::MsgType;
+use crate::workspace::Workspace;
+
+/// String argument `key`, or an error naming it.
+pub(crate) fn str_arg<'a>(args: &'a Value, key: &str) -> anyhow::Result<&'a str> {
+    args.get(key)
+        .and_then(|x| x.as_str())
+        .ok_or_else(|| anyhow::anyhow!("missing string argument `{}`", key))
+}
+
+/// The tool's workspace, or one rooted at the process cwd.
+pub(crate) fn workspace_or_cwd(workspace: &Option<Arc<Workspace>>) -> anyhow::Result<Arc<Workspace>> {
+    match workspace {
+        Some(workspace) => Ok(workspace.clone()),
+        None => Ok(Arc::new(Workspace::current()?)),
+    }
+}
+
+/// Runs commands in the client's persistent shell session (same cwd, env and
+/// timeout as ```` ```terminal ```` blocks); a client attaches one before the
+/// first call. Standalone, every call spawns a fresh shell in the process cwd,
+/// checked against the built-in rules.
+#[derive(Default)]
+pub struct RunTerminal {
+    shell: Option<SharedShell>,
+}
+
+impl RunTerminal {
+    pub fn new(shell: SharedShell) -> Self {
+        Self { shell: Some(shell) }
+    }
+}
+
+#[async_trait]
+impl Tool for RunTerminal {
+    fn name(&self) -> &str {
+        "run_terminal"
+    }
+
+    fn description(&self) -> &str {
+        "Execute a command in the user's native shell"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "command": { "type": "string" } },
+            "required": ["command"],
+            "additionalProperties": false
+        })
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        ApprovalRequest::command(self.name(), str_arg(args, "command").unwrap_or(""))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let command = str_arg(&args, "command")?.to_string();
+        let shell = self.shell.clone();
+        tokio::task::spawn_blocking(move || match shell {
+            Some(shell) => shell.lock().unwrap().run(&command),
+            None => match CommandRules::builtin().check(&command) {
+                RuleVerdict::Deny { rule, command } => Err(anyhow::anyhow!("`{}` denied by rule `{}`", command, rule)),
+                _ => Ok(run_terminal(&command)),
+            },
+        }).await?
+    }
+}
+
+pub struct RunPython;
+
+#[async_trait]
+impl Tool for RunPython {
+    fn name(&self) -> &str {
+        "run_python"
+    }
+
+    fn description(&self) -> &str {
+        "Run a Python snippet in the project workspace"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "code": { "type": "string" } },
+            "required": ["code"],
+            "additionalProperties": false
+        })
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        ApprovalRequest::new(ActionKind::Execute, self.name(), str_arg(args, "code").unwrap_or(""))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let code = str_arg(&args, "code")?.to_string();
+        Ok(tokio::task::spawn_blocking(move || run_python(&code)).await?)
+    }
+}
+
+/// Writes files inside the client's workspace. Without one, paths are
+/// confined to the process cwd.
+#[derive(Default)]
+pub struct WriteFile {
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl WriteFile {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace: Some(workspace) }
+    }
+}
+
+#[async_trait]
+impl Tool for WriteFile {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn description(&self) -> &str {
+        "Write content to a file path, creating parent directories as needed"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "content": { "type": "string" }
+            },
+            "required": ["path", "content"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Write
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        ApprovalRequest::write(self.name(), str_arg(args, "path").unwrap_or(""))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        workspace_or_cwd(&self.workspace)?.write(str_arg(&args, "path")?, str_arg(&args, "content")?)?;
+        Ok(String::from("[write_file] OK"))
+    }
+}
+
+/// Changes part of a file through search/replace edits or a unified diff
+/// (see `edit.rs`), and returns the resulting diff.
+#[derive(Default)]
+pub struct EditFile {
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl EditFile {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace: Some(workspace) }
+    }
+
+    const OK: &'static str = "[edit_file] OK\n";
+}
+
+#[async_trait]
+impl Tool for EditFile {
+    fn name(&self) -> &str {
+        "edit_file"
+    }
+
+    fn description(&self) -> &str {
+        "Edit an existing file by replacing exact snippets (`edits`: search/replace pairs, \
+         search must be unique) or applying a unified diff (`diff`). Returns the resulting diff"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "edits": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "search": { "type": "string" },
+                            "replace": { "type": "string" }
+                        },
+                        "required": ["search", "replace"],
+                        "additionalProperties": false
+                    }
+                },
+                "diff": { "type": "string" }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Write
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        ApprovalRequest::write(self.name(), str_arg(args, "path").unwrap_or(""))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let path = str_arg(&args, "path")?;
+        let edits: Vec<Edit> = match (args.get("edits").and_then(|e| e.as_array()), args.get("diff")) {
+            (Some(_), Some(_)) => anyhow::bail!("pass either `edits` or `diff`, not both"),
+            (Some(edits), None) => edits
+                .iter()
+                .map(|e| Ok(Edit::new(str_arg(e, "search")?, str_arg(e, "replace")?)))
+                .collect::<anyhow::Result<_>>()?,
+            (None, Some(_)) => parse_unified_diff(str_arg(&args, "diff")?)?,
+            (None, None) => anyhow::bail!("pass either `edits` or `diff`"),
+        };
+
+        let workspace = workspace_or_cwd(&self.workspace)?;
+        let before = workspace.read(path)?;
+        let after = apply_edits(&before, &edits)?;
+        if after == before {
+            return Ok(String::from("[edit_file] No changes"));
+        }
+        workspace.write(path, &after)?;
+        Ok(format!("{}{}", Self::OK, unified_diff(path, &before, &after)))
+    }
+
+    fn render(&self, result: &str) -> Option<MsgType> {
+        result.strip_prefix(Self::OK).map(|diff| MsgType::Diff(diff.to_string()))
+    }
+}
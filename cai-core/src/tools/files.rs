@@ -0,0 +1,404 @@
+//! Read-only workspace tools: `read_file`, `list_dir`, `glob` and `grep`.
+//!
+//! Paths are confined to the workspace like the write tools, walks skip
+//! hidden and .gitignore'd files, and every output is capped so a huge
+//! file or tree can't flood the context.
+
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use globset::Glob;
+use ignore::WalkBuilder;
+use regex::RegexBuilder;
+use serde_json::{ json, Value };
+
+use super::Tool;
+use super::builtin::{ str_arg, workspace_or_cwd };
+use crate::approval::ActionKind;
+use crate::workspace::Workspace;
+
+/// Lines `read_file` returns per call.
+pub const MAX_READ_LINES: usize = 400;
+
+/// Entries `list_dir` / `glob` return per call.
+pub const MAX_ENTRIES: usize = 500;
+
+/// Matches `grep` returns per call.
+pub const MAX_MATCHES: usize = 200;
+
+/// Bytes any of these tools return per call.
+pub const MAX_OUTPUT_BYTES: usize = 30_000;
+
+/// Characters of a matched line `grep` shows.
+const MAX_LINE_CHARS: usize = 300;
+
+/// Characters of a line `read_file` shows, so any line fits in a call.
+const MAX_READ_LINE_CHARS: usize = 2_000;
+
+/// Unsigned integer argument `key`, if given.
+fn usize_arg(args: &Value, key: &str) -> Option<usize> {
+    args.get(key).and_then(|v| v.as_u64()).map(|v| v as usize)
+}
+
+/// Path of `path` relative to the workspace root, for display.
+fn display_path(workspace: &Workspace, path: &Path) -> String {
+    path.strip_prefix(workspace.root()).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Walker over `dir` that skips hidden and ignored files and doesn't
+/// follow symlinks out of the workspace.
+fn walker(dir: &Path, max_depth: Option<usize>) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(dir);
+    builder.max_depth(max_depth).follow_links(false).require_git(false).sort_by_file_name(|a, b| a.cmp(b));
+    builder
+}
+
+/// The first `max` characters of `line`, with `…` when it's longer.
+fn clip(line: &str, max: usize) -> String {
+    let mut text: String = line.chars().take(max).collect();
+    if text.len() < line.len() {
+        text.push('…');
+    }
+    text
+}
+
+/// Joins `lines` until `MAX_OUTPUT_BYTES`; returns the text and how many
+/// lines it holds.
+fn joined(lines: Vec<String>) -> (String, usize) {
+    let mut out = String::new();
+    let mut kept = 0;
+    for line in lines {
+        if out.len() + line.len() + 1 > MAX_OUTPUT_BYTES {
+            break;
+        }
+        out.push_str(&line);
+        out.push('\n');
+        kept += 1;
+    }
+    (out, kept)
+}
+
+/// Joins `lines` until `MAX_OUTPUT_BYTES`; says so when something was cut.
+fn capped(lines: Vec<String>, truncated: bool, more: &str) -> String {
+    let total = lines.len();
+    let (mut out, kept) = joined(lines);
+    if truncated || kept < total {
+        out.push_str(&format!("[truncated -- {}]\n", more));
+    }
+    out
+}
+
+// ── read_file ──
+
+#[derive(Default)]
+pub struct ReadFile {
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl ReadFile {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace: Some(workspace) }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadFile {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "Read a text file with line numbers. Optional 1-based start_line/end_line (inclusive); \
+         long files are returned in chunks"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "start_line": { "type": "integer", "minimum": 1 },
+                "end_line": { "type": "integer", "minimum": 1 }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let workspace = workspace_or_cwd(&self.workspace)?;
+        tokio::task::spawn_blocking(move || read_file(&workspace, &args)).await?
+    }
+}
+
+fn read_file(workspace: &Workspace, args: &Value) -> anyhow::Result<String> {
+    let content = workspace.read(str_arg(args, "path")?)?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.is_empty() {
+        return Ok(String::from("[read_file] empty file"));
+    }
+
+    let start = usize_arg(args, "start_line").unwrap_or(1).max(1);
+    if start > lines.len() {
+        anyhow::bail!("start_line {} is past the end of the file ({} lines)", start, lines.len());
+    }
+    let wanted_end = usize_arg(args, "end_line").unwrap_or(lines.len()).clamp(start, lines.len());
+    let end = wanted_end.min(start + MAX_READ_LINES - 1);
+
+    let numbered: Vec<String> = (start..=end)
+        .map(|n| format!("{:>5} | {}", n, clip(lines[n - 1], MAX_READ_LINE_CHARS)))
+        .collect();
+    // Read on from the last line that fit, not from `end`
+    let (mut out, kept) = joined(numbered);
+    let last = start + kept - 1;
+    if last < wanted_end {
+        out.push_str(&format!(
+            "[lines {}-{} of {} -- pass start_line={} to read on]\n",
+            start,
+            last,
+            lines.len(),
+            last + 1
+        ));
+    }
+    Ok(out)
+}
+
+// ── list_dir ──
+
+#[derive(Default)]
+pub struct ListDir {
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl ListDir {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace: Some(workspace) }
+    }
+}
+
+#[async_trait]
+impl Tool for ListDir {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+
+    fn description(&self) -> &str {
+        "List a directory tree (default: the working directory, depth 2), skipping hidden and .gitignore'd files"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "depth": { "type": "integer", "minimum": 1 }
+            },
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let workspace = workspace_or_cwd(&self.workspace)?;
+        tokio::task::spawn_blocking(move || list_dir(&workspace, &args)).await?
+    }
+}
+
+fn list_dir(workspace: &Workspace, args: &Value) -> anyhow::Result<String> {
+    let dir = workspace.resolve(str_arg(args, "path").unwrap_or("."))?;
+    if !dir.is_dir() {
+        anyhow::bail!("{} is not a directory", display_path(workspace, &dir));
+    }
+    let depth = usize_arg(args, "depth").unwrap_or(2).max(1);
+
+    let mut entries: Vec<String> = Vec::new();
+    let mut truncated = false;
+    for entry in walker(&dir, Some(depth)).build().flatten().skip(1) {
+        if entries.len() == MAX_ENTRIES {
+            truncated = true;
+            break;
+        }
+        let indent = "  ".repeat(entry.depth() - 1);
+        let name = entry.file_name().to_string_lossy();
+        let slash = if entry.file_type().is_some_and(|t| t.is_dir()) { "/" } else { "" };
+        entries.push(format!("{}{}{}", indent, name, slash));
+    }
+    if entries.is_empty() {
+        return Ok(String::from("[list_dir] empty directory"));
+    }
+    Ok(capped(entries, truncated, "list a subdirectory or lower the depth"))
+}
+
+// ── glob ──
+
+#[derive(Default)]
+pub struct GlobFiles {
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl GlobFiles {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace: Some(workspace) }
+    }
+}
+
+#[async_trait]
+impl Tool for GlobFiles {
+    fn name(&self) -> &str {
+        "glob"
+    }
+
+    fn description(&self) -> &str {
+        "Find files whose path (relative to `path`, default the working directory) matches a glob such as **/*.rs"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": { "type": "string" },
+                "path": { "type": "string" }
+            },
+            "required": ["pattern"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let workspace = workspace_or_cwd(&self.workspace)?;
+        tokio::task::spawn_blocking(move || glob_files(&workspace, &args)).await?
+    }
+}
+
+fn glob_files(workspace: &Workspace, args: &Value) -> anyhow::Result<String> {
+    let dir = workspace.resolve(str_arg(args, "path").unwrap_or("."))?;
+    let matcher = Glob::new(str_arg(args, "pattern")?)?.compile_matcher();
+
+    let mut found: Vec<String> = Vec::new();
+    let mut truncated = false;
+    for entry in walker(&dir, None).build().flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(&dir).unwrap_or(entry.path());
+        if matcher.is_match(relative) {
+            if found.len() == MAX_ENTRIES {
+                truncated = true;
+                break;
+            }
+            found.push(display_path(workspace, entry.path()));
+        }
+    }
+    if found.is_empty() {
+        return Ok(String::from("[glob] no matches"));
+    }
+    Ok(capped(found, truncated, "narrow the pattern"))
+}
+
+// ── grep ──
+
+#[derive(Default)]
+pub struct Grep {
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl Grep {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace: Some(workspace) }
+    }
+}
+
+#[async_trait]
+impl Tool for Grep {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn description(&self) -> &str {
+        "Search file contents for a regex under `path` (default the working directory). \
+         Optional `glob` filters file names, e.g. *.rs. Returns path:line: text"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": { "type": "string" },
+                "path": { "type": "string" },
+                "glob": { "type": "string" },
+                "case_insensitive": { "type": "boolean" }
+            },
+            "required": ["pattern"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let workspace = workspace_or_cwd(&self.workspace)?;
+        tokio::task::spawn_blocking(move || grep(&workspace, &args)).await?
+    }
+}
+
+fn grep(workspace: &Workspace, args: &Value) -> anyhow::Result<String> {
+    let root = workspace.resolve(str_arg(args, "path").unwrap_or("."))?;
+    let regex = RegexBuilder::new(str_arg(args, "pattern")?)
+        .case_insensitive(args.get("case_insensitive").and_then(|v| v.as_bool()).unwrap_or(false))
+        .build()?;
+    let filter = match str_arg(args, "glob") {
+        Ok(glob) => Some(Glob::new(glob)?.compile_matcher()),
+        Err(_) => None,
+    };
+
+    let files: Vec<PathBuf> = if root.is_file() {
+        vec![root.clone()]
+    } else {
+        walker(&root, None).build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                let name = path.file_name().map(Path::new).unwrap_or(path);
+                let relative = path.strip_prefix(&root).unwrap_or(path);
+                filter.as_ref().is_none_or(|f| f.is_match(name) || f.is_match(relative))
+            })
+            .collect()
+    };
+
+    let mut matches: Vec<String> = Vec::new();
+    let mut truncated = false;
+    'files: for path in files {
+        // Binary and non-UTF-8 files are skipped
+        let Ok(content) = std::fs::read_to_string(&path) else { continue };
+        for (n, line) in content.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            if matches.len() == MAX_MATCHES {
+                truncated = true;
+                break 'files;
+            }
+            let text = clip(line.trim(), MAX_LINE_CHARS);
+            matches.push(format!("{}:{}: {}", display_path(workspace, &path), n + 1, text));
+        }
+    }
+    if matches.is_empty() {
+        return Ok(String::from("[grep] no matches"));
+    }
+    Ok(capped(matches, truncated, "narrow the pattern, path or glob"))
+}
@@ -0,0 +1,84 @@
+//! `search_history`: keyword search over the saved sessions, so the
+//! model can look up what was said or run in earlier conversations.
+
+use async_trait::async_trait;
+use serde_json::{ json, Value };
+
+use super::Tool;
+use super::builtin::str_arg;
+use crate::approval::ActionKind;
+use crate::search::{ format_date, parse_date, parse_role, search, SearchQuery };
+use crate::sessions::{ now, SessionStore };
+
+/// Hits `search_history` returns per call.
+pub const MAX_HITS: usize = 20;
+
+pub struct SearchHistory {
+    store: SessionStore,
+}
+
+impl SearchHistory {
+    pub fn new(store: SessionStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Tool for SearchHistory {
+    fn name(&self) -> &str {
+        "search_history"
+    }
+
+    fn description(&self) -> &str {
+        "Search the messages of all saved chat sessions by keywords, best matches first. \
+         Optional filters: role (user, assistant, system, tool), session id, and since/until \
+         (YYYY-MM-DD, or relative like 3d, 12h, 2w)"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "role": { "type": "string", "enum": ["user", "assistant", "system", "tool"] },
+                "session": { "type": "string" },
+                "since": { "type": "string" },
+                "until": { "type": "string" },
+                "limit": { "type": "integer", "minimum": 1, "maximum": MAX_HITS }
+            },
+            "required": ["query"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let text = |key: &str| args.get(key).and_then(|v| v.as_str()).filter(|v| !v.trim().is_empty());
+        let query = SearchQuery {
+            text: str_arg(&args, "query")?.to_string(),
+            role: text("role").map(parse_role).transpose()?,
+            session: text("session").map(str::to_string),
+            since: text("since").map(|date| parse_date(date, now())).transpose()?,
+            until: text("until").map(|date| parse_date(date, now())).transpose()?,
+            limit: args.get("limit").and_then(|v| v.as_u64()).map_or(10, |v| v as usize).clamp(1, MAX_HITS),
+        };
+
+        let hits = search(&self.store, &query)?;
+        if hits.is_empty() {
+            return Ok(String::from("[search_history] no matches"));
+        }
+        Ok(hits.iter()
+            .map(|hit| format!(
+                "session {} #{} {:?} {}: {}\n",
+                hit.session,
+                hit.node,
+                hit.role,
+                format_date(hit.time),
+                hit.highlighted("**", "**")
+            ))
+            .collect())
+    }
+}
@@ -0,0 +1,301 @@
+//! Memory tools: `memory_add`, `memory_update`, `memory_delete`,
+//! `memory_search` and `memory_view`, so the model can keep notes
+//! across sessions. They share the client's `MemoryManager`.
+
+use async_trait::async_trait;
+use serde_json::{ json, Value };
+
+use super::Tool;
+use super::builtin::str_arg;
+use crate::approval::{ ActionKind, ApprovalRequest };
+use crate::memory_manager::{ MemoryChange, MemoryEntry, SharedMemory };
+use crate::ui_trait::MsgType;
+
+/// Entries `memory_search` returns per call.
+pub const MAX_RESULTS: usize = 50;
+
+fn id_arg(args: &Value) -> anyhow::Result<u64> {
+    args.get("id")
+        .and_then(|v| v.as_u64().or_else(|| v.as_str()?.trim().trim_start_matches('#').parse().ok()))
+        .ok_or_else(|| anyhow::anyhow!("missing memory id `id`"))
+}
+
+fn tags_arg(args: &Value) -> Option<Vec<String>> {
+    let tags = args.get("tags")?.as_array()?;
+    Some(tags.iter().filter_map(|tag| tag.as_str()).map(str::to_string).collect())
+}
+
+fn importance_arg(args: &Value) -> Option<f64> {
+    args.get("importance").and_then(|v| v.as_f64())
+}
+
+/// `#3 [rust] prefers tabs (importance 0.8, session 2)`
+fn details(entry: &MemoryEntry) -> String {
+    let source = entry.source.as_deref().map(|id| format!(", session {}", id)).unwrap_or_default();
+    format!("{} (importance {:.1}{})", entry.line(), entry.importance, source)
+}
+
+fn listing(entries: &[&MemoryEntry], empty: &str) -> String {
+    if entries.is_empty() {
+        return empty.to_string();
+    }
+    let mut out: Vec<String> = entries.iter().take(MAX_RESULTS).map(|entry| details(entry)).collect();
+    if entries.len() > MAX_RESULTS {
+        out.push(format!("[{} more -- narrow the search]", entries.len() - MAX_RESULTS));
+    }
+    out.join("\n")
+}
+
+fn entry_properties() -> Value {
+    json!({
+        "content": { "type": "string" },
+        "tags": { "type": "array", "items": { "type": "string" } },
+        "importance": { "type": "number", "minimum": 0, "maximum": 1 }
+    })
+}
+
+// ── memory_add ──
+
+pub struct MemoryAdd {
+    memory: SharedMemory,
+}
+
+impl MemoryAdd {
+    pub fn new(memory: SharedMemory) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryAdd {
+    fn name(&self) -> &str {
+        "memory_add"
+    }
+
+    fn description(&self) -> &str {
+        "Remember something across sessions: user preferences, project facts, decisions. Optional tags, \
+         and importance from 0 (trivia) to 1 (always relevant; default 0.5). Returns the memory's id"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": entry_properties(),
+            "required": ["content"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Write
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        let content = args.get("content").and_then(|v| v.as_str()).unwrap_or("");
+        ApprovalRequest::new(self.kind(), self.name(), format!("remember: {}", content))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let content = str_arg(&args, "content")?;
+        let tags = tags_arg(&args).unwrap_or_default();
+        let entry = self.memory.lock().unwrap().add(content, &tags, importance_arg(&args))?;
+        Ok(format!("[memory_add] saved {}", details(&entry)))
+    }
+
+    fn render(&self, result: &str) -> Option<MsgType> {
+        Some(MsgType::Plain(result.to_string()))
+    }
+}
+
+// ── memory_update ──
+
+pub struct MemoryUpdate {
+    memory: SharedMemory,
+}
+
+impl MemoryUpdate {
+    pub fn new(memory: SharedMemory) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryUpdate {
+    fn name(&self) -> &str {
+        "memory_update"
+    }
+
+    fn description(&self) -> &str {
+        "Change memory `id`: its content, tags (replacing them) or importance. Fields left out are kept"
+    }
+
+    fn schema(&self) -> Value {
+        let mut properties = entry_properties();
+        properties["id"] = json!({ "type": "integer", "minimum": 1 });
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": ["id"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Write
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        let id = args.get("id").map(|id| id.to_string()).unwrap_or_default();
+        let content = args.get("content").and_then(|v| v.as_str()).unwrap_or("(content kept)");
+        ApprovalRequest::new(self.kind(), self.name(), format!("update memory #{}: {}", id.trim_matches('"'), content))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let change = MemoryChange {
+            content: args.get("content").and_then(|v| v.as_str()).map(str::to_string),
+            tags: tags_arg(&args),
+            importance: importance_arg(&args),
+        };
+        let entry = self.memory.lock().unwrap().update(id_arg(&args)?, change)?;
+        Ok(format!("[memory_update] updated {}", details(&entry)))
+    }
+
+    fn render(&self, result: &str) -> Option<MsgType> {
+        Some(MsgType::Plain(result.to_string()))
+    }
+}
+
+// ── memory_delete ──
+
+pub struct MemoryDelete {
+    memory: SharedMemory,
+}
+
+impl MemoryDelete {
+    pub fn new(memory: SharedMemory) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryDelete {
+    fn name(&self) -> &str {
+        "memory_delete"
+    }
+
+    fn description(&self) -> &str {
+        "Forget memory `id` (e.g. when it's outdated or wrong)"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "id": { "type": "integer", "minimum": 1 } },
+            "required": ["id"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Write
+    }
+
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        let id = args.get("id").map(|id| id.to_string()).unwrap_or_default();
+        ApprovalRequest::new(self.kind(), self.name(), format!("forget memory #{}", id.trim_matches('"')))
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let entry = self.memory.lock().unwrap().delete(id_arg(&args)?)?;
+        Ok(format!("[memory_delete] forgot {}", entry.line()))
+    }
+
+    fn render(&self, result: &str) -> Option<MsgType> {
+        Some(MsgType::Plain(result.to_string()))
+    }
+}
+
+// ── memory_search ──
+
+pub struct MemorySearch {
+    memory: SharedMemory,
+}
+
+impl MemorySearch {
+    pub fn new(memory: SharedMemory) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemorySearch {
+    fn name(&self) -> &str {
+        "memory_search"
+    }
+
+    fn description(&self) -> &str {
+        "Find memories containing every word of `query` (in content or tags, any case), optionally \
+         only those with `tag`"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "tag": { "type": "string" }
+            },
+            "required": ["query"],
+            "additionalProperties": false
+        })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let query = str_arg(&args, "query")?;
+        let tag = args.get("tag").and_then(|v| v.as_str()).filter(|tag| !tag.trim().is_empty());
+        let memory = self.memory.lock().unwrap();
+        Ok(listing(&memory.search(query, tag), "[memory_search] no matches"))
+    }
+}
+
+// ── memory_view ──
+
+pub struct MemoryView {
+    memory: SharedMemory,
+}
+
+impl MemoryView {
+    pub fn new(memory: SharedMemory) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryView {
+    fn name(&self) -> &str {
+        "memory_view"
+    }
+
+    fn description(&self) -> &str {
+        "List every memory with its id, tags, importance and the session it came from"
+    }
+
+    fn schema(&self) -> Value {
+        json!({ "type": "object", "properties": {}, "additionalProperties": false })
+    }
+
+    fn kind(&self) -> ActionKind {
+        ActionKind::Read
+    }
+
+    async fn invoke(&self, _args: Value) -> anyhow::Result<String> {
+        let mut memory = self.memory.lock().unwrap();
+        memory.load()?;
+        let entries: Vec<&MemoryEntry> = memory.entries().iter().collect();
+        Ok(listing(&entries, "[memory_view] no memories yet"))
+    }
+}
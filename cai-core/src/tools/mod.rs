@@ -0,0 +1,149 @@
+//! ===============================================================
+//! Native Tools
+//!
+//! Defines the `Tool` trait every function-calling tool implements
+//! and the `ToolRegistry` the `Client` dispatches tool calls through.
+//! The registry also generates the `tools` array sent to providers,
+//! so a tool is declared in exactly one place.
+//!
+//! Library users can register their own tools on `Client::tools`.
+//! ===============================================================
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{ json, Value };
+
+use crate::approval::{ ActionKind, ApprovalRequest };
+use crate::types::ToolCall;
+use crate::ui_trait::MsgType;
+
+pub mod builtin;
+pub use builtin::{ EditFile, RunPython, RunTerminal, WriteFile };
+
+pub mod files;
+pub use files::{ GlobFiles, Grep, ListDir, ReadFile };
+
+pub mod history;
+pub use history::SearchHistory;
+
+pub mod memory;
+pub use memory::{ MemoryAdd, MemoryDelete, MemorySearch, MemoryUpdate, MemoryView };
+
+/// A tool the model can call.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Function name the model calls, e.g. "run_terminal".
+    fn name(&self) -> &str;
+
+    /// What the tool does -- shown to the model.
+    fn description(&self) -> &str;
+
+    /// JSON schema of the arguments object.
+    fn schema(&self) -> Value;
+
+    /// What the tool can do -- decides when the approval gate asks.
+    /// Defaults to the most cautious answer.
+    fn kind(&self) -> ActionKind {
+        ActionKind::Execute
+    }
+
+    /// How a call is presented to the user for approval.
+    fn approval_request(&self, args: &Value) -> ApprovalRequest {
+        ApprovalRequest::new(self.kind(), self.name(), format!("{}({})", self.name(), args))
+    }
+
+    /// Runs the tool. The returned text (or error) is sent back to the model.
+    async fn invoke(&self, args: Value) -> anyhow::Result<String>;
+
+    /// What to show the user for a result; `None` → nothing.
+    fn render(&self, _result: &str) -> Option<MsgType> {
+        None
+    }
+}
+
+/// Ordered set of tools, looked up by name.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in tools: `run_terminal`, `run_python`, `write_file`, `edit_file`,
+    /// and the read-only `read_file`, `list_dir`, `glob` and `grep`.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(RunTerminal::default());
+        registry.register(RunPython);
+        registry.register(WriteFile::default());
+        registry.register(EditFile::default());
+        registry.register(ReadFile::default());
+        registry.register(ListDir::default());
+        registry.register(GlobFiles::default());
+        registry.register(Grep::default());
+        registry
+    }
+
+    /// Adds a tool, replacing any tool with the same name.
+    pub fn register(&mut self, tool: impl Tool + 'static) {
+        self.register_arc(Arc::new(tool));
+    }
+
+    /// Same as `register`, for tools shared with other owners.
+    pub fn register_arc(&mut self, tool: Arc<dyn Tool>) {
+        match self.tools.iter().position(|t| t.name() == tool.name()) {
+            Some(pos) => self.tools[pos] = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// Removes a tool by name. Returns whether it was registered.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let before = self.tools.len();
+        self.tools.retain(|t| t.name() != name);
+        self.tools.len() != before
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.name()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// The Chat Completions `tools` array describing every registered tool.
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.tools.iter().map(|tool| json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name(),
+                    "description": tool.description(),
+                    "parameters": tool.schema(),
+                }
+            })).collect()
+        )
+    }
+
+    /// Runs a tool call and returns the text to send back to the model.
+    /// Unknown tools and failures are reported as text, never as panics.
+    pub async fn invoke(&self, call: &ToolCall) -> String {
+        let Some(tool) = self.get(&call.name) else {
+            return format!("[tool error] Unknown tool: {}", call.name);
+        };
+        match tool.invoke(call.args()).await {
+            Ok(output) => output,
+            Err(e) => format!("[{}] ERROR: {:#}", call.name, e),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 
 #[derive(Debug, Clone,  Serialize, Deserialize)]
@@ -7,11 +8,140 @@ pub enum MessageRole {
     User,
     Assistant,
     System,
+
+    /// Result of a native tool call.
+    Tool,
 }
 
 
+/// A message of the conversation. Assistant messages may carry tool
+/// calls; each call is answered by a `Tool` message with its id.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
-    pub role: MessageRole, 
+    pub role: MessageRole,
     pub content: String,
-}
\ No newline at end of file
+
+    /// Tool calls requested by an assistant message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+
+    /// The call a `Tool` message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: MessageRole, content: impl Into<String>) -> Self {
+        Self { role, content: content.into(), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    /// An assistant message requesting `calls`.
+    pub fn tool_calls(content: impl Into<String>, calls: Vec<ToolCall>) -> Self {
+        Self { tool_calls: calls, ..Self::new(MessageRole::Assistant, content) }
+    }
+
+    /// The result of tool call `id`.
+    pub fn tool_result(id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self { tool_call_id: Some(id.into()), ..Self::new(MessageRole::Tool, content) }
+    }
+
+    /// Whether this is part of a tool exchange (a call or a result).
+    pub fn is_tool_traffic(&self) -> bool {
+        !self.tool_calls.is_empty() || matches!(self.role, MessageRole::Tool)
+    }
+
+    /// Chat Completions wire format.
+    pub fn to_json(&self) -> Value {
+        let mut msg = json!({ "role": self.role, "content": self.content });
+        if !self.tool_calls.is_empty() {
+            msg["tool_calls"] = Value::Array(self.tool_calls.iter().map(ToolCall::to_json).collect());
+        }
+        if let Some(id) = &self.tool_call_id {
+            msg["tool_call_id"] = json!(id);
+        }
+        msg
+    }
+
+    /// Wire format without tool structure, for requests that don't offer
+    /// tools: calls are described in the assistant text and results
+    /// become system messages.
+    pub fn to_text_json(&self) -> Value {
+        let calls: Vec<String> = self.tool_calls.iter().map(|c| format!("[called {}({})]", c.name, c.arguments)).collect();
+        match self.role {
+            MessageRole::Tool => json!({ "role": "system", "content": format!("[tool result]\n{}", self.content) }),
+            _ if !calls.is_empty() => {
+                let content = [self.content.as_str()].into_iter().chain(calls.iter().map(String::as_str))
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                json!({ "role": self.role, "content": content })
+            }
+            _ => json!({ "role": self.role, "content": self.content }),
+        }
+    }
+}
+
+
+/// A tool invocation requested by the model.
+/// `arguments` is the raw JSON string as emitted by the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+impl ToolCall {
+    /// Parses a Chat Completions `tool_calls[i]` entry.
+    ///
+    /// Some OpenAI-compatible servers omit the id or send `arguments` as an
+    /// object instead of a string -- both are tolerated.
+    pub fn from_json(v: &Value) -> Self {
+        let function = v.get("function");
+        let arguments = match function.and_then(|f| f.get("arguments")) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => "{}".to_string(),
+            Some(other) => other.to_string(),
+        };
+        Self {
+            id: v.get("id").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+            name: function.and_then(|f| f.get("name")).and_then(|x| x.as_str()).unwrap_or("").to_string(),
+            arguments,
+        }
+    }
+
+    /// Serializes back into a Chat Completions `tool_calls[i]` entry.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "arguments": self.arguments,
+            }
+        })
+    }
+
+    /// Parsed arguments -- empty object if the model sent invalid JSON.
+    pub fn args(&self) -> Value {
+        serde_json::from_str(&self.arguments).unwrap_or(json!({}))
+    }
+}
+
+
+/// Token usage reported by a provider.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl Usage {
+    /// Accumulates another usage report into this one.
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
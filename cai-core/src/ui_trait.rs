@@ -3,6 +3,8 @@
 // Interface for UI's providers must follow to be used as a UI
 // ==========================================================
 use serde::{Serialize, Deserialize};
+
+use crate::approval::{ ApprovalDecision, ApprovalRequest };
 #[derive(Serialize, Deserialize, Debug)]
 pub enum MsgRole {
     // Since assistant response can contain code blocks, etc, they would be handled in the UI side.
@@ -24,6 +26,9 @@ pub enum MsgType {
         title: String,
         content: Vec<String>,
     },
+
+    // Unified diff of a file edit -- UIs may colour it
+    Diff(String),
 }
 
 
@@ -42,4 +47,34 @@ pub trait UIBase {
 
     /// Will be called when a message is to be printed
     fn print_message(&self, message_type: MsgRole, message_format: MsgType);
+
+
+    // ── Streaming (optional) ──────────────────────────────────────
+    // UIs that return `true` here get assistant messages chunk by chunk
+    // through the three hooks below, instead of one `print_message` call.
+
+    /// Whether the UI can render assistant messages incrementally.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Will be called before the first chunk of a streamed assistant message.
+    fn begin_assistant_message(&self) {}
+
+    /// Will be called for every chunk of a streamed assistant message.
+    fn assistant_delta(&self, _delta: &str) {}
+
+    /// Will be called once a streamed assistant message is complete.
+    fn end_assistant_message(&self) {}
+
+
+    // ── Approvals ─────────────────────────────────────────────────
+
+    /// Will be called when an action needs the user's approval
+    /// (see `approval.rs`). UIs that can't ask reject it.
+    fn request_approval(&self, _request: &ApprovalRequest) -> ApprovalDecision {
+        ApprovalDecision::Reject {
+            feedback: Some("This interface can't ask the user for approval.".to_string()),
+        }
+    }
 }
@@ -0,0 +1,163 @@
+//! ===============================================================
+//! Workspace
+//!
+//! The directory file tools are confined to. Every path the model
+//! gives (write blocks, `write_file`, ...) is resolved against the
+//! workspace root and must stay inside it -- or inside one of the
+//! explicitly allowed extra directories.
+//!
+//! Resolution canonicalizes the longest existing prefix of the path,
+//! so symlinks pointing outside are caught, and normalizes the part
+//! that doesn't exist yet (`new/../../x` still escapes). Dangling
+//! symlinks are rejected since writing through them could land
+//! anywhere.
+//!
+//! With a checkpoint store attached, every write is snapshotted
+//! before it is applied (see `checkpoints.rs`).
+//! ===============================================================
+
+use std::path::{ Component, Path, PathBuf };
+use std::sync::{ Mutex, MutexGuard };
+
+use anyhow::{ bail, Context };
+
+use crate::checkpoints::CheckpointStore;
+
+#[derive(Debug)]
+pub struct Workspace {
+    /// Canonical root; relative paths are resolved against it.
+    root: PathBuf,
+
+    /// Canonical extra directories that may be written to.
+    writable: Vec<PathBuf>,
+
+    /// Snapshots taken before each write, if enabled.
+    checkpoints: Option<Mutex<CheckpointStore>>,
+}
+
+impl Workspace {
+    /// A workspace rooted at `root`, which must exist.
+    pub fn new(root: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let root = root.as_ref();
+        let root = root.canonicalize()
+            .with_context(|| format!("workspace root {} not found", root.display()))?;
+        Ok(Self { root, writable: Vec::new(), checkpoints: None })
+    }
+
+    /// A workspace rooted at the process cwd.
+    pub fn current() -> anyhow::Result<Self> {
+        Self::new(std::env::current_dir()?)
+    }
+
+    /// Allows writing into `dirs` too (relative ones are resolved against the
+    /// root). Every directory must exist.
+    pub fn with_writable<P: AsRef<Path>>(mut self, dirs: &[P]) -> anyhow::Result<Self> {
+        for dir in dirs {
+            let dir = self.root.join(dir.as_ref());
+            let dir = dir.canonicalize()
+                .with_context(|| format!("writable directory {} not found", dir.display()))?;
+            self.writable.push(dir);
+        }
+        Ok(self)
+    }
+
+    /// Snapshots every write into `store` before applying it.
+    pub fn with_checkpoints(mut self, store: CheckpointStore) -> Self {
+        self.checkpoints = Some(Mutex::new(store));
+        self
+    }
+
+    /// The checkpoint store, if enabled.
+    pub fn checkpoints(&self) -> Option<MutexGuard<'_, CheckpointStore>> {
+        self.checkpoints.as_ref().map(|store| store.lock().unwrap())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn writable(&self) -> &[PathBuf] {
+        &self.writable
+    }
+
+    /// Absolute, symlink-free form of `path`, or an error when it points
+    /// outside the workspace and the allowed directories.
+    pub fn resolve(&self, path: &str) -> anyhow::Result<PathBuf> {
+        if path.trim().is_empty() {
+            bail!("empty path");
+        }
+        let resolved = resolve_path(&self.root.join(path))?;
+
+        if resolved.starts_with(&self.root) || self.writable.iter().any(|dir| resolved.starts_with(dir)) {
+            Ok(resolved)
+        } else {
+            bail!("{} is outside the workspace {}", path, self.root.display())
+        }
+    }
+
+    /// Reads `path` once it resolves inside the workspace.
+    pub fn read(&self, path: &str) -> anyhow::Result<String> {
+        let resolved = self.resolve(path)?;
+        std::fs::read_to_string(&resolved).with_context(|| format!("cannot read {}", resolved.display()))
+    }
+
+    /// Writes `content` to `path` (creating parent directories) once it
+    /// resolves inside the workspace and is checkpointed. Returns the
+    /// resolved path.
+    pub fn write(&self, path: &str, content: &str) -> anyhow::Result<PathBuf> {
+        let resolved = self.resolve(path)?;
+        if let Some(mut store) = self.checkpoints() {
+            store.record(&resolved, content)?;
+        }
+        if let Some(parent) = resolved.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("cannot create {}", parent.display()))?;
+        }
+        std::fs::write(&resolved, content)
+            .with_context(|| format!("cannot write {}", resolved.display()))?;
+        Ok(resolved)
+    }
+}
+
+/// Normalizes `path` lexically, then canonicalizes its longest existing
+/// prefix and appends the rest. Normalizing first means a `..` can't step
+/// out of a directory that doesn't exist yet onto a symlink the prefix
+/// never resolved (`missing/../link/x`).
+fn resolve_path(path: &Path) -> anyhow::Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    let path = normalized.as_path();
+    let mut existing = path.to_path_buf();
+    let mut rest: Vec<Component> = Vec::new();
+
+    let base = loop {
+        match existing.canonicalize() {
+            Ok(base) => break base,
+            Err(_) if existing.symlink_metadata().is_ok() => {
+                bail!("{} is a dangling symlink", existing.display());
+            }
+            Err(_) => {}
+        }
+        let Some(last) = path.components().nth_back(rest.len()) else {
+            bail!("cannot resolve {}", path.display());
+        };
+        rest.push(last);
+        if !existing.pop() {
+            bail!("cannot resolve {}", path.display());
+        }
+    };
+
+    let mut resolved = base;
+    for component in rest.into_iter().rev() {
+        resolved.push(component);
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use std::sync::Arc;
+
+use cai_core::{
+    agent_loop::{ process_response, start_session, LoopOutcome },
+    constants::{ CONTINUE_TOKEN, RESTART_TOKEN },
+    history_manager::History,
+    models::ModelSet,
+    provider::MockProvider,
+    rules::CommandRules,
+    shell::Shell,
+    types::MessageRole,
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+#[tokio::test]
+async fn tool_calls_run_and_results_are_sent_back() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("write_file", json!({ "path": "out.txt", "content": "hello" }))
+            .reply("written")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    let target = tmp.path().join("out.txt");
+    let ui = TestUI::default();
+
+    let reply = client.send_message(&ui, MessageRole::User, "write it", None).await;
+
+    assert_eq!(reply, "written");
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "hello");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].model, "test-model");
+    let tool_result = requests[1].messages.last().unwrap();
+    assert_eq!(tool_result["role"], "tool");
+    assert_eq!(tool_result["tool_call_id"], "call_0");
+    assert_eq!(tool_result["content"], "[write_file] OK");
+
+    // The call and its result are kept in history, before the final text
+    let history = client.history.get();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[1].tool_calls[0].name, "write_file");
+    assert!(matches!(history[2].role, MessageRole::Tool));
+    assert_eq!(history[2].tool_call_id.as_deref(), Some("call_0"));
+    assert_eq!(history[3].content, "written");
+    assert_eq!(ui.printed(), vec!["written".to_string()]);
+}
+
+#[tokio::test]
+async fn tool_traffic_is_replayed_on_the_next_turn() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("write_file", json!({ "path": "out.txt", "content": "hello" }))
+            .reply("written")
+            .reply("it says hello")
+            .reply("still hello")
+    );
+    let (mut client, _tmp) = test_client(mock.clone());
+    let ui = TestUI::default();
+
+    client.send_message(&ui, MessageRole::User, "write it", None).await;
+    client.send_message(&ui, MessageRole::User, "what did you write?", None).await;
+
+    let sent = &mock.requests()[2].messages;
+    assert_eq!(sent[1]["tool_calls"][0]["function"]["name"], "write_file");
+    assert_eq!(sent[2]["role"], "tool");
+    assert_eq!(sent[2]["content"], "[write_file] OK");
+
+    // Without tools on offer the exchange is sent as plain text
+    client.config.tools = false;
+    client.send_message(&ui, MessageRole::User, "and now?", None).await;
+    let sent = &mock.requests()[3].messages;
+    assert!(sent.iter().all(|m| m.get("tool_calls").is_none() && m["role"] != "tool"));
+    assert!(sent[1]["content"].as_str().unwrap().starts_with("[called write_file("));
+    assert_eq!(sent[2]["role"], "system");
+}
+
+#[tokio::test]
+async fn terminal_blocks_are_executed_and_reported() {
+    let mock = Arc::new(MockProvider::default().reply("looks good"));
+    let (mut client, tmp) = test_client(mock.clone());
+    client.attach_shell(Shell::new(tmp.path().to_str().unwrap()).unwrap());
+    let ui = TestUI::default();
+
+    let response = "Let me check\n```terminal\necho cai-test\n```".to_string();
+    let outcome = process_response(&ui, &mut client, response).await;
+
+    assert_eq!(outcome, LoopOutcome::AwaitInput);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    let sys = requests[0].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(sys.contains("[Command Execution Results]"));
+    assert!(sys.contains("cai-test"));
+}
+
+#[tokio::test]
+async fn tool_calls_and_terminal_blocks_share_the_shell_session() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("run_terminal", json!({ "command": "mkdir -p sub && cd sub && export CAI_VAR=42" }))
+            .reply("moved")
+            .reply("seen")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    client.attach_shell(Shell::new(tmp.path().to_str().unwrap()).unwrap());
+    let ui = TestUI::default();
+
+    client.send_message(&ui, MessageRole::User, "go to sub", None).await;
+    let response = "```terminal\necho \"$CAI_VAR-$(basename \"$(pwd)\")\"\n```".to_string();
+    process_response(&ui, &mut client, response).await;
+
+    let requests = mock.requests();
+    let sys = requests[2].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(sys.contains("42-sub"), "{}", sys);
+}
+
+#[tokio::test]
+async fn tool_calls_without_a_shell_use_the_configured_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("marker");
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("run_terminal", json!({ "command": format!("touch {}", marker.display()) }))
+            .reply("done")
+    );
+    let (mut client, _tmp) = test_client(mock.clone());
+    client.config.rules = CommandRules { deny: vec!["touch".into()], ..CommandRules::default() };
+    let ui = TestUI::default();
+
+    client.send_message(&ui, MessageRole::User, "make a marker", None).await;
+
+    let result = mock.requests()[1].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(result.contains("denied by rule `touch`"), "{}", result);
+    assert!(!marker.exists());
+}
+
+#[tokio::test]
+async fn continue_token_keeps_the_loop_going() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .reply(&format!("step 2 {}", CONTINUE_TOKEN))
+            .reply("done")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    client.attach_shell(Shell::new(tmp.path().to_str().unwrap()).unwrap());
+    let ui = TestUI::default();
+
+    let response = format!("step 1 {}", CONTINUE_TOKEN);
+    let outcome = process_response(&ui, &mut client, response).await;
+
+    assert_eq!(outcome, LoopOutcome::AwaitInput);
+    assert_eq!(mock.remaining(), 0);
+    let requests = mock.requests();
+    assert_eq!(requests[0].messages.last().unwrap()["content"], "[Continue]");
+}
+
+#[tokio::test]
+async fn restart_token_ends_the_session() {
+    let mock = Arc::new(MockProvider::default());
+    let (mut client, tmp) = test_client(mock.clone());
+    client.attach_shell(Shell::new(tmp.path().to_str().unwrap()).unwrap());
+    let ui = TestUI::default();
+
+    let outcome = process_response(&ui, &mut client, format!("bye {}", RESTART_TOKEN)).await;
+
+    assert_eq!(outcome, LoopOutcome::Restart);
+    assert!(mock.requests().is_empty());
+    assert!(tmp.path().join("history.json").exists());
+}
+
+#[tokio::test]
+async fn fresh_session_sends_the_system_prompt() {
+    let mock = Arc::new(MockProvider::default().reply("hi there"));
+    let (mut client, _tmp) = test_client(mock.clone());
+    let ui = TestUI::default();
+
+    let first = start_session(&ui, &mut client, "/work").await;
+
+    assert_eq!(first, "hi there");
+    let sent = &mock.requests()[0].messages[0];
+    assert_eq!(sent["role"], "system");
+    assert!(sent["content"].as_str().unwrap().contains("CWD: /work"));
+}
+
+#[tokio::test]
+async fn long_history_gets_summarized() {
+    let mock = Arc::new(MockProvider::default().reply("the summary").reply("answer"));
+    let (mut client, tmp) = test_client(mock.clone());
+    client.history = History::new(tmp.path().join("short.json").to_str().unwrap(), 5, 3);
+    for i in 0..5 {
+        client.history.add_message(MessageRole::User, format!("message {}", i));
+    }
+    let ui = TestUI::default();
+
+    let reply = client.send_message(&ui, MessageRole::User, "one more", None).await;
+
+    assert_eq!(reply, "answer");
+    let summarize = mock.requests()[0].messages[0]["content"].as_str().unwrap().to_string();
+    assert!(summarize.contains("message 1") && summarize.contains("message 3"));
+    assert!(!summarize.contains("message 0"));
+    assert!(client.history.get().iter().any(|m| m.content.contains("[Conversation summary]\nthe summary")));
+}
+
+#[tokio::test]
+async fn model_is_chosen_by_the_decider() {
+    let mock = Arc::new(MockProvider::default().reply("nano").reply("quick answer"));
+    let (mut client, _tmp) = test_client(mock.clone());
+    client.set_models(ModelSet::default());
+    let ui = TestUI::default();
+
+    client.send_message(&ui, MessageRole::User, "what's 2+2", None).await;
+
+    let requests = mock.requests();
+    assert_eq!(requests[0].model, ModelSet::default().mini);
+    assert_eq!(requests[1].model, ModelSet::default().nano);
+}
+
+#[tokio::test]
+async fn pinned_model_skips_the_decider() {
+    let mock = Arc::new(MockProvider::default().reply("quick answer"));
+    let (mut client, _tmp) = test_client(mock.clone());
+    client.set_models(ModelSet::default());
+    client.config.model = Some("gpt-4o".into());
+
+    client.send_message(&TestUI::default(), MessageRole::User, "what's 2+2", None).await;
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].model, "gpt-4o");
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use cai_core::tools::ToolRegistry;
+use cai_core::provider::{ AnthropicProvider, CompletionRequest, Provider };
+use common::MockServer;
+use serde_json::json;
+
+fn request() -> CompletionRequest {
+    let mut req = CompletionRequest::new("claude-sonnet-4-5", vec![
+        json!({ "role": "system", "content": "sys prompt" }),
+        json!({ "role": "user", "content": "list files" }),
+        json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "toolu_1",
+                "type": "function",
+                "function": { "name": "run_terminal", "arguments": "{\"command\":\"ls\"}" }
+            }]
+        }),
+        json!({ "role": "tool", "tool_call_id": "toolu_1", "content": "a.txt" }),
+        json!({ "role": "system", "content": "[Continue]" }),
+    ]);
+    req.tools = Some(ToolRegistry::with_defaults().to_json());
+    req.params = json!({ "max_completion_tokens": 1234, "tool_choice": "auto", "parallel_tool_calls": true });
+    req
+}
+
+#[tokio::test]
+async fn maps_request_and_tool_use_response() {
+    let server = MockServer::start(vec![(
+        "application/json",
+        json!({
+            "content": [
+                { "type": "text", "text": "Running it." },
+                { "type": "tool_use", "id": "toolu_2", "name": "write_file", "input": { "path": "b.txt", "content": "hi" } }
+            ],
+            "usage": { "input_tokens": 10, "output_tokens": 5 }
+        }).to_string(),
+    )]).await;
+
+    let provider = AnthropicProvider::new(Some(&server.base_url), Some("test-key".into()));
+    let completion = provider.complete(&request()).await.unwrap();
+
+    // Response → internal shape
+    assert_eq!(completion.content.as_deref(), Some("Running it."));
+    assert_eq!(completion.tool_calls.len(), 1);
+    assert_eq!(completion.tool_calls[0].id, "toolu_2");
+    assert_eq!(completion.tool_calls[0].name, "write_file");
+    assert_eq!(completion.tool_calls[0].args(), json!({ "path": "b.txt", "content": "hi" }));
+    assert_eq!(completion.usage.unwrap().total_tokens, 15);
+
+    // Request → Anthropic shape
+    let sent = &server.requests()[0];
+    assert_eq!(sent.path, "/v1/messages");
+    assert_eq!(sent.header("x-api-key"), Some("test-key"));
+    assert!(sent.header("anthropic-version").is_some());
+
+    let body = &sent.body;
+    assert_eq!(body["system"], "sys prompt");
+    assert_eq!(body["max_tokens"], 1234);
+    assert_eq!(body["tool_choice"], json!({ "type": "auto" }));
+    assert_eq!(body["tools"][0]["name"], "run_terminal");
+    assert_eq!(body["tools"][0]["input_schema"]["required"], json!(["command"]));
+
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0]["role"], "user");
+    assert_eq!(messages[1]["content"][0], json!({
+        "type": "tool_use", "id": "toolu_1", "name": "run_terminal", "input": { "command": "ls" }
+    }));
+    // tool_result and the following system note share one user turn
+    assert_eq!(messages[2]["role"], "user");
+    assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+    assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+    assert_eq!(messages[2]["content"][1]["text"], "[Continue]");
+}
+
+#[tokio::test]
+async fn plain_text_response_has_no_tool_calls() {
+    let server = MockServer::start(vec![(
+        "application/json",
+        json!({ "content": [{ "type": "text", "text": "hello" }] }).to_string(),
+    )]).await;
+
+    let provider = AnthropicProvider::new(Some(&server.base_url), None);
+    let completion = provider
+        .complete(&CompletionRequest::new("claude-haiku-4-5", vec![json!({ "role": "user", "content": "hi" })]))
+        .await
+        .unwrap();
+
+    assert_eq!(completion.content.as_deref(), Some("hello"));
+    assert!(completion.tool_calls.is_empty());
+    assert!(server.requests()[0].header("x-api-key").is_none());
+}
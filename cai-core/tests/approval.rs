@@ -0,0 +1,81 @@
+mod common;
+
+use std::sync::Arc;
+
+use cai_core::{
+    agent_loop::process_response,
+    approval::{ ApprovalDecision, ApprovalGate, ApprovalMode, ApprovalRequest },
+    provider::MockProvider,
+    shell::Shell,
+    types::MessageRole,
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+#[tokio::test]
+async fn rejected_tool_call_sends_feedback_to_the_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("out.txt");
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("write_file", json!({ "path": target.to_str().unwrap(), "content": "x" }))
+            .reply("ok, skipped")
+    );
+    let (mut client, _tmp) = test_client(mock.clone());
+    let ui = TestUI::with_decisions(vec![ApprovalDecision::Reject { feedback: Some("use notes.md instead".into()) }]);
+
+    client.send_message(&ui, MessageRole::User, "write it", None).await;
+
+    assert!(!target.exists());
+    assert_eq!(ui.asked(), vec![target.to_str().unwrap().to_string()]);
+    let result = mock.requests()[1].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(result.starts_with("[Rejected]"), "{}", result);
+    assert!(result.contains("use notes.md instead"));
+    assert!(client.tool_calls.is_empty());
+}
+
+#[tokio::test]
+async fn session_approval_covers_later_runs_of_the_same_program() {
+    let mock = Arc::new(MockProvider::default().reply("done"));
+    let (mut client, tmp) = test_client(mock.clone());
+    client.attach_shell(Shell::new(tmp.path().to_str().unwrap()).unwrap());
+    let ui = TestUI::with_decisions(vec![ApprovalDecision::ApproveSession]);
+
+    let response = "```terminal\necho one\necho two\nwhoami\n```".to_string();
+    process_response(&ui, &mut client, response).await;
+
+    assert_eq!(ui.asked(), vec!["echo one".to_string(), "whoami".to_string()]);
+    let sys = mock.requests()[0].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(sys.contains("echo two -> two"), "{}", sys);
+
+    // A compound line is covered only when all of its programs are
+    let mut gate = client.approval.clone();
+    let ui = TestUI::with_decisions(vec![ApprovalDecision::Reject { feedback: None }]);
+    let check = |gate: &mut ApprovalGate, line: &str| gate.check(&ui, &ApprovalRequest::command("run_terminal", line));
+    assert!(check(&mut gate, "echo a && FOO=1 echo b | echo c").is_ok());
+    assert!(check(&mut gate, "echo a; touch x").is_err());
+    assert_eq!(ui.asked(), vec!["echo a; touch x".to_string()]);
+}
+
+#[tokio::test]
+async fn deny_mode_blocks_without_asking_and_auto_never_asks() {
+    let mock = Arc::new(MockProvider::default().reply("understood").reply("written"));
+    let (mut client, tmp) = test_client(mock.clone());
+    let path = tmp.path().join("denied.txt");
+    let response = format!("```write[{}]\nhello\n```", path.to_str().unwrap());
+
+    client.approval = ApprovalGate::new(ApprovalMode::Deny);
+    let ui = TestUI::default();
+    process_response(&ui, &mut client, response.clone()).await;
+
+    assert!(!path.exists());
+    assert!(ui.asked().is_empty());
+    let sys = mock.requests()[0].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(sys.contains("[Denied]"), "{}", sys);
+
+    client.approval = ApprovalGate::new(ApprovalMode::Auto);
+    process_response(&ui, &mut client, response).await;
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+    assert!(ui.asked().is_empty());
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::sync::Arc;
+
+use cai_core::{
+    agent_loop::process_response,
+    checkpoints::CheckpointStore,
+    constants::CONTINUE_TOKEN,
+    provider::MockProvider,
+    types::MessageRole,
+    workspace::Workspace,
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+#[test]
+fn writes_are_snapshotted_and_restorable() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = CheckpointStore::open(dir.path().join(".checkpoints")).unwrap();
+    let workspace = Workspace::new(dir.path()).unwrap().with_checkpoints(store);
+    let file = workspace.root().join("a.txt");
+
+    workspace.write("a.txt", "one\n").unwrap();
+    workspace.write("a.txt", "two\n").unwrap();
+
+    let store = workspace.checkpoints().unwrap();
+    assert_eq!(store.list().len(), 2);
+    assert_eq!(store.list()[0].before, None);
+    assert!(store.diff(2).unwrap().contains("-one\n+two"));
+
+    store.restore(2).unwrap();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "one\n");
+    store.restore_turn(1).unwrap();
+    assert!(!file.exists());
+
+    // Reopening keeps the index; the blobs are content-addressed
+    drop(store);
+    let reopened = CheckpointStore::open(dir.path().join(".checkpoints")).unwrap();
+    assert_eq!(reopened.list().len(), 2);
+    assert_eq!(std::fs::read_dir(dir.path().join(".checkpoints/blobs")).unwrap().count(), 2);
+}
+
+#[tokio::test]
+async fn each_loop_iteration_is_one_turn() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .reply("```write[b.txt]\nsecond\n```")
+            .tool_call("write_file", json!({ "path": "c.txt", "content": "third" }))
+            .reply("done")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    let store = CheckpointStore::open(tmp.path().join("history.checkpoints")).unwrap();
+    client.attach_workspace(Workspace::new(tmp.path()).unwrap().with_checkpoints(store));
+    let ui = TestUI::default();
+
+    // Iteration 1 writes a.txt, iteration 2 writes b.txt and then c.txt through a tool call
+    let response = format!("```write[a.txt]\nfirst\n```\n{}", CONTINUE_TOKEN);
+    process_response(&ui, &mut client, response).await;
+    client.send_message(&ui, MessageRole::User, "again", None).await;
+
+    let workspace = client.workspace().unwrap();
+    let mut store = workspace.checkpoints().unwrap();
+    let turns: Vec<(String, usize)> = store.list()
+        .iter()
+        .map(|c| (c.path.file_name().unwrap().to_string_lossy().into_owned(), c.turn))
+        .collect();
+    assert_eq!(turns, vec![("a.txt".into(), 1), ("b.txt".into(), 2), ("c.txt".into(), 3)]);
+
+    let (turn, restored) = store.undo().unwrap();
+    assert_eq!(turn, 3);
+    assert_eq!(restored.len(), 1);
+    assert!(!tmp.path().join("c.txt").exists());
+    assert!(tmp.path().join("b.txt").exists());
+    assert_eq!(store.last_turn(), Some(2));
+}
+
+#[tokio::test]
+async fn tool_writes_belong_to_the_turn_of_their_response() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("write_file", json!({ "path": "a.txt", "content": "first" }))
+            .reply(&format!("wrote a.txt {}", CONTINUE_TOKEN))
+            .tool_call("write_file", json!({ "path": "b.txt", "content": "second" }))
+            .reply("wrote b.txt")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    let store = CheckpointStore::open(tmp.path().join("history.checkpoints")).unwrap();
+    client.attach_workspace(Workspace::new(tmp.path()).unwrap().with_checkpoints(store));
+    let ui = TestUI::default();
+
+    let response = client.send_message(&ui, MessageRole::User, "write both", None).await;
+    process_response(&ui, &mut client, response).await;
+
+    let workspace = client.workspace().unwrap();
+    let mut store = workspace.checkpoints().unwrap();
+    let turns: Vec<usize> = store.list().iter().map(|c| c.turn).collect();
+    assert_eq!(turns, vec![1, 2]);
+
+    // Undo only takes back the second iteration's write
+    let (turn, restored) = store.undo().unwrap();
+    assert_eq!((turn, restored.len()), (2, 1));
+    assert!(!tmp.path().join("b.txt").exists());
+    assert_eq!(std::fs::read_to_string(tmp.path().join("a.txt")).unwrap(), "first");
+}
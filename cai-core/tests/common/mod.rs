@@ -0,0 +1,184 @@
+//! Shared helpers for the integration tests.
+
+#![allow(dead_code)]
+
+use std::sync::{ Arc, Mutex };
+
+use cai_core::{
+    approval::{ ApprovalDecision, ApprovalRequest },
+    client::Client,
+    history_manager::History,
+    memory_manager::MemoryManager,
+    models::ModelSet,
+    provider::Provider,
+    ui_trait::{ MsgRole, MsgType, UIBase },
+    workspace::Workspace,
+};
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+/// UI that records everything printed and answers input and approval
+/// prompts from a script (approving once when the script is empty).
+#[derive(Default)]
+pub struct TestUI {
+    pub printed: Mutex<Vec<String>>,
+    pub inputs: Mutex<Vec<String>>,
+    pub decisions: Mutex<Vec<ApprovalDecision>>,
+    pub asked: Mutex<Vec<ApprovalRequest>>,
+}
+
+impl TestUI {
+    pub fn printed(&self) -> Vec<String> {
+        self.printed.lock().unwrap().clone()
+    }
+
+    /// Summaries of every approval request, in order.
+    pub fn asked(&self) -> Vec<String> {
+        self.asked.lock().unwrap().iter().map(|r| r.summary.clone()).collect()
+    }
+
+    pub fn with_decisions(decisions: Vec<ApprovalDecision>) -> Self {
+        Self { decisions: Mutex::new(decisions), ..Default::default() }
+    }
+}
+
+impl UIBase for TestUI {
+    fn init(&self) -> bool {
+        true
+    }
+
+    fn get_user_input(&self) -> String {
+        let mut inputs = self.inputs.lock().unwrap();
+        if inputs.is_empty() { "q".to_string() } else { inputs.remove(0) }
+    }
+
+    fn print_message(&self, _role: MsgRole, message: MsgType) {
+        let text = match message {
+            MsgType::Plain(text) | MsgType::Diff(text) => text,
+            MsgType::TitleChildren { title, content } => format!("{}\n{}", title, content.join("\n")),
+        };
+        self.printed.lock().unwrap().push(text);
+    }
+
+    fn request_approval(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        self.asked.lock().unwrap().push(request.clone());
+        let mut decisions = self.decisions.lock().unwrap();
+        if decisions.is_empty() { ApprovalDecision::ApproveOnce } else { decisions.remove(0) }
+    }
+}
+
+/// A client whose history, memory and workspace live in a fresh temp dir.
+/// A single-model endpoint is used so no model-choosing request is made.
+pub fn test_client(provider: Arc<dyn Provider>) -> (Client, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.json").to_str().unwrap(), 28, 9);
+    let memory = MemoryManager::new(dir.path().join("memory.json").to_str().unwrap());
+
+    let mut client = Client::with_storage(provider, history, memory);
+    client.set_models(ModelSet::single("test-model"));
+    client.attach_workspace(Workspace::new(dir.path()).unwrap());
+    (client, dir)
+}
+
+/// A request captured by `MockServer`.
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+}
+
+impl CapturedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Minimal HTTP/1.1 server answering each request with the next scripted
+/// `(content_type, body)` pair, and recording what it received.
+pub struct MockServer {
+    pub base_url: String,
+    pub requests: Arc<Mutex<Vec<CapturedRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<(&'static str, String)>) -> Self {
+        let responses = responses.into_iter().map(|(content_type, body)| (content_type, vec![body.into_bytes()]));
+        Self::start_in_parts(responses.collect()).await
+    }
+
+    /// Like `start`, but sends each body in the given parts, pausing in
+    /// between so the client reads them as separate chunks.
+    pub async fn start_in_parts(responses: Vec<(&'static str, Vec<Vec<u8>>)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let captured = requests.clone();
+        tokio::spawn(async move {
+            for (content_type, parts) in responses {
+                let Ok((mut socket, _)) = listener.accept().await else { return };
+                let request = read_request(&mut socket).await;
+                captured.lock().unwrap().push(request);
+
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    content_type,
+                    parts.iter().map(Vec::len).sum::<usize>()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                for (n, part) in parts.iter().enumerate() {
+                    if n > 0 {
+                        tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+                    }
+                    let _ = socket.write_all(part).await;
+                    let _ = socket.flush().await;
+                }
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        Self { base_url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> CapturedRequest {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // Headers
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.unwrap();
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines.next().unwrap_or("").split_whitespace().nth(1).unwrap_or("").to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    // Body
+    let length: usize = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + length {
+        let n = socket.read(&mut chunk).await.unwrap();
+        if n == 0 { break; }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = serde_json::from_slice(&buf[header_end..]).unwrap_or(Value::Null);
+
+    CapturedRequest { path, headers, body }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use cai_core::{ config::Config, provider::ProviderKind };
+
+fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |key| vars.get(key).cloned()
+}
+
+#[test]
+fn layers_override_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let user = dir.path().join("config.toml");
+    let project = dir.path().join(".cai.toml");
+    std::fs::write(&user, "language = \"English\"\nmax_history = 40\nmax_continue = 3\n\n[provider]\nkind = \"anthropic\"\n").unwrap();
+    std::fs::write(&project, "max_history = 10\n").unwrap();
+
+    let env = env_of(&[("CAI_MAX_CONTINUE", "7"), ("ANTHROPIC_API_KEY", "sk-test"), ("OPENAI_API_KEY", "ignored")]);
+    let config = Config::load_from(Some(&user), Some(&project), &env).unwrap();
+
+    assert_eq!(config.language, "English");
+    assert_eq!(config.max_history, 10);
+    assert_eq!(config.summary_size(), 3);
+    assert_eq!(config.max_continue, 7);
+    assert_eq!(config.provider.kind, ProviderKind::Anthropic);
+    assert_eq!(config.provider.api_key.as_deref(), Some("sk-test"));
+    assert_eq!(config.sources, vec![user, project]);
+    assert!(!config.to_toml().contains("sk-test"));
+}
+
+#[test]
+fn missing_files_fall_back_to_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::load_from(Some(&dir.path().join("nope.toml")), None, &env_of(&[])).unwrap();
+    let defaults = Config::default();
+
+    assert_eq!(config.max_history, defaults.max_history);
+    assert_eq!(config.language, defaults.language);
+    assert!(config.sources.is_empty());
+}
+
+#[test]
+fn malformed_file_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join(".cai.toml");
+    std::fs::write(&project, "max_history = \"lots\"\n").unwrap();
+
+    assert!(Config::load_from(None, Some(&project), &env_of(&[])).is_err());
+}
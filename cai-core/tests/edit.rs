@@ -0,0 +1,108 @@
+mod common;
+
+use std::{ sync::Arc, time::{ Duration, Instant } };
+
+use cai_core::{
+    edit::{ apply_edits, parse_unified_diff, Edit },
+    provider::MockProvider,
+    tools::{ EditFile, Tool },
+    types::MessageRole,
+    workspace::Workspace,
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+const SOURCE: &str = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n\nfn helper() {\n    let x = 1;\n}\n";
+
+#[test]
+fn search_replace_matches_exactly_then_fuzzily() {
+    let edited = apply_edits(SOURCE, &[Edit::new("println!(\"{}\", x);", "println!(\"x = {}\", x);")]).unwrap();
+    assert!(edited.contains("println!(\"x = {}\", x);"));
+
+    // Indentation and trailing whitespace differ from the file
+    let edited = apply_edits(SOURCE, &[Edit::new("fn helper() {\n  let x = 1;  \n}", "fn helper() {}")]).unwrap();
+    assert!(edited.ends_with("\nfn helper() {}\n"), "{}", edited);
+
+    // A slightly wrong search still finds its block
+    let edited = apply_edits(SOURCE, &[Edit::new("fn main() {\n    let x = 1;\n    println!(\"{}\", y);", "fn main() {")]).unwrap();
+    assert!(edited.starts_with("fn main() {\n}\n"), "{}", edited);
+}
+
+#[test]
+fn unmatched_and_ambiguous_edits_fail_clearly() {
+    let err = apply_edits(SOURCE, &[Edit::new("    let x = 1;\n", "    let x = 2;\n")]).unwrap_err().to_string();
+    assert!(err.contains("edit 1 of 1") && err.contains("lines 2, 7"), "{}", err);
+
+    let err = apply_edits(SOURCE, &[Edit::new("struct Config {\n    name: String,\n}", "")]).unwrap_err().to_string();
+    assert!(err.contains("not found; closest is lines"), "{}", err);
+}
+
+#[test]
+fn fuzzy_search_stays_fast_on_large_files() {
+    let source: String = (0..20_000).map(|i| format!("    let value_{} = compute({}, \"{}\");\n", i, i * 7, i % 13)).collect();
+    let search: String = (0..40).map(|i| format!("    let other_{} = measure({});\n", i, i)).collect();
+
+    let started = Instant::now();
+    let err = apply_edits(&source, &[Edit::new(search, "")]).unwrap_err().to_string();
+    assert!(err.contains("not found"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(3), "{:?}", started.elapsed());
+}
+
+#[test]
+fn unified_diff_hunks_use_line_numbers_to_disambiguate() {
+    let diff = "--- a/main.rs\n+++ b/main.rs\n@@ -6,3 +6,3 @@\n fn helper() {\n-    let x = 1;\n+    let x = 2;\n }\n";
+    let edits = parse_unified_diff(diff).unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].line_hint, Some(6));
+
+    let edited = apply_edits(SOURCE, &edits).unwrap();
+    assert!(edited.contains("fn main() {\n    let x = 1;"));
+    assert!(edited.contains("fn helper() {\n    let x = 2;"));
+
+    // Stale line number, no helper context: the nearest match wins
+    let diff = "@@ -8,1 +8,1 @@\n-    let x = 1;\n+    let x = 3;\n";
+    let edited = apply_edits(SOURCE, &parse_unified_diff(diff).unwrap()).unwrap();
+    assert!(edited.contains("fn helper() {\n    let x = 3;"));
+
+    assert!(parse_unified_diff("just some text").is_err());
+}
+
+#[tokio::test]
+async fn edit_file_tool_returns_and_shows_the_diff() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("edit_file", json!({
+                "path": "main.rs",
+                "edits": [{ "search": "let x = 1;\n    println", "replace": "let x = 5;\n    println" }]
+            }))
+            .reply("done")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    std::fs::write(tmp.path().join("main.rs"), SOURCE).unwrap();
+    let ui = TestUI::default();
+
+    client.send_message(&ui, MessageRole::User, "set x to 5", None).await;
+
+    let edited = std::fs::read_to_string(tmp.path().join("main.rs")).unwrap();
+    assert!(edited.contains("let x = 5;") && edited.contains("fn helper() {\n    let x = 1;"));
+    let result = mock.requests()[1].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(result.starts_with("[edit_file] OK\n--- a/main.rs"), "{}", result);
+    assert!(result.contains("-    let x = 1;\n+    let x = 5;"));
+    assert!(ui.printed().iter().any(|p| p.starts_with("--- a/main.rs")));
+}
+
+#[tokio::test]
+async fn edit_file_tool_refuses_edits_and_diff_together() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("main.rs"), SOURCE).unwrap();
+    let tool = EditFile::new(Arc::new(Workspace::new(dir.path()).unwrap()));
+
+    let err = tool.invoke(json!({
+        "path": "main.rs",
+        "edits": [{ "search": "let x = 1;\n    println", "replace": "let x = 5;\n    println" }],
+        "diff": "@@ -7,1 +7,1 @@\n-    let x = 1;\n+    let x = 9;\n"
+    })).await.unwrap_err();
+
+    assert!(err.to_string().contains("not both"), "{}", err);
+    assert_eq!(std::fs::read_to_string(dir.path().join("main.rs")).unwrap(), SOURCE);
+}
@@ -0,0 +1,65 @@
+use cai_core::{
+    export::{ from_jsonl, plain_code_html, to_html, to_jsonl, to_markdown, ExportFormat },
+    types::{ ChatMessage, MessageRole, ToolCall },
+};
+
+fn conversation() -> Vec<ChatMessage> {
+    let call = ToolCall { id: "call_1".into(), name: "read_file".into(), arguments: r#"{"path":"a.rs"}"#.into() };
+    vec![
+        ChatMessage::new(MessageRole::System, "You are cai."),
+        ChatMessage::new(MessageRole::System, "[Conversation summary]\nWe set up the project."),
+        ChatMessage::new(MessageRole::User, "what's in a.rs? <b>"),
+        ChatMessage::tool_calls("", vec![call]),
+        ChatMessage::tool_result("call_1", "fn main() {}\n```inner```"),
+        ChatMessage::new(MessageRole::Assistant, "It has a main:\n```rust\nfn main() {}\n```\nThat's all."),
+    ]
+}
+
+#[test]
+fn markdown_shows_roles_tools_and_summaries() {
+    let md = to_markdown(&conversation(), None);
+
+    assert!(md.starts_with("# Conversation\n\n<details>\n<summary>System prompt</summary>"), "{}", md);
+    assert!(md.contains("## Summary of earlier messages\n\nWe set up the project."));
+    assert!(md.contains("## User\n\nwhat's in a.rs? <b>"));
+    assert!(md.contains("**Tool call** `read_file` (call_1)\n\n```json\n{\n  \"path\": \"a.rs\"\n}\n```"));
+    // The result holds a ``` run, so it gets a longer fence
+    assert!(md.contains("**Tool result** (call_1)\n\n````text\nfn main() {}\n```inner```\n````"), "{}", md);
+    assert!(md.contains("## Assistant\n\nIt has a main:\n```rust"));
+}
+
+#[test]
+fn html_is_escaped_and_code_goes_through_the_highlighter() {
+    let highlight = |lang: &str, code: &str| format!("<pre data-lang=\"{}\">{}</pre>", lang, code.len());
+    let html = to_html(&conversation(), None, &highlight);
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("what's in a.rs? &lt;b&gt;"));
+    assert!(html.contains("<pre data-lang=\"rust\">13</pre>"), "{}", html);
+    assert!(html.contains("<div class=\"text\">That's all.</div>"));
+    assert!(html.contains("Tool call <code>read_file</code>"));
+
+    assert_eq!(plain_code_html("rs", "a < b"), "<pre><code>a &lt; b</code></pre>");
+}
+
+#[test]
+fn jsonl_round_trips_and_reports_bad_lines() {
+    let jsonl = to_jsonl(&conversation());
+    assert_eq!(jsonl.lines().count(), 1);
+
+    let imported = from_jsonl(&format!("{}\n{}", jsonl, jsonl)).unwrap();
+    assert_eq!(imported.len(), 2);
+    let messages = &imported[0];
+    assert_eq!(messages.len(), 6);
+    assert_eq!(messages[3].tool_calls[0].name, "read_file");
+    assert_eq!(messages[4].tool_call_id.as_deref(), Some("call_1"));
+    assert_eq!(messages[5].content, conversation()[5].content);
+
+    let parts = r#"{"messages": [{"role": "user", "content": [{"type": "text", "text": "hi"}]}]}"#;
+    assert_eq!(from_jsonl(parts).unwrap()[0][0].content, "hi");
+
+    let err = from_jsonl(&format!("{}\n{{\"messages\": [{{\"role\": \"robot\"}}]}}", jsonl.trim_end())).unwrap_err();
+    assert!(format!("{:#}", err).contains("line 2: unknown role `robot`"), "{:#}", err);
+
+    assert_eq!(ExportFormat::from_path("out/chat.HTML".as_ref()), Some(ExportFormat::Html));
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use cai_core::{
+    tools::{ files::{ MAX_OUTPUT_BYTES, MAX_READ_LINES }, GlobFiles, Grep, ListDir, ReadFile, Tool },
+    workspace::Workspace,
+};
+use serde_json::json;
+
+/// src/{main.rs, lib.rs, big.txt}, docs/guide.md, an ignored target/ dir.
+fn sample_workspace() -> (Arc<Workspace>, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::create_dir_all(root.join("docs")).unwrap();
+    std::fs::create_dir_all(root.join("target/debug")).unwrap();
+    std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+    std::fs::write(root.join("src/main.rs"), "fn main() {\n    println!(\"Hello\");\n}\n").unwrap();
+    std::fs::write(root.join("src/lib.rs"), "pub fn hello() {}\n").unwrap();
+    std::fs::write(root.join("docs/guide.md"), "# Guide\nSay hello.\n").unwrap();
+    std::fs::write(root.join("target/debug/out.rs"), "fn hello() {}\n").unwrap();
+    let big: String = (1..=1000).map(|n| format!("line {}\n", n)).collect();
+    std::fs::write(root.join("src/big.txt"), big).unwrap();
+    (Arc::new(Workspace::new(root).unwrap()), dir)
+}
+
+#[tokio::test]
+async fn read_file_returns_numbered_ranges_in_chunks() {
+    let (workspace, _dir) = sample_workspace();
+    let tool = ReadFile::new(workspace);
+
+    let out = tool.invoke(json!({ "path": "src/main.rs", "start_line": 2, "end_line": 2 })).await.unwrap();
+    assert_eq!(out, "    2 |     println!(\"Hello\");\n");
+
+    let out = tool.invoke(json!({ "path": "src/big.txt" })).await.unwrap();
+    assert_eq!(out.lines().filter(|l| l.contains(" | ")).count(), MAX_READ_LINES);
+    assert!(out.contains(&format!("pass start_line={}", MAX_READ_LINES + 1)), "{}", out);
+
+    assert!(tool.invoke(json!({ "path": "../etc/passwd" })).await.is_err());
+}
+
+#[tokio::test]
+async fn read_file_reads_on_after_the_byte_cap() {
+    let (workspace, dir) = sample_workspace();
+    let tool = ReadFile::new(workspace);
+    // 100 lines of 1000 bytes, then one far longer than the cap
+    let mut wide: String = (1..=100).map(|n| format!("{:0>1000}\n", n)).collect();
+    wide.push_str(&"x".repeat(MAX_OUTPUT_BYTES * 2));
+    std::fs::write(dir.path().join("wide.txt"), wide).unwrap();
+
+    let out = tool.invoke(json!({ "path": "wide.txt" })).await.unwrap();
+    let shown = out.lines().filter(|l| l.contains(" | ")).count();
+    assert!(shown < 100);
+    assert!(out.ends_with(&format!("[lines 1-{} of 101 -- pass start_line={} to read on]\n", shown, shown + 1)), "{}", out);
+
+    // Long lines are clipped, so every call gets somewhere
+    let out = tool.invoke(json!({ "path": "wide.txt", "start_line": 101 })).await.unwrap();
+    assert!(out.starts_with("  101 | xxx") && out.trim_end().ends_with('…'), "{}", &out[..40]);
+    assert!(out.len() < MAX_OUTPUT_BYTES);
+}
+
+#[tokio::test]
+async fn list_dir_respects_depth_and_gitignore() {
+    let (workspace, _dir) = sample_workspace();
+    let tool = ListDir::new(workspace);
+
+    let out = tool.invoke(json!({ "depth": 1 })).await.unwrap();
+    assert_eq!(out, "docs/\nsrc/\n");
+
+    let out = tool.invoke(json!({})).await.unwrap();
+    assert!(out.contains("src/\n  big.txt\n  lib.rs\n  main.rs\n"), "{}", out);
+    assert!(!out.contains("target"));
+}
+
+#[tokio::test]
+async fn glob_and_grep_search_the_workspace() {
+    let (workspace, _dir) = sample_workspace();
+
+    let out = GlobFiles::new(workspace.clone()).invoke(json!({ "pattern": "**/*.rs" })).await.unwrap();
+    assert_eq!(out, "src/lib.rs\nsrc/main.rs\n");
+
+    let grep = Grep::new(workspace);
+    let out = grep.invoke(json!({ "pattern": "hello", "case_insensitive": true })).await.unwrap();
+    assert_eq!(out, "docs/guide.md:2: Say hello.\nsrc/lib.rs:1: pub fn hello() {}\nsrc/main.rs:2: println!(\"Hello\");\n");
+
+    let out = grep.invoke(json!({ "pattern": "hello", "glob": "*.md" })).await.unwrap();
+    assert_eq!(out, "docs/guide.md:2: Say hello.\n");
+
+    let out = grep.invoke(json!({ "pattern": "line \\d+$", "path": "src" })).await.unwrap();
+    assert!(out.ends_with("[truncated -- narrow the pattern, path or glob]\n"), "{}", out);
+}
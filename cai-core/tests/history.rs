@@ -0,0 +1,193 @@
+use cai_core::{
+    history_manager::{ History, COMPACT_TOOL_OUTPUT_CHARS },
+    tokens::{ context_window, TokenBudget, Tokenizer },
+    types::{ ChatMessage, MessageRole, ToolCall },
+};
+
+#[test]
+fn tokenizers_and_windows_follow_the_model() {
+    assert!(Tokenizer::for_model("gpt-4o-mini").is_exact());
+    assert!(Tokenizer::for_model("openai/gpt-4.1").is_exact());
+    assert!(!Tokenizer::for_model("claude-sonnet-4-5").is_exact());
+    assert_eq!(Tokenizer::Estimate.count("12345678"), 2);
+    assert_eq!(Tokenizer::for_model("gpt-4o").count("hello world"), 2);
+
+    assert_eq!(context_window("gpt-4o-2024-08-06"), 128_000);
+    assert_eq!(context_window("gpt-4-0613"), 8_192);
+    assert_eq!(context_window("claude-opus-4-1"), 200_000);
+    assert_eq!(context_window("llama3.1:8b"), 32_768);
+    assert_eq!(TokenBudget::new("gpt-4o", Some(1000), 0.5).max_tokens, 500);
+}
+
+#[test]
+fn summaries_follow_the_token_budget_not_the_message_count() {
+    let mut history = History::ephemeral(200, 2);
+    history.set_budget(TokenBudget::new("claude", Some(1000), 0.6));
+    history.add_message(MessageRole::System, "system prompt".into());
+    for i in 0..40 {
+        history.add_message(MessageRole::User, format!("short {}", i));
+    }
+    assert!(!history.needs_summarize());
+
+    // One pasted log blows the budget on its own
+    history.add_message(MessageRole::User, "log line\n".repeat(200));
+    history.add_message(MessageRole::Assistant, "ok".into());
+    assert!(history.needs_summarize());
+
+    // Everything before the log's turn is folded; the log and its reply stay
+    let prompt = history.get_summarize_prompt();
+    assert!(prompt.contains("short 0") && prompt.contains("short 39") && !prompt.contains("log line"));
+    history.insert_summary("[summary]".into());
+    assert_eq!(history.get().len(), 4);
+    assert!(!history.needs_summarize());
+}
+
+#[test]
+fn summaries_never_split_a_command_from_its_output() {
+    let mut history = History::ephemeral(5, 2);
+    let turns = [
+        (MessageRole::System, "system prompt"),
+        (MessageRole::User, "list files"),
+        (MessageRole::Assistant, "```bash\nls\n```"),
+        (MessageRole::System, "[output] a.txt"),
+        (MessageRole::Assistant, "```bash\ncat a.txt\n```"),
+        (MessageRole::System, "[output] hello"),
+        (MessageRole::User, "thanks"),
+        (MessageRole::Assistant, "you're welcome"),
+    ];
+    for (role, content) in turns.clone() {
+        history.add_message(role, content.into());
+    }
+    assert!(history.needs_summarize());
+
+    let prompt = history.get_summarize_prompt();
+    assert!(prompt.contains("[output] hello"), "{}", prompt);
+
+    // Nothing is cut until the summary comes back
+    assert_eq!(history.get().len(), turns.len());
+    history.insert_summary("[summary]".into());
+    let left: Vec<String> = history.get().into_iter().map(|m| m.content).collect();
+    assert_eq!(left, ["system prompt", "[summary]", "thanks", "you're welcome"]);
+
+    // One long turn: cut at a reply, keeping each command with its output
+    let mut history = History::ephemeral(4, 2);
+    for (role, content) in &turns[..6] {
+        history.add_message(role.clone(), content.to_string());
+    }
+    let prompt = history.get_summarize_prompt();
+    assert!(prompt.contains("[output] a.txt") && !prompt.contains("cat a.txt"), "{}", prompt);
+    history.insert_summary("[summary]".into());
+    let left: Vec<String> = history.get().into_iter().map(|m| m.content).collect();
+    assert_eq!(left, ["system prompt", "[summary]", "```bash\ncat a.txt\n```", "[output] hello"]);
+}
+
+fn contents(history: &History) -> Vec<String> {
+    history.get().into_iter().map(|m| m.content).collect()
+}
+
+#[test]
+fn edits_and_retries_branch_without_losing_the_original() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("history.json");
+    let mut history = History::new(file.to_str().unwrap(), 200, 10);
+    history.add_message(MessageRole::System, "system prompt".into());
+    history.add_message(MessageRole::User, "write a parser".into()); // #1
+    history.add_message(MessageRole::Assistant, "regex parser".into()); // #2
+
+    history.retry_last_reply().unwrap();
+    history.add_message(MessageRole::Assistant, "nom parser".into()); // #3
+    assert_eq!(contents(&history), ["system prompt", "write a parser", "nom parser"]);
+
+    history.edit(1, "write a lexer".into()).unwrap(); // #4
+    history.add_message(MessageRole::Assistant, "logos lexer".into()); // #5
+    assert_eq!(contents(&history), ["system prompt", "write a lexer", "logos lexer"]);
+    assert!(history.edit(2, "not a user message".into()).is_err());
+
+    history.switch(1).unwrap();
+    assert_eq!(history.cursor(), Some(3));
+    history.checkout(2).unwrap();
+    assert_eq!(contents(&history), ["system prompt", "write a parser", "regex parser"]);
+    assert!(history.checkout(42).is_err());
+
+    let tree = history.render_tree();
+    assert_eq!(tree.lines().count(), 6);
+    assert!(tree.starts_with("* #0 System: system prompt\n  * #1 User: write a parser\n"), "{}", tree);
+    assert!(tree.contains("\n    * #2 Assistant: regex parser\n      #3 Assistant: nom parser\n    #4 User"), "{}", tree);
+
+    // Saved as a tree and loaded back with the same cursor
+    history.save();
+    let loaded = History::new(file.to_str().unwrap(), 200, 10);
+    assert_eq!(loaded.cursor(), Some(2));
+    assert_eq!(loaded.render_tree(), tree);
+}
+
+#[test]
+fn linear_history_files_still_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("history.json");
+    std::fs::write(&file, r#"[
+        { "role": "system", "content": "system prompt" },
+        { "role": "user", "content": "hi" },
+        { "role": "assistant", "content": "hello" }
+    ]"#).unwrap();
+
+    let mut history = History::new(file.to_str().unwrap(), 200, 10);
+    assert_eq!(contents(&history), ["system prompt", "hi", "hello"]);
+    assert_eq!(history.path(), [0, 1, 2]);
+
+    // Summaries cut the current branch only; the others keep their messages
+    let mut history_b = History::ephemeral(3, 1);
+    for (role, content) in [
+        (MessageRole::System, "system prompt"),
+        (MessageRole::User, "one"),
+        (MessageRole::Assistant, "reply one"),
+        (MessageRole::User, "two"),
+        (MessageRole::Assistant, "reply two"),
+    ] {
+        history_b.add_message(role, content.into());
+    }
+    history_b.edit(3, "two, edited".into()).unwrap();
+    assert!(history_b.get_summarize_prompt().contains("reply one"));
+    history_b.insert_summary("[summary]".into());
+    assert_eq!(contents(&history_b), ["system prompt", "[summary]", "two, edited"]);
+    history_b.switch(3).unwrap();
+    assert_eq!(contents(&history_b), ["system prompt", "one", "reply one", "two", "reply two"]);
+
+    history.clear();
+    assert_eq!(contents(&history), ["system prompt"]);
+}
+
+#[test]
+fn tool_exchanges_stay_whole_and_old_outputs_are_compacted() {
+    let mut history = History::ephemeral(6, 1);
+    let call = |id: &str| ToolCall { id: id.into(), name: "read_file".into(), arguments: "{}".into() };
+    history.add_message(MessageRole::System, "system prompt".into());
+    history.add_message(MessageRole::User, "read a".into());
+    history.push(ChatMessage::tool_calls("", vec![call("a")]));
+    history.push(ChatMessage::tool_result("a", "a".repeat(10_000)));
+    history.add_message(MessageRole::Assistant, "a is long".into());
+    history.add_message(MessageRole::User, "read b".into());
+    history.push(ChatMessage::tool_calls("", vec![call("b")]));
+    history.push(ChatMessage::tool_result("b", "b".repeat(10_000)));
+    history.add_message(MessageRole::Assistant, "b is long".into());
+    history.add_message(MessageRole::User, "read c".into());
+    history.push(ChatMessage::tool_calls("", vec![call("c")]));
+    history.push(ChatMessage::tool_result("c", "c".repeat(10_000)));
+
+    // Summarizing one message would split the first exchange: all of it goes
+    let prompt = history.get_summarize_prompt();
+    assert!(prompt.contains("[called read_file({})]") && prompt.contains("characters cut"));
+    assert!(prompt.len() < 3 * COMPACT_TOOL_OUTPUT_CHARS, "{}", prompt.len());
+
+    // Of the rest, only the latest turn keeps its full output
+    history.insert_summary("[summary]".into());
+    let left = history.get();
+    assert_eq!(left[2].content, "read b");
+    assert!(left[4].content.len() < COMPACT_TOOL_OUTPUT_CHARS + 100);
+    assert_eq!(left.last().unwrap().content.len(), 10_000);
+
+    // Regenerating goes back before the tool calls of the latest reply
+    history.add_message(MessageRole::Assistant, "c is long".into());
+    history.retry_last_reply().unwrap();
+    assert_eq!(history.get().last().unwrap().content, "read c");
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::sync::Arc;
+
+use cai_core::{
+    agent_loop::start_session,
+    memory_manager::{ MemoryChange, MemoryManager, DEFAULT_IMPORTANCE, PROMPT_MEMORIES },
+    provider::MockProvider,
+    types::{ MessageRole, ToolCall },
+};
+use common::{ test_client, TestUI };
+use serde_json::{ json, Value };
+
+fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+}
+
+#[test]
+fn entries_have_stable_ids_and_metadata() {
+    for name in ["memory.json", "memory.db"] {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name).to_string_lossy().into_owned();
+        let mut memory = MemoryManager::new(&path);
+        memory.set_source("refactor");
+
+        let first = memory.add("  prefers tabs ", &tags(&["Style", "#style", " rust "]), Some(0.9)).unwrap();
+        let second = memory.add("works on cai", &[], None).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(first.content, "prefers tabs");
+        assert_eq!(first.tags, ["style", "rust"]);
+        assert_eq!(first.source.as_deref(), Some("refactor"));
+        assert_eq!(second.importance, DEFAULT_IMPORTANCE);
+        assert!(first.created > 0 && first.updated == first.created);
+
+        let updated = memory.update(1, MemoryChange { tags: Some(tags(&["editor"])), ..Default::default() }).unwrap();
+        assert_eq!((updated.content.as_str(), updated.tags.clone()), ("prefers tabs", tags(&["editor"])));
+        assert_eq!(updated.created, first.created);
+
+        // Ids aren't reused after a delete
+        memory.delete(2).unwrap();
+        assert_eq!(memory.add("third", &[], None).unwrap().id, 3, "{}", name);
+
+        let reloaded = MemoryManager::new(&path);
+        assert_eq!(reloaded.view(), "#1 [editor] prefers tabs\n#3 third");
+        assert_eq!(reloaded.get(1).unwrap().importance, 0.9);
+        assert_eq!(reloaded.get(1).unwrap().source.as_deref(), Some("refactor"));
+    }
+}
+
+#[test]
+fn bad_input_is_an_error_not_a_panic() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut memory = MemoryManager::new(&dir.path().join("memory.json").to_string_lossy());
+    memory.add("likes Rust", &tags(&["lang"]), None).unwrap();
+
+    assert!(format!("{:#}", memory.find("(unclosed").unwrap_err()).contains("invalid pattern"));
+    assert_eq!(memory.find("rust").unwrap().len(), 1);
+    assert_eq!(memory.find("^lang$").unwrap().len(), 1);
+    assert!(memory.update(7, MemoryChange::default()).is_err());
+    assert!(memory.delete(7).is_err());
+    assert!(memory.add("   ", &[], None).is_err());
+    assert!(memory.add("x", &[], Some(1.5)).is_err());
+    assert!(memory.update(1, MemoryChange { importance: Some(-0.1), ..Default::default() }).is_err());
+    assert_eq!(memory.entries().len(), 1);
+}
+
+#[test]
+fn plain_text_memory_is_migrated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("memory.json");
+    std::fs::write(&path, "user name is John\n\nlikes short answers\n").unwrap();
+
+    let mut memory = MemoryManager::new(&path.to_string_lossy());
+    assert_eq!(memory.view(), "#1 user name is John\n#2 likes short answers");
+    assert!(memory.entries().iter().all(|entry| entry.source.is_none() && entry.created > 0));
+
+    // The first change rewrites the file as JSON
+    memory.add("uses fish", &[], None).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.trim_start().starts_with('{'), "{}", text);
+    assert_eq!(MemoryManager::new(&path.to_string_lossy()).entries().len(), 3);
+
+    // A broken JSON file isn't mistaken for plain text
+    std::fs::write(&path, "{ not json").unwrap();
+    let mut broken = MemoryManager::new(&path.to_string_lossy());
+    assert!(broken.is_empty());
+    assert!(broken.add("x", &[], None).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ not json");
+}
+
+#[tokio::test]
+async fn memory_tools_share_the_clients_memory() {
+    let (client, _tmp) = test_client(Arc::new(MockProvider::default()));
+    let call = |name: &str, args: Value| ToolCall { id: "1".into(), name: name.into(), arguments: args.to_string() };
+
+    let added = client.tools.invoke(&call("memory_add", json!({ "content": "prefers tabs", "tags": ["Style"] }))).await;
+    assert_eq!(added, "[memory_add] saved #1 [style] prefers tabs (importance 0.5)");
+    client.tools.invoke(&call("memory_add", json!({ "content": "deploys on fridays", "importance": 0.9 }))).await;
+
+    let found = client.tools.invoke(&call("memory_search", json!({ "query": "TABS", "tag": "#style" }))).await;
+    assert_eq!(found, "#1 [style] prefers tabs (importance 0.5)");
+    let none = client.tools.invoke(&call("memory_search", json!({ "query": "tabs fridays" }))).await;
+    assert_eq!(none, "[memory_search] no matches");
+
+    // Ids may come as "#2" strings too
+    client.tools.invoke(&call("memory_update", json!({ "id": "#2", "content": "never deploys on fridays" }))).await;
+    client.tools.invoke(&call("memory_delete", json!({ "id": 1 }))).await;
+    let view = client.tools.invoke(&call("memory_view", json!({}))).await;
+    assert_eq!(view, "#2 never deploys on fridays (importance 0.9)");
+    assert_eq!(client.memory.lock().unwrap().view(), "#2 never deploys on fridays");
+
+    let err = client.tools.invoke(&call("memory_delete", json!({ "id": 1 }))).await;
+    assert_eq!(err, "[memory_delete] ERROR: no memory #1");
+    let err = client.tools.invoke(&call("memory_add", json!({ "content": "x", "importance": 2 }))).await;
+    assert!(err.starts_with("[memory_add] ERROR: importance"), "{}", err);
+}
+
+#[tokio::test]
+async fn relevant_memories_open_the_session() {
+    let mock = Arc::new(MockProvider::default().reply("hi").reply("welcome back"));
+    let (mut client, _tmp) = test_client(mock.clone());
+    {
+        let mut memory = client.memory.lock().unwrap();
+        for i in 0..PROMPT_MEMORIES {
+            memory.add(&format!("trivia {}", i), &[], Some(0.1)).unwrap();
+        }
+        memory.add("the user is called Sam", &[], Some(1.0)).unwrap();
+        memory.add("tests run with cargo nextest", &tags(&["cai"]), Some(0.1)).unwrap();
+    }
+
+    start_session(&TestUI::default(), &mut client, "/work/cai").await;
+    let prompt = mock.requests()[0].messages[0]["content"].as_str().unwrap().to_string();
+    let block = prompt.split("## Memory\n").nth(1).unwrap();
+    let lines: Vec<&str> = block.lines().collect();
+    assert_eq!(lines[0], "#21 the user is called Sam");
+    assert_eq!(lines[1], "#22 [cai] tests run with cargo nextest");
+    assert!(block.contains("(2 more -- use memory_search)"), "{}", block);
+
+    // Resuming repeats the current memories
+    client.memory.lock().unwrap().delete(21).unwrap();
+    client.history.add_message(MessageRole::Assistant, "hi".into());
+    start_session(&TestUI::default(), &mut client, "/work/cai").await;
+    let resume = mock.requests()[1].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(resume.contains("## Memory\n#22 [cai] tests run with cargo nextest"), "{}", resume);
+    assert!(!resume.contains("Sam"));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use std::sync::Arc;
+
+use cai_core::{
+    agent_loop::run_once,
+    constants::CONTINUE_TOKEN,
+    history_manager::History,
+    provider::{ Completion, MockProvider },
+    shell::Shell,
+    types::{ MessageRole, Usage },
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+#[tokio::test]
+async fn one_shot_runs_tools_and_continues_to_the_final_answer() {
+    let mock = MockProvider::default()
+        .tool_call("write_file", json!({ "path": "msg.txt", "content": "fix: typo" }))
+        .reply(&format!("Drafted it. {}", CONTINUE_TOKEN));
+    mock.push(Completion {
+        usage: Some(Usage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 }),
+        ..Completion::text("fix: typo in README")
+    });
+    let mock = Arc::new(mock);
+
+    let (mut client, tmp) = test_client(mock.clone());
+    client.history = History::ephemeral(28, 9);
+    client.attach_shell(Shell::new(tmp.path().to_str().unwrap()).unwrap());
+    let target = tmp.path().join("msg.txt");
+    let ui = TestUI::default();
+
+    let result = run_once(&ui, &mut client, "/work", "write a commit message").await;
+
+    assert_eq!(result.text, "fix: typo in README");
+    assert!(result.error.is_none());
+    assert_eq!(result.tool_calls.len(), 1);
+    assert_eq!(result.tool_calls[0].name, "write_file");
+    assert_eq!(result.usage.total_tokens, 15);
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "fix: typo");
+
+    // System prompt is added locally, not sent on its own
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].messages[0]["role"], "system");
+    assert_eq!(requests[0].messages[1]["content"], "write a commit message");
+    assert!(matches!(client.history.get()[0].role, MessageRole::System));
+
+    // Nothing persisted
+    assert!(!tmp.path().join("history.json").exists());
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["text"], "fix: typo in README");
+    assert_eq!(json["tool_calls"][0]["name"], "write_file");
+    assert!(matches!(client.history.get().last().unwrap().role, MessageRole::Assistant));
+}
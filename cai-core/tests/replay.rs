@@ -0,0 +1,84 @@
+mod common;
+
+use std::{ sync::Arc, time::{ Duration, Instant } };
+
+use cai_core::{
+    provider::{
+        replay::{ self, Transcript },
+        CompletionRequest, MockProvider, Provider, RecordingProvider, ReplayProvider,
+    },
+    types::MessageRole,
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+#[tokio::test]
+async fn recorded_transcript_replays_offline() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = dir.path().join("fixtures/transcript.json");
+    let tool_args = json!({ "path": "notes.txt", "content": "x" });
+
+    // Record a session against the scripted backend
+    let mock = Arc::new(MockProvider::default().tool_call("write_file", tool_args).reply("saved"));
+    let recorder = Arc::new(RecordingProvider::new(mock, &fixture));
+    let (mut client, _tmp) = test_client(recorder);
+    let recorded = client.send_message(&TestUI::default(), MessageRole::User, "save a note", None).await;
+
+    // Replay it strictly: same requests must come in the same order
+    let mut replay = ReplayProvider::load(&fixture).unwrap();
+    replay.strict = true;
+    let replay = Arc::new(replay);
+    let (mut client, _tmp) = test_client(replay.clone());
+    let replayed = client.send_message(&TestUI::default(), MessageRole::User, "save a note", None).await;
+
+    assert_eq!(recorded, "saved");
+    assert_eq!(replayed, recorded);
+    assert_eq!(replay.remaining(), 0);
+}
+
+#[tokio::test]
+async fn strict_replay_flags_diverging_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = dir.path().join("transcript.json");
+
+    let recorder = Arc::new(RecordingProvider::new(Arc::new(MockProvider::default().reply("a")), &fixture));
+    let (mut client, _tmp) = test_client(recorder);
+    client.send_message(&TestUI::default(), MessageRole::User, "first", None).await;
+
+    let mut replay = ReplayProvider::load(&fixture).unwrap();
+    replay.strict = true;
+    let (mut client, _tmp) = test_client(Arc::new(replay));
+    let reply = client.send_message(&TestUI::default(), MessageRole::User, "different", None).await;
+
+    assert_eq!(reply, "[ERROR] Request failed.");
+    assert!(client.last_error.is_some());
+}
+
+#[tokio::test]
+async fn exhausted_transcripts_fail_instead_of_answering() {
+    let replay = ReplayProvider::new(Transcript::default());
+    let err = replay.complete(&CompletionRequest::new("m", Vec::new())).await.unwrap_err();
+    assert!(err.to_string().contains("exhausted"), "{}", err);
+
+    // Given up at once: no back-off for a transcript that can't grow
+    let (mut client, _tmp) = test_client(Arc::new(replay));
+    let started = Instant::now();
+    let reply = client.send_message(&TestUI::default(), MessageRole::User, "hi", None).await;
+    assert_eq!(reply, "[ERROR] Request failed.");
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn unloadable_replay_transcripts_are_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let corrupt = dir.path().join("corrupt.json");
+    std::fs::write(&corrupt, "{ not json").unwrap();
+    let live: Arc<dyn Provider> = Arc::new(MockProvider::default());
+
+    for path in [dir.path().join("missing.json"), corrupt] {
+        std::env::set_var("CAI_REPLAY", &path);
+        let err = replay::from_env(live.clone()).err().unwrap();
+        assert!(format!("{:#}", err).contains("Cannot load transcript"), "{:#}", err);
+    }
+    std::env::remove_var("CAI_REPLAY");
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use cai_core::{
+    history_manager::History,
+    search::{ format_date, index_file, parse_date, search, SearchIndex, SearchQuery },
+    sessions::SessionStore,
+    tools::{ SearchHistory, Tool },
+    types::MessageRole,
+};
+use serde_json::json;
+
+/// A session holding `turns` (user, assistant) after a system prompt,
+/// saved through a history that keeps the index up to date.
+fn session(store: &SessionStore, name: &str, turns: &[(&str, &str)]) -> History {
+    let meta = store.create(Some(name), Path::new("/work"), "gpt-4o").unwrap();
+    let mut history = History::new(&store.history_file(&meta.id).unwrap().to_string_lossy(), 200, 10);
+    history.set_index(index_file(store), &meta.id);
+    history.add_message(MessageRole::System, "You are cai, a terminal assistant.".into());
+    for (user, assistant) in turns {
+        history.add_message(MessageRole::User, user.to_string());
+        history.add_message(MessageRole::Assistant, assistant.to_string());
+    }
+    history.save();
+    history
+}
+
+fn query(text: &str) -> SearchQuery {
+    SearchQuery { text: text.into(), ..Default::default() }
+}
+
+fn found(store: &SessionStore, query: &SearchQuery) -> Vec<(String, usize)> {
+    search(store, query).unwrap().into_iter().map(|hit| (hit.session, hit.node)).collect()
+}
+
+#[test]
+fn hits_are_ranked_filtered_and_highlighted() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::open(dir.path().join("sessions"));
+    session(&store, "config", &[
+        ("How do I parse a TOML config in Rust?", "Use the toml crate: toml::from_str parses TOML into your Config struct."),
+    ]);
+    session(&store, "images", &[
+        ("resize images with python", "Pillow does it. No config needed, and nothing about the assistant."),
+    ]);
+
+    // Both terms, repeated, in a short message beat a single mention
+    let hits = search(&store, &query("toml config")).unwrap();
+    assert_eq!((hits[0].session.as_str(), hits[0].node), ("config", 2));
+    assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    assert_eq!(hits.last().unwrap().session, "images");
+    assert_eq!(hits[1].highlighted("[", "]"), "How do I parse a [TOML] [config] in Rust?");
+
+    // System prompts aren't indexed
+    assert!(found(&store, &query("terminal assistant")).iter().all(|(_, node)| *node != 0));
+
+    let role = SearchQuery { role: Some(MessageRole::User), ..query("config") };
+    assert_eq!(found(&store, &role), vec![("config".to_string(), 1)]);
+    let one = SearchQuery { session: Some("images".into()), ..query("config") };
+    assert_eq!(found(&store, &one), vec![("images".to_string(), 2)]);
+    let future = SearchQuery { since: Some(parse_date("2999-01-01", 0).unwrap()), ..query("config") };
+    assert!(found(&store, &future).is_empty());
+    let limited = SearchQuery { limit: 1, ..query("config") };
+    assert_eq!(found(&store, &limited).len(), 1);
+
+    assert!(search(&store, &SearchQuery { session: Some("nope".into()), ..query("config") }).is_err());
+    assert!(found(&store, &query("  ...  ")).is_empty());
+}
+
+#[test]
+fn index_follows_saves_and_deleted_sessions() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::open(dir.path().join("sessions"));
+    let mut history = session(&store, "edits", &[("rename the parser module", "Done, it is now lexer.rs.")]);
+    session(&store, "other", &[("unrelated", "question")]);
+
+    // An edit starts a branch: both versions are searchable
+    history.edit(1, "rename the tokenizer module".into()).unwrap();
+    history.save();
+    assert_eq!(found(&store, &query("tokenizer")), vec![("edits".to_string(), 3)]);
+    assert_eq!(found(&store, &query("parser")), vec![("edits".to_string(), 1)]);
+
+    // Cleared messages drop out of the index
+    history.clear();
+    assert!(found(&store, &query("tokenizer parser lexer")).is_empty());
+
+    // Sessions the index hasn't seen are indexed on search, deleted ones dropped
+    std::fs::remove_file(index_file(&store)).unwrap();
+    assert_eq!(found(&store, &query("unrelated")), vec![("other".to_string(), 1)]);
+    store.delete("other").unwrap();
+    assert!(found(&store, &query("unrelated")).is_empty());
+    assert!(!SearchIndex::open(index_file(&store)).contains("other"));
+}
+
+#[test]
+fn snippets_cut_at_non_ascii_whitespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::open(dir.path().join("sessions"));
+    let text = format!("{}\u{00A0}{}\u{3000}{} needle", "a".repeat(40), "b".repeat(60), "c".repeat(10));
+    session(&store, "nbsp", &[(&text, "ok")]);
+
+    let hits = search(&store, &query("needle")).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].snippet.starts_with('…') && hits[0].snippet.ends_with("needle"), "{}", hits[0].snippet);
+    assert_eq!(hits[0].highlighted("[", "]").rsplit(' ').next(), Some("[needle]"));
+}
+
+#[tokio::test]
+async fn search_history_tool_reports_hits() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::open(dir.path().join("sessions"));
+    session(&store, "db", &[("which database did we pick?", "We chose PostgreSQL over SQLite for the server.")]);
+
+    let tool = SearchHistory::new(store);
+    let out = tool.invoke(json!({ "query": "postgresql", "role": "assistant", "since": "1d" })).await.unwrap();
+    assert!(out.starts_with("session db #2 Assistant "), "{}", out);
+    assert!(out.contains("We chose **PostgreSQL** over SQLite"));
+
+    let none = tool.invoke(json!({ "query": "postgresql", "role": "user" })).await.unwrap();
+    assert_eq!(none, "[search_history] no matches");
+    assert!(tool.invoke(json!({ "query": "x", "since": "yesterday" })).await.is_err());
+}
+
+#[test]
+fn dates_parse_absolute_and_relative() {
+    assert_eq!(parse_date("1970-01-02", 0).unwrap(), 86_400);
+    assert_eq!(format_date(parse_date("2026-10-18", 0).unwrap()), "2026-10-18");
+    assert_eq!(format_date(parse_date("2024-02-29", 0).unwrap() + 86_399), "2024-02-29");
+    assert_eq!(parse_date("2d", 1_000_000).unwrap(), 1_000_000 - 2 * 86_400);
+    assert_eq!(parse_date("3h", 100).unwrap(), 0);
+    assert!(parse_date("2026-02", 0).is_err());
+    assert!(parse_date("5y", 0).is_err());
+}
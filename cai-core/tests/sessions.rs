@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use cai_core::sessions::SessionStore;
+
+#[test]
+fn sessions_are_created_listed_renamed_and_deleted() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::open(dir.path().join("sessions"));
+    assert!(store.list().unwrap().is_empty());
+
+    let first = store.create(None, Path::new("/work/a"), "gpt-4o").unwrap();
+    let mut second = store.create(None, Path::new("/work/b"), "gpt-4o").unwrap();
+    let named = store.create(Some("refactor"), Path::new("/work/a"), "gpt-4o").unwrap();
+    assert_eq!((first.id.as_str(), second.id.as_str()), ("1", "2"));
+    assert!(store.create(Some("refactor"), Path::new("/work/a"), "gpt-4o").is_err());
+    assert!(store.create(Some("../escape"), Path::new("/work/a"), "gpt-4o").is_err());
+    assert_eq!(store.history_file("refactor").unwrap(), dir.path().join("sessions/refactor/history.json"));
+
+    second.title_from("\n  fix the flaky login test please\nthanks");
+    second.updated += 10;
+    store.touch(&mut second).unwrap();
+    let listed: Vec<String> = store.list().unwrap().into_iter().map(|meta| meta.id).collect();
+    assert_eq!(listed[0], "2");
+    assert_eq!(store.get("2").unwrap().title, "fix the flaky login test please");
+
+    store.rename("refactor", "  Parser refactor ").unwrap();
+    assert_eq!(store.get("refactor").unwrap().title, "Parser refactor");
+    assert_eq!(store.latest_for(Path::new("/work/b")).unwrap().unwrap().id, "2");
+    assert!(store.latest_for(Path::new("/work/c")).unwrap().is_none());
+
+    store.delete(&named.id).unwrap();
+    assert!(store.get("refactor").is_err());
+    assert!(store.delete("refactor").is_err());
+    assert_eq!(store.list().unwrap().len(), 2);
+}
+
+#[test]
+fn ids_cannot_reach_outside_the_sessions_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::open(dir.path().join("sessions"));
+    store.create(Some("inside"), Path::new("/work"), "gpt-4o").unwrap();
+
+    // A directory next to the sessions one that looks like a session
+    let outside = dir.path().join("x");
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::copy(dir.path().join("sessions/inside/meta.json"), outside.join("meta.json")).unwrap();
+
+    for id in ["../x", "inside/../../x", "/tmp", ""] {
+        assert!(store.get(id).is_err(), "{}", id);
+        assert!(store.delete(id).is_err(), "{}", id);
+        assert!(store.history_file(id).is_err(), "{}", id);
+    }
+    assert!(outside.join("meta.json").exists());
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use cai_core::{
+    config::Config,
+    history_manager::History,
+    memory_manager::{ MemoryChange, MemoryEntry, MemoryManager },
+    storage::{ history_store, memory_store, HistoryStore, SqliteHistory, StorageKind },
+    types::MessageRole,
+};
+
+fn history(path: &Path) -> History {
+    History::new(&path.to_string_lossy(), 200, 10)
+}
+
+fn contents(history: &History) -> Vec<String> {
+    history.get().into_iter().map(|message| message.content).collect()
+}
+
+#[test]
+fn both_backends_keep_trees_and_memories() {
+    // Apart, so the SQLite files don't start from the JSON ones
+    let (json_dir, sqlite_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+    for path in [json_dir.path().join("history.json"), sqlite_dir.path().join("history.db")] {
+        let name = path.display();
+        let mut h = history(&path);
+        h.add_message(MessageRole::System, "prompt".into());
+        h.add_message(MessageRole::User, "first".into());
+        h.add_message(MessageRole::Assistant, "reply".into());
+        h.save();
+        h.edit(1, "edited".into()).unwrap();
+        h.add_message(MessageRole::Assistant, "other reply".into());
+        h.save();
+
+        let loaded = history(&path);
+        assert_eq!(contents(&loaded), ["prompt", "edited", "other reply"], "{}", name);
+        assert_eq!(loaded.children(0).len(), 2);
+        assert_eq!(loaded.nodes().len(), 5);
+
+        // Clearing shrinks what's stored too
+        let mut h = loaded;
+        h.clear();
+        assert_eq!(history(&path).nodes().len(), 1, "{}", name);
+    }
+
+    for path in [json_dir.path().join("memory.json"), sqlite_dir.path().join("memory.db")] {
+        let (name, path) = (path.display().to_string(), path.to_string_lossy().into_owned());
+        let mut memory = MemoryManager::new(&path);
+        let tabs = memory.add("likes tabs", &[], None).unwrap();
+        let fish = memory.add("uses fish shell", &[], None).unwrap();
+        memory.update(tabs.id, MemoryChange { content: Some("likes spaces".into()), ..Default::default() }).unwrap();
+        memory.delete(fish.id).unwrap();
+        assert_eq!(MemoryManager::new(&path).view(), "#1 likes spaces", "{}", name);
+
+        // Changes go to the store, not to a stale copy
+        let mut other = memory_store(&path);
+        other.insert(MemoryEntry::new("works on cai")).unwrap();
+        memory.add("prefers short answers", &[], None).unwrap();
+        let contents: Vec<String> = memory_store(&path).load().unwrap().into_iter().map(|e| e.content).collect();
+        assert_eq!(contents, ["likes spaces", "works on cai", "prefers short answers"], "{}", name);
+        memory.clear().unwrap();
+        assert!(MemoryManager::new(&path).is_empty());
+    }
+}
+
+#[test]
+fn sqlite_merges_appends_from_another_process() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.db");
+    let mut h = history(&path);
+    h.add_message(MessageRole::System, "prompt".into());
+    h.add_message(MessageRole::User, "shared".into());
+    h.save();
+
+    // Two instances load the same history and each append a turn
+    let mut a = history(&path);
+    let mut b = history(&path);
+    a.add_message(MessageRole::User, "from a".into());
+    a.add_message(MessageRole::Assistant, "reply a".into());
+    a.save();
+    b.add_message(MessageRole::User, "from b".into());
+    b.save();
+
+    // b keeps a's turn as a sibling branch and stays on its own
+    assert_eq!(contents(&b), ["prompt", "shared", "from b"]);
+    assert_eq!(b.children(1), vec![2, 4]);
+    let loaded = history(&path);
+    assert_eq!(contents(&loaded), ["prompt", "shared", "from b"]);
+    assert_eq!(loaded.nodes().len(), 5);
+    let mut switched = loaded;
+    switched.switch(2).unwrap();
+    assert_eq!(contents(&switched), ["prompt", "shared", "from a", "reply a"]);
+}
+
+#[test]
+fn sqlite_rewrites_a_history_another_process_cleared() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.db");
+    let mut h = history(&path);
+    h.add_message(MessageRole::System, "prompt".into());
+    h.add_message(MessageRole::User, "shared".into());
+    h.add_message(MessageRole::Assistant, "reply".into());
+    h.save();
+
+    // One instance clears while another keeps appending
+    let mut a = history(&path);
+    let mut b = history(&path);
+    a.clear();
+    b.add_message(MessageRole::User, "from b".into());
+    b.save();
+
+    let loaded = history(&path);
+    assert_eq!(contents(&loaded), ["prompt", "shared", "reply", "from b"]);
+    assert_eq!(loaded.nodes().len(), 4);
+
+    // The cleared instance saving again keeps b's nodes, like any append
+    a.save();
+    assert_eq!(history(&path).nodes().len(), 4);
+}
+
+#[test]
+fn sqlite_files_start_from_json_and_check_their_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("history.json");
+    std::fs::write(&json, r#"[{"role": "system", "content": "prompt"}, {"role": "user", "content": "old"}]"#).unwrap();
+    std::fs::write(dir.path().join("memory.json"), "likes tabs\nuses fish shell").unwrap();
+
+    let db = dir.path().join("history.db");
+    let mut h = history(&db);
+    assert_eq!(contents(&h), ["prompt", "old"]);
+    h.add_message(MessageRole::User, "new".into());
+    h.save();
+    std::fs::remove_file(&json).unwrap();
+    assert_eq!(contents(&history(&db)), ["prompt", "old", "new"]);
+
+    let memory_db = dir.path().join("memory.db").to_string_lossy().into_owned();
+    let mut memory = MemoryManager::new(&memory_db);
+    assert_eq!(memory.view(), "#1 likes tabs\n#2 uses fish shell");
+
+    // The old file is imported once: cleared memories stay cleared
+    memory.clear().unwrap();
+    assert!(MemoryManager::new(&memory_db).is_empty());
+    let mut memory = MemoryManager::new(&memory_db);
+    let added = memory.add("x", &[], None).unwrap();
+    memory.delete(added.id).unwrap();
+    assert!(MemoryManager::new(&memory_db).is_empty());
+
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+    assert_eq!(version, 1);
+    conn.pragma_update(None, "user_version", 99).unwrap();
+    drop(conn);
+    let err = SqliteHistory::new(&db).load().unwrap_err();
+    assert!(format!("{:#}", err).contains("newer cai"), "{:#}", err);
+    assert!(history_store(&db.to_string_lossy()).load().is_err());
+}
+
+#[test]
+fn old_memory_databases_are_migrated() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("memory.db");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch("
+        CREATE TABLE memories (id INTEGER PRIMARY KEY AUTOINCREMENT, content TEXT NOT NULL);
+        INSERT INTO memories (content) VALUES ('likes tabs');
+        PRAGMA user_version = 1;
+    ").unwrap();
+    drop(conn);
+
+    let mut memory = MemoryManager::new(&path.to_string_lossy());
+    let entry = memory.get(1).unwrap();
+    assert_eq!((entry.content.as_str(), entry.importance, entry.tags.len()), ("likes tabs", 0.5, 0));
+    assert_eq!(memory.add("new", &["x".to_string()], None).unwrap().id, 2);
+}
+
+#[test]
+fn storage_setting_picks_the_files() {
+    assert_eq!(StorageKind::of(Path::new("a/history.sqlite3")), StorageKind::Sqlite);
+    assert_eq!(StorageKind::Sqlite.path_for("a/history.json"), "a/history.db");
+    assert_eq!(StorageKind::Json.path_for("a/history.json"), "a/history.json");
+
+    let env = |key: &str| match key {
+        "CAI_STORAGE" => Some("SQLite".to_string()),
+        _ => None,
+    };
+    let config = Config::load_from(None, None, &env).unwrap();
+    assert_eq!(config.storage, StorageKind::Sqlite);
+    assert_eq!((config.history_file.as_str(), config.memory_file.as_str()), ("history.db", "memory.db"));
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use async_trait::async_trait;
+use cai_core::client_util::call_stream_with_backoff;
+use cai_core::provider::{
+    AnthropicProvider, Completion, CompletionRequest, DeltaSender, Endpoint, OpenAIProvider, Provider,
+    ProviderKind,
+};
+use common::MockServer;
+use serde_json::{ json, Value };
+use tokio::sync::mpsc::unbounded_channel;
+
+fn sse(events: &[Value]) -> String {
+    events.iter().map(|e| format!("data: {}\n\n", e)).collect()
+}
+
+fn request() -> CompletionRequest {
+    CompletionRequest::new("test-model", vec![json!({ "role": "user", "content": "hi" })])
+}
+
+#[tokio::test]
+async fn openai_stream_assembles_text_and_tool_calls() {
+    let chunk = |delta: Value| json!({ "choices": [{ "index": 0, "delta": delta }] });
+    let mut body = sse(&[
+        chunk(json!({ "role": "assistant", "content": "Hel" })),
+        chunk(json!({ "content": "lo" })),
+        chunk(json!({ "tool_calls": [{ "index": 0, "id": "call_a", "type": "function",
+            "function": { "name": "run_terminal", "arguments": "{\"comm" } }] })),
+        chunk(json!({ "tool_calls": [{ "index": 1, "id": "call_b", "type": "function",
+            "function": { "name": "run_python", "arguments": "" } }] })),
+        chunk(json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "and\":\"ls\"}" } }] })),
+        chunk(json!({ "tool_calls": [{ "index": 1, "function": { "arguments": "{\"code\":\"1\"}" } }] })),
+        json!({ "choices": [], "usage": { "prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 7 } }),
+    ]);
+    body.push_str("data: [DONE]\n\n");
+
+    let server = MockServer::start(vec![("text/event-stream", body)]).await;
+    let provider = OpenAIProvider::from_endpoint(&Endpoint {
+        kind: ProviderKind::OpenAI,
+        base_url: Some(server.base_url.clone()),
+        ..Default::default()
+    });
+
+    let (tx, mut rx) = unbounded_channel();
+    let completion = provider.complete_stream(&request(), tx).await.unwrap();
+
+    let mut chunks = Vec::new();
+    while let Ok(c) = rx.try_recv() {
+        chunks.push(c);
+    }
+    assert_eq!(chunks, vec!["Hel", "lo"]);
+    assert_eq!(completion.content.as_deref(), Some("Hello"));
+
+    assert_eq!(completion.tool_calls.len(), 2);
+    assert_eq!(completion.tool_calls[0].id, "call_a");
+    assert_eq!(completion.tool_calls[0].args(), json!({ "command": "ls" }));
+    assert_eq!(completion.tool_calls[1].name, "run_python");
+    assert_eq!(completion.tool_calls[1].args(), json!({ "code": "1" }));
+    assert_eq!(completion.usage.unwrap().total_tokens, 7);
+
+    assert_eq!(server.requests()[0].body["stream"], true);
+}
+
+#[tokio::test]
+async fn anthropic_stream_assembles_text_and_tool_use() {
+    let body = sse(&[
+        json!({ "type": "message_start", "message": { "usage": { "input_tokens": 11 } } }),
+        json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }),
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "On " } }),
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "it" } }),
+        json!({ "type": "content_block_stop", "index": 0 }),
+        json!({ "type": "content_block_start", "index": 1,
+            "content_block": { "type": "tool_use", "id": "toolu_9", "name": "write_file", "input": {} } }),
+        json!({ "type": "content_block_delta", "index": 1, "delta": { "type": "input_json_delta", "partial_json": "{\"path\":" } }),
+        json!({ "type": "content_block_delta", "index": 1, "delta": { "type": "input_json_delta", "partial_json": "\"a.txt\"}" } }),
+        json!({ "type": "content_block_stop", "index": 1 }),
+        json!({ "type": "message_delta", "delta": { "stop_reason": "tool_use" }, "usage": { "output_tokens": 6 } }),
+        json!({ "type": "message_stop" }),
+    ]);
+
+    let server = MockServer::start(vec![("text/event-stream", body)]).await;
+    let provider = AnthropicProvider::new(Some(&server.base_url), None);
+
+    let (tx, mut rx) = unbounded_channel();
+    let completion = provider.complete_stream(&request(), tx).await.unwrap();
+
+    let mut streamed = String::new();
+    while let Ok(c) = rx.try_recv() {
+        streamed.push_str(&c);
+    }
+    assert_eq!(streamed, "On it");
+    assert_eq!(completion.content.as_deref(), Some("On it"));
+
+    assert_eq!(completion.tool_calls.len(), 1);
+    assert_eq!(completion.tool_calls[0].id, "toolu_9");
+    assert_eq!(completion.tool_calls[0].args(), json!({ "path": "a.txt" }));
+
+    let usage = completion.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (11, 6, 17));
+}
+
+#[tokio::test]
+async fn anthropic_stream_keeps_characters_split_across_chunks() {
+    let body = sse(&[
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "café ✓" } }),
+        json!({ "type": "content_block_start", "index": 1,
+            "content_block": { "type": "tool_use", "id": "toolu_1", "name": "write_file", "input": {} } }),
+        json!({ "type": "content_block_delta", "index": 1, "delta": { "type": "input_json_delta", "partial_json": "{\"content\":\"naïve\"}" } }),
+    ]).into_bytes();
+    // Cut inside "é" and inside "ï"
+    let e = body.windows(2).position(|w| w == "é".as_bytes()).unwrap() + 1;
+    let i = body.windows(2).position(|w| w == "ï".as_bytes()).unwrap() + 1;
+    let parts = vec![body[..e].to_vec(), body[e..i].to_vec(), body[i..].to_vec()];
+
+    let server = MockServer::start_in_parts(vec![("text/event-stream", parts)]).await;
+    let provider = AnthropicProvider::new(Some(&server.base_url), None);
+
+    let (tx, mut rx) = unbounded_channel();
+    let completion = provider.complete_stream(&request(), tx).await.unwrap();
+
+    assert_eq!(rx.try_recv().unwrap(), "café ✓");
+    assert_eq!(completion.content.as_deref(), Some("café ✓"));
+    assert_eq!(completion.tool_calls[0].args(), json!({ "content": "naïve" }));
+}
+
+/// Streams some text, then drops the connection.
+#[derive(Default)]
+struct BrokenStream {
+    attempts: AtomicUsize,
+}
+
+#[async_trait]
+impl Provider for BrokenStream {
+    fn name(&self) -> &str {
+        "broken"
+    }
+
+    async fn complete(&self, _request: &CompletionRequest) -> anyhow::Result<Completion> {
+        anyhow::bail!("not streaming")
+    }
+
+    async fn complete_stream(&self, _request: &CompletionRequest, deltas: DeltaSender) -> anyhow::Result<Completion> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        let _ = deltas.send("Hel".to_string());
+        anyhow::bail!("connection reset")
+    }
+}
+
+#[tokio::test]
+async fn streams_are_not_retried_once_text_went_out() {
+    let provider = BrokenStream::default();
+    let (tx, mut rx) = unbounded_channel();
+
+    assert!(call_stream_with_backoff(&provider, &request(), tx).await.is_err());
+    assert_eq!(provider.attempts.load(Ordering::SeqCst), 1);
+    assert_eq!(rx.try_recv().unwrap(), "Hel");
+    assert!(rx.try_recv().is_err());
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use cai_core::{
+    provider::MockProvider,
+    tools::{ Tool, ToolRegistry },
+    types::{ MessageRole, ToolCall },
+};
+use common::{ test_client, TestUI };
+use serde_json::{ json, Value };
+
+struct Shout;
+
+#[async_trait]
+impl Tool for Shout {
+    fn name(&self) -> &str {
+        "shout"
+    }
+
+    fn description(&self) -> &str {
+        "Upper-cases text"
+    }
+
+    fn schema(&self) -> Value {
+        json!({ "type": "object", "properties": { "text": { "type": "string" } }, "required": ["text"] })
+    }
+
+    async fn invoke(&self, args: Value) -> anyhow::Result<String> {
+        let text = args.get("text").and_then(|x| x.as_str()).ok_or_else(|| anyhow::anyhow!("no text"))?;
+        Ok(text.to_uppercase())
+    }
+}
+
+#[tokio::test]
+async fn custom_tools_are_offered_and_dispatched() {
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("shout", json!({ "text": "hi" }))
+            .reply("done")
+    );
+    let (mut client, _tmp) = test_client(mock.clone());
+    client.tools.register(Shout);
+
+    let reply = client.send_message(&TestUI::default(), MessageRole::User, "shout hi", None).await;
+    assert_eq!(reply, "done");
+
+    let requests = mock.requests();
+    let offered: Vec<&str> = requests[0].tools.as_ref().unwrap().as_array().unwrap()
+        .iter()
+        .map(|t| t["function"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(offered, vec![
+        "run_terminal", "run_python", "write_file", "edit_file", "read_file", "list_dir", "glob", "grep",
+        "memory_add", "memory_update", "memory_delete", "memory_search", "memory_view", "shout",
+    ]);
+    assert_eq!(requests[1].messages.last().unwrap()["content"], "HI");
+}
+
+#[tokio::test]
+async fn registry_reports_errors_and_unknown_tools_as_text() {
+    let mut registry = ToolRegistry::new();
+    registry.register(Shout);
+
+    let call = |name: &str, args: Value| ToolCall { id: "1".into(), name: name.into(), arguments: args.to_string() };
+    assert_eq!(registry.invoke(&call("shout", json!({}))).await, "[shout] ERROR: no text");
+    assert_eq!(registry.invoke(&call("nope", json!({}))).await, "[tool error] Unknown tool: nope");
+
+    assert!(registry.unregister("shout"));
+    assert!(registry.is_empty());
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use std::sync::Arc;
+
+use cai_core::{
+    agent_loop::process_response,
+    provider::MockProvider,
+    types::MessageRole,
+    workspace::Workspace,
+};
+use common::{ test_client, TestUI };
+use serde_json::json;
+
+#[test]
+fn paths_resolve_inside_the_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let workspace = Workspace::new(dir.path()).unwrap();
+    let root = workspace.root().to_path_buf();
+
+    assert_eq!(workspace.resolve("src/new/../main.rs").unwrap(), root.join("src/main.rs"));
+    assert_eq!(workspace.resolve(root.join("a.txt").to_str().unwrap()).unwrap(), root.join("a.txt"));
+
+    for escape in ["../outside.txt", "new/../../outside.txt", "/etc/passwd", ""] {
+        assert!(workspace.resolve(escape).is_err(), "{}", escape);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_cannot_escape() {
+    let root = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    std::os::unix::fs::symlink(outside.path(), root.path().join("link")).unwrap();
+    std::os::unix::fs::symlink(outside.path().join("missing"), root.path().join("dangling")).unwrap();
+    let workspace = Workspace::new(root.path()).unwrap();
+
+    assert!(workspace.write("link/x.txt", "x").is_err());
+    assert!(workspace.write("dangling", "x").is_err());
+    assert!(!outside.path().join("x.txt").exists());
+    assert!(!outside.path().join("missing").exists());
+
+    // `..` out of a directory that doesn't exist yet lands on the symlink
+    assert!(workspace.write("missing/../link/pwned.txt", "x").is_err());
+    assert!(workspace.resolve("new/dir/../../link/pwned.txt").is_err());
+    assert!(!outside.path().join("pwned.txt").exists());
+
+    // Unless the target is explicitly allowed
+    let workspace = workspace.with_writable(&[outside.path()]).unwrap();
+    workspace.write("link/x.txt", "x").unwrap();
+    assert_eq!(std::fs::read_to_string(outside.path().join("x.txt")).unwrap(), "x");
+}
+
+#[tokio::test]
+async fn file_tools_are_confined_to_the_workspace() {
+    let outside = tempfile::tempdir().unwrap();
+    let escape = outside.path().join("escape.txt");
+    let mock = Arc::new(
+        MockProvider::default()
+            .tool_call("write_file", json!({ "path": escape.to_str().unwrap(), "content": "x" }))
+            .reply("tried")
+            .reply("ok")
+    );
+    let (mut client, tmp) = test_client(mock.clone());
+    let ui = TestUI::default();
+
+    client.send_message(&ui, MessageRole::User, "write it", None).await;
+    let result = mock.requests()[1].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(result.contains("outside the workspace"), "{}", result);
+
+    let response = "```write[../escape.txt]\nx\n```\n```write[notes/a.txt]\nhello\n```".to_string();
+    process_response(&ui, &mut client, response).await;
+
+    assert!(!escape.exists());
+    assert!(!tmp.path().parent().unwrap().join("escape.txt").exists());
+    assert_eq!(std::fs::read_to_string(tmp.path().join("notes/a.txt")).unwrap(), "hello");
+    let sys = mock.requests()[2].messages.last().unwrap()["content"].as_str().unwrap().to_string();
+    assert!(sys.contains("[Write Block Failed]") && sys.contains("outside the workspace"), "{}", sys);
+}
@@ -1,7 +1,6 @@
 //! Yes, this stupid module is for auto git pull.
 
 use std::process::Command;
-use std::io::{self, Write};
 
 #[derive(Debug)]
 pub enum AutoGitStatus {
@@ -89,28 +88,29 @@ pub fn check_and_pull() -> AutoGitStatus {
         .output();
     if let Ok(ref o) = cmp {
         let count = String::from_utf8_lossy(&o.stdout).trim().parse::<u32>().unwrap_or(0);
-        if count > 0 {
-            // Try to pull
-            let pull = Command::new("git").arg("pull").output();
-            if let Ok(ref p) = pull {
-                if p.status.success() {
-                    log_info("Pulled latest changes.");
-                    return AutoGitStatus::Pulled;
-                } else {
-                    log_warn("Failed to pull changes.");
-                    return AutoGitStatus::Warning;
-                }
-            } else {
-                log_warn("Failed to execute git pull.");
-                return AutoGitStatus::Warning;
-            }
-        } else {
+        if count == 0 {
             log_info("Already up to date.");
             return AutoGitStatus::AlreadyUpToDate;
         }
+
+        // Try to pull
+        match Command::new("git").arg("pull").output() {
+            Ok(ref p) if p.status.success() => {
+                log_info("Pulled latest changes.");
+                AutoGitStatus::Pulled
+            }
+            Ok(_) => {
+                log_warn("Failed to pull changes.");
+                AutoGitStatus::Warning
+            }
+            Err(_) => {
+                log_warn("Failed to execute git pull.");
+                AutoGitStatus::Warning
+            }
+        }
     } else {
         log_warn("Could not compare with remote.");
-        return AutoGitStatus::Warning;
+        AutoGitStatus::Warning
     }
 }
 
@@ -0,0 +1,296 @@
+//! ===============================================================
+//! Command-Line Interface
+//!
+//! Argument definitions for the `cai` binary. Global flags are the
+//! last configuration layer: they are applied on top of the `Config`
+//! resolved from files and environment variables.
+//! ===============================================================
+
+use std::path::PathBuf;
+
+use clap::{ Args, Parser, Subcommand };
+
+use cai_core::{ approval::ApprovalMode, config::Config, export::ExportFormat };
+
+#[derive(Debug, Parser)]
+#[command(name = "cai", version, about = "Console AI -- a chat agent that can run commands and edit files")]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    /// What to do (default: chat)
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Args)]
+pub struct GlobalArgs {
+    /// Model id to use instead of the configured one
+    #[arg(long, short, global = true)]
+    pub model: Option<String>,
+
+    /// Directory to work in (default: the current directory)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub cwd: Option<PathBuf>,
+
+    /// Don't offer native tool calls to the model
+    #[arg(long, global = true)]
+    pub no_tools: bool,
+
+    /// When to ask before running commands and writing files:
+    /// always-ask, ask-for-writes, auto or deny
+    #[arg(long, global = true, value_name = "MODE", value_parser = parse_approval)]
+    pub approval: Option<ApprovalMode>,
+
+    /// Session to work in, created when missing
+    /// (default: the last session started in this directory)
+    #[arg(long, short, global = true, value_name = "NAME")]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Interactive chat (default)
+    Chat,
+
+    /// Answer a single prompt and exit -- e.g. `git diff | cai ask "write a commit message"`
+    ///
+    /// Piped stdin is appended to the prompt. Only the final answer is
+    /// printed. Exit status: 0 answered, 1 request failed, 2 no prompt.
+    Ask {
+        /// The prompt (optional when stdin is piped)
+        prompt: Vec<String>,
+
+        /// Print a JSON object with text, tool calls and usage
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show, clear or search the conversation history, or show its branches
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+    },
+
+    /// Show or clear the persistent memory
+    Memory {
+        #[command(subcommand)]
+        action: Option<StoreAction>,
+    },
+
+    /// List, diff and restore the files the agent wrote
+    Checkpoints {
+        #[command(subcommand)]
+        action: Option<CheckpointAction>,
+    },
+
+    /// List, start, resume, rename or delete sessions
+    Sessions {
+        #[command(subcommand)]
+        action: Option<SessionAction>,
+    },
+
+    /// Export the session's conversation (its current branch)
+    Export {
+        /// Output file (default: stdout)
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// md, html or jsonl (default: from the output file's extension, else md)
+        #[arg(long, short, value_parser = parse_format)]
+        format: Option<ExportFormat>,
+    },
+
+    /// Import conversations from fine-tuning JSONL, one new session each
+    Import {
+        file: PathBuf,
+
+        /// Session name (numbered when the file holds several conversations)
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Show the resolved configuration
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum StoreAction {
+    /// Print the contents (default)
+    Show,
+
+    /// Delete the contents
+    Clear,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum HistoryAction {
+    /// Print the current branch (default)
+    Show,
+
+    /// Delete the history, every branch included
+    Clear,
+
+    /// Print every branch as a tree
+    Tree,
+
+    /// Search the messages of every session (--session limits it to one)
+    Search {
+        /// Keywords, ranked by BM25
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// Only messages of this role: user, assistant, system or tool
+        #[arg(long)]
+        role: Option<String>,
+
+        /// From this date on: YYYY-MM-DD, or relative like 3d, 12h, 2w
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Up to this date (same forms as --since)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Maximum number of results
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum ConfigAction {
+    /// Print the resolved configuration (default)
+    Show,
+
+    /// Print the config file locations
+    Path,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SessionAction {
+    /// List the sessions, most recent first (default)
+    List,
+
+    /// Start a new session (named, or numbered) and chat in it
+    New { name: Option<String> },
+
+    /// Chat in session NAME (default: the last one for this directory)
+    Resume { name: Option<String> },
+
+    /// Set the title of session NAME
+    Rename { name: String, title: Vec<String> },
+
+    /// Delete session NAME with its history and checkpoints
+    Delete { name: String },
+}
+
+/// Also available in chat as `/checkpoints`, `/diff ID`, `/restore ID`,
+/// `/restore-turn N` and `/undo`.
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum CheckpointAction {
+    /// List the checkpoints (default)
+    List,
+
+    /// Show the change recorded by checkpoint ID
+    Diff { id: usize },
+
+    /// Put the file of checkpoint ID back as it was before that write
+    Restore { id: usize },
+
+    /// Undo every write of turn N
+    RestoreTurn { turn: usize },
+
+    /// Undo the latest turn and forget its checkpoints
+    Undo,
+}
+
+impl CheckpointAction {
+    /// Parses a chat slash command. `None` → not a checkpoint command.
+    pub fn from_slash(input: &str) -> Option<Result<Self, String>> {
+        let mut words = input.split_whitespace();
+        let command = words.next()?;
+        let mut number = |name: &str| -> Result<usize, String> {
+            words.next()
+                .and_then(|n| n.trim_start_matches('#').parse().ok())
+                .ok_or_else(|| format!("usage: {} <number>", name))
+        };
+        Some(match command {
+            "/checkpoints" => Ok(Self::List),
+            "/diff" => number(command).map(|id| Self::Diff { id }),
+            "/restore" => number(command).map(|id| Self::Restore { id }),
+            "/restore-turn" => number(command).map(|turn| Self::RestoreTurn { turn }),
+            "/undo" => Ok(Self::Undo),
+            _ => return None,
+        })
+    }
+}
+
+/// Chat commands that move around the conversation tree: `/tree`,
+/// `/fork ID`, `/switch ID`, `/edit ID TEXT` and `/regen`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BranchCommand {
+    /// Show every branch
+    Tree,
+
+    /// Continue from message ID; the next message starts a new branch
+    Fork { id: usize },
+
+    /// Switch to the latest branch through message ID
+    Switch { id: usize },
+
+    /// Replace user message ID by TEXT on a new branch and answer it
+    Edit { id: usize, text: String },
+
+    /// Regenerate the latest reply on a new branch
+    Regen,
+}
+
+impl BranchCommand {
+    /// Parses a chat slash command. `None` → not a branch command.
+    pub fn from_slash(input: &str) -> Option<Result<Self, String>> {
+        let input = input.trim_start();
+        let (command, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let (id, text) = rest.trim_start().split_once(char::is_whitespace).unwrap_or((rest.trim(), ""));
+        let id = id.trim_start_matches('#').parse::<usize>()
+            .map_err(|_| format!("usage: {} <number>{}", command, if command == "/edit" { " <text>" } else { "" }));
+        Some(match command {
+            "/tree" => Ok(Self::Tree),
+            "/fork" => id.map(|id| Self::Fork { id }),
+            "/switch" => id.map(|id| Self::Switch { id }),
+            "/edit" => id.and_then(|id| match text.trim() {
+                "" => Err("usage: /edit <number> <text>".to_string()),
+                text => Ok(Self::Edit { id, text: text.to_string() }),
+            }),
+            "/regen" => Ok(Self::Regen),
+            _ => return None,
+        })
+    }
+}
+
+impl GlobalArgs {
+    /// Applies the flags over the resolved configuration (layer 5).
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(model) = &self.model {
+            config.model = Some(model.clone());
+        }
+        if let Some(approval) = self.approval {
+            config.approval = approval;
+        }
+        if self.no_tools {
+            config.tools = false;
+        }
+    }
+}
+
+fn parse_format(format: &str) -> Result<ExportFormat, String> {
+    ExportFormat::parse(format).ok_or_else(|| "expected md, html or jsonl".to_string())
+}
+
+fn parse_approval(mode: &str) -> Result<ApprovalMode, String> {
+    ApprovalMode::parse(mode)
+        .ok_or_else(|| "expected always-ask, ask-for-writes, auto or deny".to_string())
+}
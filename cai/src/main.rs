@@ -1,21 +1,44 @@
 //! ===============================================================
 //! Console AI Framework - Main Entry Point
 //!
-//! This file orchestrates the CLI loop, manages user/AI interaction,
-//! and coordinates memory, history, and command parsing modules.
+//! This file parses the command line, orchestrates the CLI loop,
+//! manages user/AI interaction, and coordinates memory, history,
+//! and command parsing modules.
 //! ===============================================================
 
-use std::{ env, path::PathBuf };
+use std::{ env, io::{ IsTerminal, Read }, path::{ Path, PathBuf }, process::ExitCode };
+
+use anyhow::Context;
+use clap::Parser;
 
 mod auto_git_pull;
 use auto_git_pull::{check_and_pull, AutoGitStatus};
 
+mod cli;
+use cli::{ BranchCommand, CheckpointAction, Cli, Command, ConfigAction, HistoryAction, SessionAction, StoreAction };
+
+mod quiet_ui;
+use quiet_ui::QuietUI;
+
 
 
 // ===================== Local Modules =====================
 use cai_core::{
     // Client module -- handles AI interactions
-    client::Client, constants::*, parsers::*, prompt::*, shell::Shell, types::MessageRole, ui_trait::{MsgRole, MsgType, UIBase}
+    agent_loop::{process_response, run_once, start_session, LoopOutcome, OneShot},
+    checkpoints::CheckpointStore,
+    export::{ from_jsonl, to_html, to_jsonl, to_markdown, ExportFormat },
+    client::Client,
+    config::{ find_project_config, user_config_path, Config },
+    history_manager::History,
+    memory_manager::MemoryManager,
+    provider::Endpoint,
+    search::{ format_date, index_file, parse_date, parse_role, search, SearchQuery },
+    sessions::{ now, SessionMeta, SessionStore },
+    shell::Shell,
+    workspace::Workspace,
+    types::MessageRole,
+    ui_trait::{ MsgRole, MsgType, UIBase }
 };
 
 
@@ -29,53 +52,121 @@ use cai_cli::UI;
 // ===============================================================
 /// ## Main Async Entry Point
 ///
-/// Initializes all core components and runs the main CLI loop.
-/// Handles user input, AI responses, and special command parsing.
+/// Parses the command line, resolves the configuration and runs
+/// the selected subcommand (interactive chat by default).
 // ===============================================================
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
+    let cli: Cli = Cli::parse();
+    let command: Command = cli.command.unwrap_or(Command::Chat);
+
+    // Self-update only makes sense for interactive sessions.
+    if matches!(command, Command::Chat) {
+        let auto_pull_result: AutoGitStatus = check_and_pull();
+        match auto_pull_result {
+            AutoGitStatus::AlreadyUpToDate | AutoGitStatus::LocalChanges  => {}
+
+            // Since there were changes, end the program and let the user restart it.
+            AutoGitStatus::Pulled => {
+                println!("[SYSTEM] Reloading... New commit detected.");
+                println!("[SYSTEM] Restart the program to apply the changes.");
+                println!("[SYSTEM] Exiting...");
+                return ExitCode::SUCCESS;
+            }
 
-    let auto_pull_result: AutoGitStatus = check_and_pull();
-    match auto_pull_result {
-        AutoGitStatus::AlreadyUpToDate | AutoGitStatus::LocalChanges  => {}
+            _ => {
+                #[cfg(debug_assertions)]
+                println!("[DEBUG] AutoGitPull: {:?}", auto_pull_result);
+            }
+        }
+    }
 
-        // Since there were changes, end the program and let the user restart it.
-        AutoGitStatus::Pulled => {
-            println!("[SYSTEM] Reloading... New commit detected.");
-            println!("[SYSTEM] Restart the program to apply the changes.");
-            println!("[SYSTEM] Exiting...");
-            return Ok(());
+    // --- Move into the working directory: config lookup, history and memory files are relative to it ---
+    if let Some(dir) = &cli.global.cwd {
+        if let Err(e) = env::set_current_dir(dir) {
+            eprintln!("[ERROR] Cannot use {} as working directory: {}", dir.display(), e);
+            return ExitCode::FAILURE;
         }
+    }
+
 
-        _ => {
-            #[cfg(debug_assertions)]
-            println!("[DEBUG] AutoGitPull: {:?}", auto_pull_result);
+    // ===== Resolve configuration =====
+    // defaults < ~/.config/cai/config.toml < .cai.toml < env < CLI flags
+    let mut config: Config = match env::current_dir().map_err(Into::into).and_then(|cwd| Config::load(&cwd)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            return ExitCode::FAILURE;
         }
+    };
+    cli.global.apply(&mut config);
+
+
+    // ===== Pick the session =====
+    // History and checkpoints live in the session; without one they fall
+    // back to `history_file` in the cwd.
+    let session_name: Option<&str> = cli.global.session.as_deref();
+    let (mode, name) = match &command {
+        Command::Chat => (SessionMode::Open, session_name),
+        Command::Sessions { action: Some(SessionAction::New { name }) } => (SessionMode::New, name.as_deref()),
+        Command::Sessions { action: Some(SessionAction::Resume { name }) } => (SessionMode::Resume, name.as_deref()),
+        _ => (SessionMode::Existing, session_name),
+    };
+    let session: Option<(SessionStore, SessionMeta)> = match select_session(&mut config, mode, name) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+
+    match command {
+        Command::Chat => run_chat(&config, cli.global.cwd.is_none(), session).await,
+        Command::Ask { prompt, json } => run_ask(&config, &prompt.join(" "), json).await,
+        Command::History { action: Some(HistoryAction::Search { query, role, since, until, limit }) } => {
+            let query = search_query(query.join(" "), role, cli.global.session, since, until, limit);
+            run_search(&config, query)
+        }
+        Command::History { action } => run_history(&config, session.as_ref(), action.unwrap_or(HistoryAction::Show)),
+        Command::Memory { action } => run_memory(&config, action.unwrap_or(StoreAction::Show)),
+        Command::Checkpoints { action } => run_checkpoints(&config, action.unwrap_or(CheckpointAction::List)),
+        Command::Sessions { action } => match action.unwrap_or(SessionAction::List) {
+            SessionAction::New { .. } | SessionAction::Resume { .. } => {
+                run_chat(&config, cli.global.cwd.is_none(), session).await
+            }
+            action => run_sessions(&config, action),
+        },
+        Command::Export { output, format } => run_export(&config, session.as_ref().map(|(_, meta)| meta), output, format),
+        Command::Import { file, name } => run_import(&config, &file, name.as_deref()),
+        Command::Config { action } => run_config(&config, action.unwrap_or(ConfigAction::Show)),
     }
+}
+
 
 
+// ===============================================================
+/// ## Interactive Chat
+///
+/// Runs the main CLI loop: handles user input, AI responses and
+/// special command parsing until the user quits.
+// ===============================================================
+async fn run_chat(config: &Config, use_test_dir: bool, mut session: Option<(SessionStore, SessionMeta)>) -> ExitCode {
     let ui: &dyn UIBase = &UI;
     ui.init(); // Initialize the UI -- setup configuration, etc.
-    
-
-    // ===== Initialize AI Client and it's utilities =====
-     // --- Ensure a OPENAI_API_KEY is set in the environment ---
-     if env::var("OPENAI_API_KEY").is_err() {
-        eprintln!("[ERROR] OPENAI_API_KEY not set in the environment.");
-        eprintln!("Please set it before running the program.");
-        return Ok(());
-    }
 
+    if !check_api_key(&config.endpoint()) {
+        return ExitCode::FAILURE;
+    }
 
     // --- Determine Current Working Directory (absolute path) ---
-    let mut current_path: PathBuf = env
-        ::current_dir()
-        .expect("Couldn't optain current dir path.")
-        .canonicalize()
-        .expect("Failed to create absolute path of current directory");
-
-    // If in debug mode, create a test directory for the AI to work in.
-    if cfg!(debug_assertions) {
+    let mut current_path: PathBuf = match working_dir() {
+        Some(path) => path,
+        None => return ExitCode::FAILURE,
+    };
+
+    // If in debug mode (and no --cwd was given), create a test directory for the AI to work in.
+    if cfg!(debug_assertions) && use_test_dir {
         // Check if the directory exists, if not, create it.
         if !current_path.join("ai_test_dir").exists() {
             std::fs
@@ -85,74 +176,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         current_path = current_path.join("ai_test_dir");
     }
 
-    // --- Select Model (default: gpt-4.1) ---
-    let model: String = std::env
-        ::args()
-        .collect::<Vec<String>>()
-        .get(1)
-        .unwrap_or(&DEFAULT_MODEL.to_string())
-        .to_string();
-
     // --- Initialize Core Components ---
-    let mut assistant: Client = Client::new();
-    assistant.model = model;
-
-    let mut shell: Shell = Shell::new(current_path.to_str().unwrap()).expect("Failed to create shell. *cries*");
-
-
-    /*
-    ---------------------------------------------------------------
-    | continues: Number of consecutive continues
-    | If it reaches MAX_CONTINUE, it will reset to 0
-    | When 0, user input is required; otherwise, AI continues
-    ---------------------------------------------------------------
-    */
-    let mut continues: usize = 1; // 1 so first response get's passed into processing loop - nasty fix but whatever
-
-    // Buffer for the latest AI response
-    let mut response: String = String::new();
+    let mut assistant: Client = match Client::from_config(config) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    // --- System Prompt or Resume ---
-    if assistant.history.is_empty() {
-        response = assistant.send_message(
-            ui,
-            MessageRole::System,
-            &get_sys_prompt(
-                assistant.memory.read(None).as_str(), // Pass memory content
-                current_path.to_str().unwrap(),
-            ),
-            None
-        ).await;
-    } else {
-        response = assistant.send_message(ui, MessageRole::System, RESUME_PROMPT, None).await;
+    // Tool calls and ```terminal blocks share this session (cwd, env, timeout);
+    // file writes are confined to the same directory.
+    if !attach_session(&mut assistant, config, &current_path) {
+        return ExitCode::FAILURE;
+    }
+    if let Some((store, meta)) = &session {
+        assistant.attach_sessions(store.clone(), &meta.id);
     }
 
-    // ===================== CLI Commands =====================
-    // Short user-sided commands to fix issues or change behavior
-    let args: Vec<String> = env
-        ::args()
-        .collect::<Vec<String>>()
-        .get(2..)
-        .unwrap_or(&[])
-        .to_vec();
-
-    for arg in args.iter() {
-        match arg.to_lowercase().as_str() {
-            // Delete History
-            "clear_history" | "cls_h" => {
-                assistant.history.clear();
-            }
-            // Delete memory
-            "clear_memory" | "cls_m" => {
-                assistant.memory.clear();
-            }
-            // Unknown command
-            other => {
-                eprintln!("Unknown command: {}", other);
-            }
-        }
+    if let Some((_, meta)) = &session {
+        let title = if meta.title.is_empty() { "new session" } else { meta.title.as_str() };
+        println!("[SYSTEM] Session {}: {}", meta.id, title);
     }
 
+    // --- System Prompt or Resume ---
+    // Buffer for the latest AI response
+    let mut response: String = start_session(ui, &mut assistant, &current_path.to_string_lossy()).await;
+
 
     // ===============================================================
     // Main Interaction Loop
@@ -161,111 +211,603 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Also processes the AI response for special tokens and blocks.
     // ===============================================================
     'mainloop: loop {
+        // --- AI Response Processing Phase ---
+        if process_response(ui, &mut assistant, response).await == LoopOutcome::Restart {
+            break 'mainloop;
+        }
+
         // --- User Input Phase ---
-        if continues == 0 {
+        // Checkpoint commands (/undo, /diff, ...) and branch commands
+        // (/tree, /edit, ...) are handled locally.
+        let mut input: String = loop {
+            let input: String = ui.get_user_input();
+            if let Some(action) = CheckpointAction::from_slash(&input) {
+                match action {
+                    Ok(action) => {
+                        let output = assistant.workspace()
+                            .and_then(|workspace| match workspace.checkpoints() {
+                                Some(mut store) => checkpoint_action(&mut store, action),
+                                None => Ok("[SYSTEM] Checkpoints are disabled.".to_string()),
+                            })
+                            .unwrap_or_else(|e| format!("[ERROR] {:#}", e));
+                        ui.print_message(MsgRole::System, MsgType::Plain(output));
+                    }
+                    Err(usage) => ui.print_message(MsgRole::Error, MsgType::Plain(format!("[ERROR] {}", usage))),
+                }
+                continue;
+            }
+            if let Some(command) = BranchCommand::from_slash(&input) {
+                match command.map_err(anyhow::Error::msg) {
+                    Ok(command) => match branch_command(ui, &mut assistant, command).await {
+                        // Edits and regenerations answered: process that reply
+                        Ok(Some(reply)) => {
+                            response = reply;
+                            continue 'mainloop;
+                        }
+                        Ok(None) => {}
+                        Err(e) => ui.print_message(MsgRole::Error, MsgType::Plain(format!("[ERROR] {:#}", e))),
+                    },
+                    Err(usage) => ui.print_message(MsgRole::Error, MsgType::Plain(format!("[ERROR] {}", usage))),
+                }
+                continue;
+            }
+            break input;
+        };
+
+        // Provide a default message if input is empty.
+        if input.is_empty() {
+            input = "No Message".to_string();
+        }
+
+        // Exit condition.
+        if input.eq_ignore_ascii_case("q") {
+            assistant.history.save();
+            println!("[SYSTEM] Chat history saved. Exiting.");
+            break;
+        }
 
-            let mut input: String = ui.get_user_input();
+        println!();
 
-            // Provide a default message if input is empty.
-            if input.is_empty() {
-                input = "No Message".to_string();
-            }
+        response = assistant.send_message(ui, MessageRole::User, &input, None).await;
 
-            // Exit condition.
-            if input.eq_ignore_ascii_case("q") {
-                assistant.history.save();
-                println!("[SYSTEM] Chat history saved. Exiting.");
-                break;
+        if let Some((store, meta)) = &mut session {
+            meta.model = assistant.model.clone();
+            meta.title_from(&input);
+            if let Err(e) = store.touch(meta) {
+                eprintln!("[WARNING] Couldn't update session {}: {:#}", meta.id, e);
             }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
 
-            println!();
 
-            response = assistant.send_message(ui, MessageRole::User, &input, None).await;
 
+// ===============================================================
+/// ## One-Shot Question
+///
+/// Answers a single prompt (args + piped stdin) with a throwaway
+/// history, printing only the final answer -- or a JSON object.
+/// Exit status: 0 answered, 1 failure, 2 no prompt.
+// ===============================================================
+async fn run_ask(config: &Config, prompt: &str, json: bool) -> ExitCode {
+    let ui: &dyn UIBase = &QuietUI;
+
+    let Some(prompt) = read_prompt(prompt) else {
+        eprintln!("[ERROR] No prompt given. Pass it as an argument or pipe it through stdin.");
+        return ExitCode::from(2);
+    };
+
+    if !check_api_key(&config.endpoint()) {
+        return ExitCode::FAILURE;
+    }
+    let Some(current_path) = working_dir() else {
+        return ExitCode::FAILURE;
+    };
+    let cwd = current_path.to_string_lossy();
+
+    let mut assistant: Client = match Client::from_config(config) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            return ExitCode::FAILURE;
         }
+    };
+    assistant.history = History::ephemeral(config.max_history, config.summary_size());
+    if !attach_session(&mut assistant, config, &current_path) {
+        return ExitCode::FAILURE;
+    }
 
+    let result: OneShot = run_once(ui, &mut assistant, &cwd, &prompt).await;
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+    } else if let Some(error) = &result.error {
+        eprintln!("[ERROR] {}", error);
+    } else {
+        println!("{}", result.text);
+    }
+
+    if result.error.is_some() { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
 
-        // --- AI Response Processing Phase ---
-        'processing_loop: loop {
-            /*
-            ---------------------------------------------------------------
-            | sys_message: Message that will be sent to AI after response processing
-            | When empty, no further processing is needed
-            | When not empty, triggers another AI processing round
-            ---------------------------------------------------------------
-            */
-            let mut sys_message: String = String::new();
-
-            // --- Parse and Execute Special Blocks ---
-
-            // Handles file write instructions
-            if let Err(e) = parse_write_block(ui, &response) {
-                // Add to response the failure message
-                sys_message.push_str(&format!("[Write Block Failed]\n{}\n", e));
-            }
 
-            parse_python_block(ui, &response, &mut sys_message); // Handles python code execution
-            parse_commands_block(ui, &response, &mut shell, &mut sys_message); // Handles shell commands
-            // parse_memory_block(ui, &response, &mut assistant, &mut sys_message); // Handles memory updates
 
-            // * Token processing ----------------------------
-            if response.contains(RESTART_TOKEN) {
-                assistant.history.save();
+// ===================== Storage Subcommands =====================
 
-                ui.print_message(
-                    MsgRole::System,
-                    MsgType::Plain("[SYSTEM] Chat history saved. Restarting....".to_string()),
-                );
+fn run_history(config: &Config, session: Option<&(SessionStore, SessionMeta)>, action: HistoryAction) -> ExitCode {
+    let mut history: History = History::new(&config.history_file, config.max_history, config.summary_size());
+    if let Some((store, meta)) = session {
+        history.set_index(index_file(store), &meta.id);
+    }
+    match action {
+        HistoryAction::Show => {
+            if history.is_empty() {
+                println!("[SYSTEM] No history in {}", config.history_file);
+            }
+            for message in history.get() {
+                println!("[{:?}]\n{}\n", message.role, message.content);
+            }
+        }
+        HistoryAction::Clear => {
+            history.clear();
+            println!("[SYSTEM] History cleared: {}", config.history_file);
+        }
+        HistoryAction::Tree => {
+            if history.is_empty() {
+                println!("[SYSTEM] No history in {}", config.history_file);
+            }
+            print!("{}", history.render_tree());
+        }
+        HistoryAction::Search { .. } => unreachable!("handled by run_search"),
+    }
+    ExitCode::SUCCESS
+}
+
+/// Builds the query of `cai history search`; dates and role are checked by `run_search`.
+fn search_query(
+    text: String,
+    role: Option<String>,
+    session: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: usize
+) -> anyhow::Result<SearchQuery> {
+    Ok(SearchQuery {
+        text,
+        role: role.as_deref().map(parse_role).transpose()?,
+        session,
+        since: since.as_deref().map(|date| parse_date(date, now())).transpose()?,
+        until: until.as_deref().map(|date| parse_date(date, now())).transpose()?,
+        limit,
+    })
+}
+
+fn run_search(config: &Config, query: anyhow::Result<SearchQuery>) -> ExitCode {
+    let Some(dir) = config.sessions_dir() else {
+        eprintln!("[ERROR] No data directory for sessions; set data_dir or CAI_DATA_DIR.");
+        return ExitCode::FAILURE;
+    };
+    let store = SessionStore::open(dir);
+    let hits = match query.and_then(|query| search(&store, &query)) {
+        Ok(hits) => hits,
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-                break 'mainloop;
+    if hits.is_empty() {
+        println!("[SYSTEM] No matches in {}", store.dir().display());
+    }
+    // Bold matches on a terminal, Markdown-style emphasis when piped
+    let (open, close) = if std::io::stdout().is_terminal() { ("\x1b[1m", "\x1b[0m") } else { ("**", "**") };
+    for hit in hits {
+        let title = if hit.title.is_empty() { String::new() } else { format!(" \"{}\"", hit.title) };
+        println!(
+            "{}{} #{} [{:?}] {}  score {:.2}\n  {}\n",
+            hit.session,
+            title,
+            hit.node,
+            hit.role,
+            format_date(hit.time),
+            hit.score,
+            hit.highlighted(open, close)
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_memory(config: &Config, action: StoreAction) -> ExitCode {
+    let mut memory: MemoryManager = MemoryManager::new(&config.memory_file);
+    match action {
+        StoreAction::Show => {
+            if memory.is_empty() {
+                println!("[SYSTEM] No memory in {}", config.memory_file);
+            }
+            for entry in memory.entries() {
+                let source = entry.source.as_deref().map(|id| format!(", session {}", id)).unwrap_or_default();
+                println!("{}\n    importance {:.1}, updated {}{}", entry.line(), entry.importance, ago(entry.updated), source);
+            }
+        }
+        StoreAction::Clear => {
+            if let Err(e) = memory.clear() {
+                eprintln!("[ERROR] {:#}", e);
+                return ExitCode::FAILURE;
+            }
+            println!("[SYSTEM] Memory cleared: {}", config.memory_file);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_checkpoints(config: &Config, action: CheckpointAction) -> ExitCode {
+    let result = CheckpointStore::open(config.checkpoint_dir())
+        .and_then(|mut store| checkpoint_action(&mut store, action));
+    match result {
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_sessions(config: &Config, action: SessionAction) -> ExitCode {
+    let Some(dir) = config.sessions_dir() else {
+        eprintln!("[ERROR] No data directory for sessions; set data_dir or CAI_DATA_DIR.");
+        return ExitCode::FAILURE;
+    };
+    let store = SessionStore::open(dir);
+    let result: anyhow::Result<String> = match action {
+        SessionAction::List => store.list().map(|sessions| {
+            if sessions.is_empty() {
+                return format!("[SYSTEM] No sessions in {}", store.dir().display());
             }
+            let cwd = working_dir().unwrap_or_default();
+            let current = sessions.iter().find(|meta| meta.cwd == cwd).map(|meta| meta.id.clone());
+            sessions
+                .iter()
+                .map(|meta| format!(
+                    "{} {:<12} {:>9}  {:<40}  {} ({})",
+                    if current.as_ref() == Some(&meta.id) { "*" } else { " " },
+                    meta.id,
+                    ago(meta.updated),
+                    if meta.title.is_empty() { "(untitled)" } else { meta.title.as_str() },
+                    meta.cwd.display(),
+                    meta.model
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }),
+        SessionAction::Rename { name, title } => store
+            .rename(&name, &title.join(" "))
+            .map(|meta| format!("[SYSTEM] Session {} renamed to {:?}.", meta.id, meta.title)),
+        SessionAction::Delete { name } => store
+            .delete(&name)
+            .map(|()| format!("[SYSTEM] Session {} deleted.", name)),
+        SessionAction::New { .. } | SessionAction::Resume { .. } => unreachable!("handled by run_chat"),
+    };
+    match result {
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-            // --- Continue token logic ---
-            if response.contains(CONTINUE_TOKEN) {
-                ui.print_message(
-                    MsgRole::System,
-                    MsgType::Plain("[SYSTEM] Continuing".to_string()),
-                );
-
-                if sys_message.is_empty() {
-                    response = assistant.send_message(ui, MessageRole::System, "[Continue]", None).await;
-                } else {
-                    response = assistant.send_message(ui, MessageRole::System, &sys_message, None).await;
+fn run_export(config: &Config, meta: Option<&SessionMeta>, output: Option<PathBuf>, format: Option<ExportFormat>) -> ExitCode {
+    let history: History = History::new(&config.history_file, config.max_history, config.summary_size());
+    if history.is_empty() {
+        eprintln!("[ERROR] No history in {}", config.history_file);
+        return ExitCode::FAILURE;
+    }
+    let messages = history.get();
+
+    let format = format
+        .or_else(|| output.as_deref().and_then(ExportFormat::from_path))
+        .unwrap_or(ExportFormat::Markdown);
+    let text = match format {
+        ExportFormat::Markdown => to_markdown(&messages, meta),
+        ExportFormat::Html => {
+            #[cfg(feature = "cli")]
+            let highlight = cai_cli::highlight_html;
+            #[cfg(not(feature = "cli"))]
+            let highlight = cai_core::export::plain_code_html;
+            to_html(&messages, meta, &highlight)
+        }
+        ExportFormat::Jsonl => to_jsonl(&messages),
+    };
+
+    match output {
+        None => print!("{}", text),
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, text) {
+                eprintln!("[ERROR] Cannot write {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+            println!("[SYSTEM] Exported {} messages to {}", messages.len(), path.display());
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_import(config: &Config, file: &Path, name: Option<&str>) -> ExitCode {
+    let Some(dir) = config.sessions_dir() else {
+        eprintln!("[ERROR] No data directory for sessions; set data_dir or CAI_DATA_DIR.");
+        return ExitCode::FAILURE;
+    };
+    let store = SessionStore::open(dir);
+    let result = std::fs::read_to_string(file)
+        .with_context(|| format!("cannot read {}", file.display()))
+        .and_then(|text| from_jsonl(&text))
+        .and_then(|conversations| {
+            let cwd = env::current_dir()?.canonicalize()?;
+            let single = conversations.len() == 1;
+            let mut imported = Vec::new();
+            for (i, messages) in conversations.into_iter().enumerate() {
+                let name = name.map(|name| if single { name.to_string() } else { format!("{}-{}", name, i + 1) });
+                let mut meta = store.create(name.as_deref(), &cwd, &config.model.clone().unwrap_or_default())?;
+                if let Some(first) = messages.iter().find(|m| matches!(m.role, MessageRole::User)) {
+                    meta.title_from(&first.content);
                 }
-                continues += 1;
-
-                // Enforce continue limit.
-                if continues >= MAX_CONTINUE {
-                    continues = 0;
-                    response = assistant.send_message(
-                        ui,
-                        MessageRole::System,
-                        "[You've reached the maximum number of continues.]",
-                        None
-                    ).await;
-                    break 'processing_loop;
+                store.touch(&mut meta)?;
+
+                let history_file = config.storage.path_for(&store.history_file(&meta.id)?.to_string_lossy());
+                let mut history = History::new(&history_file, config.max_history, config.summary_size());
+                history.set_index(index_file(&store), &meta.id);
+                let count = messages.len();
+                for message in messages {
+                    history.push(message);
                 }
+                history.save();
+                imported.push(format!("[SYSTEM] Imported {} messages into session {}", count, meta.id));
+            }
+            Ok(imported)
+        });
 
-                continue 'processing_loop;
-            } else {
-                // If there was a system message, send it and continue processing.
-                if !sys_message.is_empty() {
-                    response = assistant.send_message(ui, MessageRole::System, &sys_message, None).await;
-                }
-                // Reset continue counter if needed.
-                if continues != 0 {
-                    continues = 0;
-                }
-                // If no further processing is needed, break.
-                if sys_message.is_empty() {
-                    break 'processing_loop;
-                } else {
-                    continue 'processing_loop;
-                }
+    match result {
+        Ok(imported) if imported.is_empty() => {
+            eprintln!("[ERROR] No conversations in {}", file.display());
+            ExitCode::FAILURE
+        }
+        Ok(imported) => {
+            println!("{}", imported.join("\n"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("[ERROR] {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_config(config: &Config, action: ConfigAction) -> ExitCode {
+    match action {
+        ConfigAction::Show => {
+            for source in &config.sources {
+                println!("# from {}", source.display());
             }
+            print!("{}", config.to_toml());
+        }
+        ConfigAction::Path => {
+            let describe = |path: Option<PathBuf>| match path {
+                Some(path) if path.is_file() => path.display().to_string(),
+                Some(path) => format!("{} (not found)", path.display()),
+                None => "(not found)".to_string(),
+            };
+            println!("user:    {}", describe(user_config_path()));
+            println!("project: {}", describe(env::current_dir().ok().and_then(|cwd| find_project_config(&cwd))));
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+
+
+// ===================== Helpers =====================
+
+/// How a command picks its session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionMode {
+    /// Create a new one.
+    New,
+
+    /// The named one (created when missing), else the latest for the
+    /// cwd, else a new one.
+    Open,
+
+    /// The named one, else the latest for the cwd -- which must exist.
+    Resume,
+
+    /// Like `Resume`, but without a session is fine.
+    Existing,
+}
+
+/// Picks the session for this run and points `history_file` into it.
+/// `None` → no session applies (or no data directory).
+fn select_session(
+    config: &mut Config,
+    mode: SessionMode,
+    name: Option<&str>
+) -> anyhow::Result<Option<(SessionStore, SessionMeta)>> {
+    let Some(dir) = config.sessions_dir() else {
+        if mode != SessionMode::Existing {
+            eprintln!("[WARNING] No data directory; using {} instead of a session.", config.history_file);
+        }
+        return Ok(None);
+    };
+    let store = SessionStore::open(dir);
+    let cwd = env::current_dir()?.canonicalize()?;
+    let model = config.model.clone().unwrap_or_default();
+
+    let meta = match (mode, name) {
+        (SessionMode::New, name) => Some(store.create(name, &cwd, &model)?),
+        (SessionMode::Open, Some(name)) => match store.get(name) {
+            Ok(meta) => Some(meta),
+            Err(_) => Some(store.create(Some(name), &cwd, &model)?),
+        },
+        (SessionMode::Open, None) => match store.latest_for(&cwd)? {
+            Some(meta) => Some(meta),
+            None => Some(store.create(None, &cwd, &model)?),
+        },
+        (SessionMode::Resume | SessionMode::Existing, Some(name)) => Some(store.get(name)?),
+        (SessionMode::Resume, None) => Some(store.latest_for(&cwd)?.ok_or_else(|| {
+            anyhow::anyhow!("no session for {} -- see `cai sessions`", cwd.display())
+        })?),
+        (SessionMode::Existing, None) => store.latest_for(&cwd)?,
+    };
+
+    let Some(meta) = meta else { return Ok(None) };
+    config.history_file = config.storage.path_for(&store.history_file(&meta.id)?.to_string_lossy());
+    Ok(Some((store, meta)))
+}
+
+/// `1700000000` → `3d ago`
+fn ago(time: u64) -> String {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    match now.saturating_sub(time) {
+        secs if secs < 60 => "just now".to_string(),
+        secs if secs < 3600 => format!("{}m ago", secs / 60),
+        secs if secs < 86_400 => format!("{}h ago", secs / 3600),
+        secs => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Runs a checkpoint command, returning what to print.
+fn checkpoint_action(store: &mut CheckpointStore, action: CheckpointAction) -> anyhow::Result<String> {
+    let list = |paths: &[PathBuf]| paths.iter().map(|p| format!("  {}", p.display())).collect::<Vec<_>>().join("\n");
+    Ok(match action {
+        CheckpointAction::List => {
+            if store.list().is_empty() {
+                return Ok(format!("[SYSTEM] No checkpoints in {}", store.dir().display()));
+            }
+            store.list()
+                .iter()
+                .map(|c| format!(
+                    "#{:<4} turn {:<3} {}{}",
+                    c.id,
+                    c.turn,
+                    c.path.display(),
+                    if c.before.is_none() { " (new)" } else { "" }
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        CheckpointAction::Diff { id } => store.diff(id)?,
+        CheckpointAction::Restore { id } => {
+            format!("[SYSTEM] Restored {} to before checkpoint #{}.", store.restore(id)?.display(), id)
+        }
+        CheckpointAction::RestoreTurn { turn } => {
+            format!("[SYSTEM] Restored turn {}:\n{}", turn, list(&store.restore_turn(turn)?))
+        }
+        CheckpointAction::Undo => {
+            let (turn, paths) = store.undo()?;
+            format!("[SYSTEM] Undid turn {}:\n{}", turn, list(&paths))
+        }
+    })
+}
+
+/// Runs a branch command. Returns the new reply when one was generated
+/// (`/edit`, `/regen`).
+async fn branch_command(ui: &dyn UIBase, assistant: &mut Client, command: BranchCommand) -> anyhow::Result<Option<String>> {
+    let history = &mut assistant.history;
+    let output = match command {
+        BranchCommand::Tree => history.render_tree(),
+        BranchCommand::Fork { id } => {
+            history.checkout(id)?;
+            format!("[SYSTEM] Continuing from #{}; your next message starts a new branch.", id)
+        }
+        BranchCommand::Switch { id } => {
+            history.switch(id)?;
+            let last = history.get().last().map(|m| m.content.clone()).unwrap_or_default();
+            format!("[SYSTEM] Switched to the branch ending at #{}:\n{}", history.cursor().unwrap_or(id), last)
+        }
+        BranchCommand::Edit { id, text } => {
+            history.edit(id, text)?;
+            return Ok(Some(assistant.respond(ui, None).await));
+        }
+        BranchCommand::Regen => {
+            history.retry_last_reply()?;
+            return Ok(Some(assistant.respond(ui, None).await));
+        }
+    };
+    history.save();
+    ui.print_message(MsgRole::System, MsgType::Plain(output));
+    Ok(None)
+}
+
+/// Ensures an API key is set when talking to the vendor's official API.
+fn check_api_key(endpoint: &Endpoint) -> bool {
+    if endpoint.requires_api_key() && endpoint.api_key.is_none() {
+        eprintln!("[ERROR] {} not set in the environment.", endpoint.api_key_var());
+        eprintln!("Please set it before running the program, or point OPENAI_BASE_URL at a local server.");
+        return false;
+    }
+    true
+}
+
+/// Attaches the shell session and the workspace rooted at `dir`, with the
+/// session's checkpoint store.
+fn attach_session(assistant: &mut Client, config: &Config, dir: &Path) -> bool {
+    match Shell::new(&dir.to_string_lossy()) {
+        Ok(shell) => { assistant.attach_shell(shell.with_timeout(config.command_timeout()).with_rules(config.command_rules())); }
+        Err(e) => {
+            eprintln!("[ERROR] Failed to create shell: {}", e);
+            return false;
+        }
+    }
+    let workspace = Workspace::new(dir)
+        .and_then(|workspace| workspace.with_writable(&config.writable_dirs))
+        .and_then(|workspace| Ok(workspace.with_checkpoints(CheckpointStore::open(config.checkpoint_dir())?)));
+    match workspace {
+        Ok(workspace) => { assistant.attach_workspace(workspace); }
+        Err(e) => {
+            eprintln!("[ERROR] Invalid workspace: {:#}", e);
+            return false;
+        }
+    }
+    true
+}
+
+/// The prompt from the arguments, followed by stdin when it's piped.
+/// `None` when both are empty.
+fn read_prompt(args: &str) -> Option<String> {
+    let mut stdin = String::new();
+    if !std::io::stdin().is_terminal() {
+        if let Err(e) = std::io::stdin().read_to_string(&mut stdin) {
+            eprintln!("[WARNING] Couldn't read stdin: {}", e);
         }
     }
 
-    Ok(())
-}
\ No newline at end of file
+    match (args.trim(), stdin.trim()) {
+        ("", "") => None,
+        (args, "") => Some(args.to_string()),
+        ("", stdin) => Some(stdin.to_string()),
+        (args, stdin) => Some(format!("{}\n\n{}", args, stdin)),
+    }
+}
+
+/// Absolute path of the current working directory.
+fn working_dir() -> Option<PathBuf> {
+    match env::current_dir().and_then(|dir| dir.canonicalize()) {
+        // The shell session takes it as text, which has to be exact
+        Ok(path) if path.to_str().is_none() => {
+            eprintln!("[ERROR] Current dir path is not valid UTF-8: {}", path.display());
+            None
+        }
+        Ok(path) => Some(path),
+        Err(e) => {
+            eprintln!("[ERROR] Couldn't obtain current dir path: {}", e);
+            None
+        }
+    }
+}
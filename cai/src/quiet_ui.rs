@@ -0,0 +1,45 @@
+//! Output-free UI for one-shot runs (`cai ask`).
+//!
+//! stdout is reserved for the final answer, so nothing is printed while
+//! the agent works -- except errors, which go to stderr. Nobody is there
+//! to approve actions, so everything the approval mode asks for is rejected.
+
+use cai_core::{
+    approval::{ ApprovalDecision, ApprovalRequest },
+    ui_trait::{ MsgRole, MsgType, UIBase },
+};
+
+pub struct QuietUI;
+
+impl UIBase for QuietUI {
+    fn init(&self) -> bool {
+        true
+    }
+
+    /// Never asked in one-shot mode; quitting is the only sane answer.
+    fn get_user_input(&self) -> String {
+        "q".to_string()
+    }
+
+    fn print_message(&self, message_type: MsgRole, message_format: MsgType) {
+        if !matches!(message_type, MsgRole::Error) {
+            return;
+        }
+        match message_format {
+            MsgType::Plain(text) | MsgType::Diff(text) => eprintln!("{}", text),
+            MsgType::TitleChildren { title, content } => {
+                eprintln!("{}", title);
+                for child in content {
+                    eprintln!("{}", child);
+                }
+            }
+        }
+    }
+
+    fn request_approval(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        eprintln!("[SYSTEM] Rejected {}: `{}` (non-interactive run)", request.source, request.summary);
+        ApprovalDecision::Reject {
+            feedback: Some("Running non-interactively; the user can pass `--approval auto` to allow this.".to_string()),
+        }
+    }
+}